@@ -1,350 +1,1265 @@
-use std::time::Duration;
-
-use chrono::Local;
-use tokio::time::sleep;
+use chrono::{DateTime, Local, TimeDelta};
+use log::debug;
+use sf_api::{
+    command::{
+        Command as SFCommand, ExpeditionSetting, FortunePayment, TimeSkip,
+    },
+    gamestate::{
+        GameState,
+        dungeons::{Dungeon, DungeonProgress, LightDungeon, ShadowDungeon},
+        items::{Enchantment, PlayerItemPlace},
+        rewards::{Reward, RewardType},
+        tavern::{AvailableTasks, CurrentAction, ExpeditionStage},
+        underworld::UnderworldResourceType,
+        unlockables::{HabitatExploration, HabitatType, Pets, ScrapBook},
+    },
+    misc::EnumMapGet,
+};
+use strum::IntoEnumIterator;
 
 use crate::{
-    config::{CharacterConfig, MissionStrategy},
+    AccountIdent,
+    config::{
+        CharacterConfig, DungeonTarget, ExpeditionRewardKind,
+        ExpeditionRewardPriority, MissionStrategy, QuestingPreference,
+    },
+    player::until_next_server_day,
 };
 
-/// Adapter to pass per-character config into automation without borrowing issues.
+/// A mission, as the mission picker sees it
 #[derive(Debug, Clone)]
-pub struct AutomationCfg {
-    pub auto_tavern: bool,
-    pub auto_expeditions: bool,
-    pub auto_dungeons: bool,
-    pub auto_pets: bool,
-    pub mission_strategy: MissionStrategy,
+pub struct Quest {
+    pub id: u32,
+    pub seconds: u32,
+    pub gold: u64,
+    pub xp: u64,
+    pub mushrooms: u8,
 }
 
-impl From<&CharacterConfig> for AutomationCfg {
-    fn from(c: &CharacterConfig) -> Self {
+impl Quest {
+    /// Converts the quest at `pos` in the tavern. Quests never cost
+    /// mushrooms, only thirst
+    pub fn from_tavern(
+        pos: usize,
+        quest: &sf_api::gamestate::tavern::Quest,
+    ) -> Self {
         Self {
-            auto_tavern: c.auto_tavern,
-            auto_expeditions: c.auto_expeditions,
-            auto_dungeons: c.auto_dungeons,
-            auto_pets: c.auto_pets,
-            mission_strategy: c.mission_strategy,
+            id: pos as u32,
+            seconds: quest.base_length,
+            gold: u64::from(quest.base_silver),
+            xp: u64::from(quest.base_experience),
+            mushrooms: 0,
         }
     }
 }
 
-pub struct TickOutcome {
-    pub did_something: bool,
-    pub summary: String,
+/// The timers automation decides on, that are not directly available on
+/// the `GameState`. Timers without a value count as ready
+pub trait GameStateLike {
+    fn tavern_end_time(&self) -> Option<DateTime<Local>>;
+    fn expedition_end_time(&self) -> Option<DateTime<Local>>;
+    fn dungeon_ready(&self, now: DateTime<Local>) -> bool;
+    fn pet_pvp_ready(&self, now: DateTime<Local>) -> bool;
+    fn pet_exploration_ready(&self, now: DateTime<Local>) -> bool;
 }
 
-/// These mirror the data you should get back from your session listing endpoints.
-#[derive(Debug, Clone)]
-pub struct Quest {
-    pub id: u32,
-    pub minutes: u32,
-    pub gold: u64,
-    pub xp: u64,
-    pub mushrooms: u8,
-}
-#[derive(Debug, Clone)]
-pub struct Expedition {
-    pub id: u32,
-    pub minutes: u32,
-    pub gold: u64,
-    pub xp: u64,
-    pub mushrooms: u8,
+impl GameStateLike for GameState {
+    fn tavern_end_time(&self) -> Option<DateTime<Local>> {
+        match &self.tavern.current_action {
+            CurrentAction::Quest { busy_until, .. } => Some(*busy_until),
+            _ => None,
+        }
+    }
+
+    fn expedition_end_time(&self) -> Option<DateTime<Local>> {
+        match self.tavern.expeditions.active()?.current_stage() {
+            ExpeditionStage::Waiting(until) => Some(until),
+            _ => None,
+        }
+    }
+
+    fn dungeon_ready(&self, now: DateTime<Local>) -> bool {
+        self.dungeons.next_free_fight.is_none_or(|t| t <= now)
+    }
+
+    fn pet_pvp_ready(&self, now: DateTime<Local>) -> bool {
+        self.pets.as_ref().is_some_and(|p| {
+            p.opponent.next_free_battle.is_none_or(|t| t <= now)
+        })
+    }
+
+    fn pet_exploration_ready(&self, now: DateTime<Local>) -> bool {
+        self.pets
+            .as_ref()
+            .is_some_and(|p| p.next_free_exploration.is_none_or(|t| t <= now))
+    }
 }
 
-/// Runs a single automation tick for a character.
-/// Expected available calls on your session:
-/// - collect_tavern_reward, list_tavern, start_tavern
-/// - collect_expedition_reward, list_expeditions, start_expedition
-/// - next_dungeon_candidate, fight_dungeon
-/// - next_pet_candidate, fight_pet
-/// - refresh_gamestate
-pub async fn tick<S, GS>(
-    cfg: &AutomationCfg,
-    session: &mut S,
-    gs: &mut GS,
-) -> TickOutcome
-where
-    S: SessionLike,
-    GS: GameStateLike,
-{
-    let mut did = false;
-    let mut parts: Vec<String> = Vec::new();
+/// The groups of actions automation chooses between, when nothing else has
+/// to be done first
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ActionGroup {
+    Tavern,
+    Dungeons,
+    Pets,
+    Guild,
+}
 
-    if cfg.auto_tavern {
-        if let Some(s) = tavern_tick(cfg, session, gs).await { did = true; parts.push(s); }
+/// The order the action groups are evaluated in. Unless randomized, the
+/// tavern always comes first
+pub fn action_order(randomize: bool) -> [ActionGroup; 4] {
+    let mut order = [
+        ActionGroup::Tavern,
+        ActionGroup::Dungeons,
+        ActionGroup::Pets,
+        ActionGroup::Guild,
+    ];
+    if randomize {
+        fastrand::shuffle(&mut order);
     }
-    if cfg.auto_expeditions {
-        if let Some(s) = expedition_tick(cfg, session, gs).await { did = true; parts.push(s); }
+    order
+}
+
+/// Everything automation looks at to decide on the next command of a
+/// character
+pub struct TickContext<'a> {
+    pub ident: AccountIdent,
+    pub gs: &'a GameState,
+    pub cfg: &'a CharacterConfig,
+    /// The current time on the server
+    pub now: DateTime<Local>,
+    /// Mushrooms of the shared budget may be spent on beer and skips
+    pub can_spend_mush: bool,
+    /// Another hydra attempt may be paid for with a mushroom
+    pub hydra_use_mush: bool,
+    pub scrapbook: Option<&'a ScrapBook>,
+    /// When the underworld souls were last collected
+    pub souls_collected: Option<DateTime<Local>>,
+    /// The player automation lures into the underworld next
+    pub lure_target: Option<u32>,
+    /// Lures with a target are left for today
+    pub lures_pending: bool,
+    /// The order the action groups are evaluated in, see `action_order()`
+    pub order: [ActionGroup; 4],
+}
+
+/// Decides on the next command of a character. The action groups are
+/// evaluated in the order of the context. The daily rewards and the
+/// underworld only come after them. `None`, if there is nothing to do right
+/// now
+pub fn tick(ctx: &TickContext) -> Option<SFCommand> {
+    let cmd = ctx
+        .order
+        .into_iter()
+        .find_map(|group| match group {
+            ActionGroup::Tavern => tavern_action(ctx),
+            ActionGroup::Dungeons => dungeon_action(ctx),
+            ActionGroup::Pets => pet_action(ctx),
+            ActionGroup::Guild => guild_action(ctx),
+        })
+        .or_else(|| extra_action(ctx));
+    if cmd.is_none() {
+        log_idle_summary(ctx);
     }
-    if cfg.auto_dungeons {
-        if let Some(s) = dungeon_tick(session, gs).await { did = true; parts.push(s); }
+    cmd
+}
+
+/// Finishes and continues whatever the tavern is busy with, or starts
+/// something new, once it is idle
+fn tavern_action(ctx: &TickContext) -> Option<SFCommand> {
+    let TickContext {
+        ident,
+        gs,
+        cfg,
+        now,
+        ..
+    } = *ctx;
+    match &gs.tavern.current_action {
+        CurrentAction::Quest { busy_until, .. } => {
+            if *busy_until <= now {
+                return Some(SFCommand::FinishQuest { skip: None });
+            }
+            // Long waits are only ever skipped with glasses, never with
+            // mushrooms
+            let remaining = (*busy_until - now).to_std().unwrap_or_default();
+            if remaining.as_secs() <= 60 {
+                return None;
+            }
+            if cfg.use_glasses_for_tavern && gs.tavern.quicksand_glasses > 0 {
+                debug!(
+                    "Automation {ident:?}: Quest waiting {}s -> skip with \
+                     glass",
+                    remaining.as_secs()
+                );
+                Some(SFCommand::FinishQuest {
+                    skip: Some(TimeSkip::Glass),
+                })
+            } else {
+                debug!(
+                    "Automation {ident:?}: Quest waiting {}s -> no skip \
+                     (tavern glasses disabled or none available)",
+                    remaining.as_secs()
+                );
+                None
+            }
+        }
+        CurrentAction::Expedition => expedition_action(ctx),
+        CurrentAction::CityGuard { busy_until, .. } => {
+            if *busy_until > now {
+                return None;
+            }
+            debug!("Automation {ident:?}: CityGuard finished -> FinishWork");
+            Some(SFCommand::FinishWork)
+        }
+        CurrentAction::Unknown(_) | CurrentAction::Idle => idle_action(ctx),
     }
-    if cfg.auto_pets {
-        if let Some(s) = pets_tick(session, gs).await { did = true; parts.push(s); }
+}
+
+fn expedition_action(ctx: &TickContext) -> Option<SFCommand> {
+    let TickContext {
+        ident,
+        gs,
+        cfg,
+        now,
+        ..
+    } = *ctx;
+    match gs.tavern.expeditions.active()?.current_stage() {
+        ExpeditionStage::Boss(_) => {
+            debug!("Automation {ident:?}: Expedition boss -> continue");
+            Some(SFCommand::ExpeditionContinue)
+        }
+        ExpeditionStage::Rewards(rewards) => {
+            let prio = cfg.expedition_reward_priority;
+            let Some(pos) = pick_expedition_reward(&rewards, prio) else {
+                debug!("Automation {ident:?}: Expedition rewards empty");
+                return None;
+            };
+            debug!(
+                "Automation {ident:?}: Expedition pick reward index {pos} of \
+                 {} (priority {prio:?})",
+                rewards.len()
+            );
+            Some(SFCommand::ExpeditionPickReward { pos })
+        }
+        ExpeditionStage::Encounters(encs) => {
+            if encs.is_empty() {
+                debug!("Automation {ident:?}: Expedition encounters empty");
+                return None;
+            }
+            debug!(
+                "Automation {ident:?}: Expedition pick first encounter ({} \
+                 options)",
+                encs.len()
+            );
+            Some(SFCommand::ExpeditionPickEncounter { pos: 0 })
+        }
+        ExpeditionStage::Waiting(until) => {
+            let remaining = (until - now).to_std().unwrap_or_default();
+            if cfg.use_glasses_for_expeditions
+                && remaining.as_secs() > 60
+                && gs.tavern.quicksand_glasses > 0
+            {
+                debug!(
+                    "Automation {ident:?}: Expedition waiting {}s -> skip \
+                     with glass",
+                    remaining.as_secs()
+                );
+                Some(SFCommand::ExpeditionSkipWait {
+                    typ: TimeSkip::Glass,
+                })
+            } else {
+                debug!(
+                    "Automation {ident:?}: Expedition waiting {}s -> no skip",
+                    remaining.as_secs()
+                );
+                None
+            }
+        }
+        ExpeditionStage::Finished | ExpeditionStage::Unknown => None,
     }
+}
 
-    TickOutcome { did_something: did, summary: parts.join(" | ") }
+/// The amount of beer, that can be drunk per day
+fn beer_cap(gs: &GameState) -> u8 {
+    let extra = gs
+        .character
+        .equipment
+        .has_enchantment(Enchantment::ThirstyWanderer);
+    10 + extra as u8
 }
 
-async fn tavern_tick<S, GS>(
-    cfg: &AutomationCfg,
-    session: &mut S,
-    gs: &mut GS,
-) -> Option<String>
-where
-    S: SessionLike,
-    GS: GameStateLike,
-{
-    if gs.tavern_end_time().map(|t| t <= Local::now()).unwrap_or(false) {
-        let _ = session.collect_tavern_reward().await.ok()?;
-        let _ = session.refresh_gamestate(gs).await.ok()?;
-    }
-    if gs.tavern_end_time().is_none() || gs.tavern_end_time().unwrap() <= Local::now() {
-        let quests = session.list_tavern().await.ok()?;
-        let pick = pick_mission(
-            quests.into_iter().map(|q| Quest {
-                id: q.id(),
-                minutes: q.minutes(),
-                gold: q.gold(),
-                xp: q.xp(),
-                mushrooms: q.mushrooms(),
-            }).collect(),
-            cfg.mission_strategy
+/// Starts the next quest, expedition or city guard shift
+fn idle_action(ctx: &TickContext) -> Option<SFCommand> {
+    let TickContext { ident, gs, cfg, .. } = *ctx;
+    // An explicit preference is enforced, before anything is started
+    let preferred = match cfg.questing_preference {
+        QuestingPreference::Auto => None,
+        QuestingPreference::PreferQuests => {
+            Some(ExpeditionSetting::PreferQuests)
+        }
+        QuestingPreference::PreferExpeditions => {
+            Some(ExpeditionSetting::PreferExpeditions)
+        }
+    };
+    if (cfg.auto_tavern || cfg.auto_expeditions)
+        && let Some(value) = preferred
+        && gs.tavern.questing_preference != value
+        && gs.tavern.can_change_questing_preference()
+    {
+        debug!(
+            "Automation {ident:?}: Switching questing preference to {value:?}"
+        );
+        return Some(SFCommand::SetQuestsInsteadOfExpeditions { value });
+    }
+
+    // A full backpack blocks item rewards of quests
+    if cfg.auto_sell_junk
+        && let Some(scrapbook) = ctx.scrapbook
+        && let Some(pos) =
+            pick_junk_item(gs, scrapbook, cfg.sell_junk_max_attributes)
+    {
+        debug!(
+            "Automation {ident:?}: Selling junk item at backpack position {pos}"
         );
-        if let Some(q) = pick {
-            let _ = session.start_tavern(q.id).await.ok()?;
-            let _ = session.refresh_gamestate(gs).await.ok()?;
-            return Some(format!("tavern:{}m", q.minutes));
+        return Some(SFCommand::SellShop {
+            inventory: PlayerItemPlace::MainInventory,
+            inventory_pos: pos,
+        });
+    }
+
+    let task = match gs.tavern.available_tasks() {
+        AvailableTasks::Expeditions(_) if cfg.auto_expeditions => {
+            expedition_start(ctx)
         }
+        AvailableTasks::Quests(_) if cfg.auto_tavern => quest_start(ctx),
+        _ => None,
+    };
+    if task.is_some() || !(cfg.auto_tavern || cfg.auto_expeditions) {
+        return task;
     }
-    None
+    city_guard_start(ctx)
 }
 
-async fn expedition_tick<S, GS>(
-    cfg: &AutomationCfg,
-    session: &mut S,
-    gs: &mut GS,
-) -> Option<String>
-where
-    S: SessionLike,
-    GS: GameStateLike,
-{
-    if gs.expedition_end_time().map(|t| t <= Local::now()).unwrap_or(false) {
-        let _ = session.collect_expedition_reward().await.ok()?;
-        let _ = session.refresh_gamestate(gs).await.ok()?;
-    }
-    if gs.expedition_end_time().is_none() || gs.expedition_end_time().unwrap() <= Local::now() {
-        let exps = session.list_expeditions().await.ok()?;
-        let pick = pick_mission(
-            exps.into_iter().map(|e| Expedition {
-                id: e.id(),
-                minutes: e.minutes(),
-                gold: e.gold(),
-                xp: e.xp(),
-                mushrooms: e.mushrooms(),
-            }).collect(),
-            cfg.mission_strategy
+fn expedition_start(ctx: &TickContext) -> Option<SFCommand> {
+    let TickContext { ident, gs, cfg, .. } = *ctx;
+    if cfg.questing_preference == QuestingPreference::Auto
+        && gs.tavern.questing_preference == ExpeditionSetting::PreferQuests
+        && gs.tavern.can_change_questing_preference()
+    {
+        debug!("Automation {ident:?}: Switching to Expeditions");
+        return Some(SFCommand::SetQuestsInsteadOfExpeditions {
+            value: ExpeditionSetting::PreferExpeditions,
+        });
+    }
+    if gs.tavern.thirst_for_adventure_sec == 0 {
+        return None;
+    }
+    debug!("Automation {ident:?}: Starting Expedition 0");
+    Some(SFCommand::ExpeditionStart { pos: 0 })
+}
+
+fn quest_start(ctx: &TickContext) -> Option<SFCommand> {
+    let TickContext { ident, gs, cfg, .. } = *ctx;
+    let AvailableTasks::Quests(qs) = gs.tavern.available_tasks() else {
+        return None;
+    };
+    // Item rewards only overwrite something, if the inventory is full
+    let overwrite_inv = cfg.quest_overwrite_inventory;
+    let has_free_slot = gs.character.inventory.free_slot().is_some();
+    let quests: Vec<_> = qs
+        .iter()
+        .enumerate()
+        .filter(|(_, q)| overwrite_inv || has_free_slot || q.item.is_none())
+        .map(|(i, q)| Quest::from_tavern(i, q))
+        .collect();
+    let Some(pick) = pick_mission(quests.iter().cloned(), cfg.mission_strategy)
+    else {
+        debug!(
+            "Automation {ident:?}: Every quest would overwrite an inventory \
+             item -> waiting"
+        );
+        return None;
+    };
+    let quest_pos = pick.id as usize;
+    let thirst = gs.tavern.thirst_for_adventure_sec;
+    if pick.seconds <= thirst {
+        debug!(
+            "Automation {ident:?}: Starting quest {quest_pos} (len {}s)",
+            pick.seconds
+        );
+        return Some(SFCommand::StartQuest {
+            quest_pos,
+            overwrite_inv,
+        });
+    }
+
+    let beer_cap = beer_cap(gs);
+    if cfg.auto_buy_beer_mushrooms
+        && cfg.max_mushrooms_beer > 0
+        && ctx.can_spend_mush
+        && gs.tavern.beer_drunk < beer_cap
+    {
+        debug!(
+            "Automation {ident:?}: Buying beer (drunk {}, cap {beer_cap})",
+            gs.tavern.beer_drunk
+        );
+        return Some(SFCommand::BuyBeer);
+    }
+    let fitting = quests.into_iter().filter(|q| q.seconds <= thirst);
+    let Some(fallback) = pick_mission(fitting, cfg.mission_strategy) else {
+        debug!(
+            "Automation {ident:?}: No quest fits remaining thirst \
+             ({thirst}s) and not buying beer -> waiting"
+        );
+        return None;
+    };
+    debug!(
+        "Automation {ident:?}: Fallback quest {} within thirst (len {}s)",
+        fallback.id, fallback.seconds
+    );
+    Some(SFCommand::StartQuest {
+        quest_pos: fallback.id as usize,
+        overwrite_inv,
+    })
+}
+
+/// Starts the city guard, once the thirst is used up and no more beer can
+/// be bought
+fn city_guard_start(ctx: &TickContext) -> Option<SFCommand> {
+    let TickContext { ident, gs, cfg, .. } = *ctx;
+    if gs.tavern.thirst_for_adventure_sec > 0 {
+        return None;
+    }
+    let beer_cap = beer_cap(gs);
+    let can_buy_beer = cfg.auto_buy_beer_mushrooms
+        && cfg.max_mushrooms_beer > 0
+        && ctx.can_spend_mush
+        && gs.tavern.beer_drunk < beer_cap;
+    if can_buy_beer {
+        debug!(
+            "Automation {ident:?}: Thirst empty but beer available (drunk {} \
+             / cap {beer_cap}, mushrooms {}) -> no CityGuard",
+            gs.tavern.beer_drunk, gs.character.mushrooms
         );
-        if let Some(e) = pick {
-            let _ = session.start_expedition(e.id).await.ok()?;
-            let _ = session.refresh_gamestate(gs).await.ok()?;
-            return Some(format!("expedition:{}m", e.minutes));
+        return None;
+    }
+    // Nothing refills thirst before the next server day
+    let thirst_gap = until_next_server_day(gs);
+    let min_gap = TimeDelta::minutes(cfg.min_guard_thirst_gap_min.into());
+    if thirst_gap < min_gap {
+        debug!(
+            "Automation {ident:?}: Thirst refills in {}min -> no CityGuard",
+            thirst_gap.num_minutes()
+        );
+        return None;
+    }
+    let hours = cfg.city_guard_hours();
+    debug!(
+        "Automation {ident:?}: Thirst empty and beer exhausted/unavailable -> \
+         Start {hours}h CityGuard"
+    );
+    Some(SFCommand::StartWork { hours })
+}
+
+fn dungeon_action(ctx: &TickContext) -> Option<SFCommand> {
+    let TickContext {
+        ident,
+        gs,
+        cfg,
+        now,
+        ..
+    } = *ctx;
+    if !cfg.any_dungeon_fights() {
+        return None;
+    }
+    if cfg.auto_portal()
+        && gs.dungeons.portal.as_ref().is_some_and(|p| p.can_fight)
+    {
+        debug!("Automation {ident:?}: Portal fight ready");
+        return Some(SFCommand::FightPortal);
+    }
+    if !(cfg.auto_tower() || cfg.auto_dungeons_other()) {
+        return None;
+    }
+    let use_mush = if gs.dungeon_ready(now) {
+        false
+    } else if cfg.max_mushrooms_dungeon_skip > 0 && ctx.can_spend_mush {
+        debug!(
+            "Automation {ident:?}: Dungeons not ready, using mushroom to skip"
+        );
+        true
+    } else {
+        return None;
+    };
+    let fight = dungeon_fight(gs, cfg, use_mush);
+    match &fight {
+        Some(fight) => {
+            debug!("Automation {ident:?}: Dungeon fight chosen: {fight:?}");
         }
+        None => debug!(
+            "Automation {ident:?}: Dungeons ready but no matching open \
+             dungeon/tower found"
+        ),
     }
-    None
+    fight
 }
 
-async fn dungeon_tick<S, GS>(session: &mut S, gs: &mut GS) -> Option<String>
-where
-    S: SessionLike,
-    GS: GameStateLike,
-{
-    if !gs.dungeon_ready() { return None; }
-    let next = session.next_dungeon_candidate(gs).await.ok()?;
-    if let Some(d) = next {
-        let r = session.fight_dungeon(d.ident()).await.ok()?;
-        let _ = session.refresh_gamestate(gs).await.ok()?;
-        return Some(format!("dungeon:{}:{}", d.ident(), if r.win() {"win"} else {"lose"}));
+/// The next dungeon fight for the configured target. `None`, if no open
+/// dungeon matches the target, or the matching ones are toggled off
+fn dungeon_fight(
+    gs: &GameState,
+    cfg: &CharacterConfig,
+    use_mush: bool,
+) -> Option<SFCommand> {
+    let tower = || match gs.dungeons.progress(LightDungeon::Tower) {
+        _ if !cfg.auto_tower() => None,
+        DungeonProgress::Open { finished } => Some(SFCommand::FightTower {
+            current_level: finished as u8,
+            use_mush,
+        }),
+        _ => None,
+    };
+    let fight = |dungeon: Dungeon| SFCommand::FightDungeon {
+        dungeon,
+        use_mushroom: use_mush,
+    };
+
+    // All open dungeons, except for the tower
+    let open: Vec<(Dungeon, u16)> = LightDungeon::iter()
+        .filter(|a| *a != LightDungeon::Tower)
+        .map(Dungeon::from)
+        .chain(ShadowDungeon::iter().map(Dungeon::from))
+        .filter(|_| cfg.auto_dungeons_other())
+        .filter_map(|d| match gs.dungeons.progress(d) {
+            DungeonProgress::Open { finished } => Some((d, finished)),
+            _ => None,
+        })
+        .collect();
+    let lowest = || open.iter().min_by_key(|a| a.1).map(|a| fight(a.0));
+    let specific = |dungeon: Dungeon| {
+        open.iter().find(|a| a.0 == dungeon).map(|a| fight(a.0))
+    };
+
+    match cfg.dungeon_target {
+        DungeonTarget::Auto => tower().or_else(lowest),
+        DungeonTarget::TowerOnly
+        | DungeonTarget::SpecificLight(LightDungeon::Tower) => tower(),
+        DungeonTarget::SpecificLight(d) => specific(d.into()),
+        DungeonTarget::SpecificShadow(d) => specific(d.into()),
+        DungeonTarget::LowestFirst => lowest(),
+        DungeonTarget::HighestFirst => {
+            open.iter().max_by_key(|a| a.1).map(|a| fight(a.0))
+        }
     }
-    None
 }
 
-async fn pets_tick<S, GS>(session: &mut S, gs: &mut GS) -> Option<String>
-where
-    S: SessionLike,
-    GS: GameStateLike,
-{
-    if !gs.pet_ready() { return None; }
-    let cand = session.next_pet_candidate(gs).await.ok()?;
-    if let Some(p) = cand {
-        let r = session.fight_pet(p.element(), p.slot()).await.ok()?;
-        let _ = session.refresh_gamestate(gs).await.ok()?;
-        return Some(format!("pet:{}:{}", p.element_str(), if r.win() {"win"} else {"lose"}));
+/// Fights the pet opponent, or else explores the habitats
+fn pet_action(ctx: &TickContext) -> Option<SFCommand> {
+    let TickContext {
+        ident,
+        gs,
+        cfg,
+        now,
+        ..
+    } = *ctx;
+    if !cfg.auto_pets {
+        return None;
     }
-    None
+    let pets = gs.pets.as_ref()?;
+    if gs.pet_pvp_ready(now) {
+        match pet_pvp_habitat(pets, cfg) {
+            Some(habitat) => {
+                debug!("Automation {ident:?}: Pets PvP habitat {habitat:?}");
+                return Some(SFCommand::FightPetOpponent {
+                    habitat,
+                    opponent_id: pets.opponent.id,
+                });
+            }
+            None => debug!(
+                "Automation {ident:?}: Pets PvP ready but no eligible habitat"
+            ),
+        }
+    }
+
+    let use_mush = if gs.pet_exploration_ready(now) {
+        false
+    } else if cfg.max_mushrooms_pet_skip > 0 && ctx.can_spend_mush {
+        true
+    } else {
+        return None;
+    };
+    let Some((habitat, enemy_pos, player_pet_id)) = pet_exploration(pets)
+    else {
+        debug!(
+            "Automation {ident:?}: Pets exploration ready but no habitat \
+             currently exploring"
+        );
+        return None;
+    };
+    debug!(
+        "Automation {ident:?}: Pets explore habitat {habitat:?} fight_pos \
+         {enemy_pos} pet_id {player_pet_id} (mushroom: {use_mush})"
+    );
+    Some(SFCommand::FightPetDungeon {
+        use_mush,
+        habitat,
+        enemy_pos,
+        player_pet_id,
+    })
 }
 
-/// Simple perpetual loop you can start per-character if you want a dedicated task.
-/// Most users will integrate the smaller `tick` into their `AutoPoll` subscription instead.
-pub async fn auto_loop<S, GS>(cfg: AutomationCfg, mut session: S, mut gs: GS)
-where
-    S: SessionLike,
-    GS: GameStateLike,
-{
-    loop {
-        let out = tick(&cfg, &mut session, &mut gs).await;
-        let delay = if out.did_something { Duration::from_secs(5) } else { Duration::from_secs(30) };
-        sleep(delay).await;
-        let _ = session.refresh_gamestate(&mut gs).await;
+/// The habitat, that fights the pet opponent
+fn pet_pvp_habitat(pets: &Pets, cfg: &CharacterConfig) -> Option<HabitatType> {
+    let habitats = HabitatType::iter().map(|h| {
+        let habitat = pets.habitats.get(h);
+        PvpHabitat {
+            habitat: h,
+            battled: habitat.battled_opponent,
+            best_level: habitat.pets.iter().map(|p| p.level).max(),
+        }
+    });
+    let min_level = cfg.pets_skip_unwinnable.then_some(cfg.pets_pvp_min_level);
+    pick_pvp_habitat(habitats, pets.opponent.habitat, min_level)
+}
+
+/// What the pet opponent picker needs to know about a habitat
+#[derive(Debug, Clone, Copy)]
+struct PvpHabitat {
+    habitat: HabitatType,
+    battled: bool,
+    /// The level of the strongest pet. `None` without any pets
+    best_level: Option<u16>,
+}
+
+/// The habitat of the opponent is preferred, otherwise the one with the
+/// strongest pet. Habitats, that already fought the opponent, are skipped.
+/// The strength of the opponent is not known, so with a `min_level`, a
+/// fight only counts as unwinnable, if the best pet is below it
+fn pick_pvp_habitat(
+    habitats: impl IntoIterator<Item = PvpHabitat>,
+    opponent: Option<HabitatType>,
+    min_level: Option<u16>,
+) -> Option<HabitatType> {
+    let available: Vec<_> = habitats
+        .into_iter()
+        .filter(|a| !a.battled)
+        .filter(|a| {
+            min_level.is_none_or(|min| a.best_level.unwrap_or(0) >= min)
+        })
+        .collect();
+    if let Some(opponent) = opponent
+        && available.iter().any(|a| a.habitat == opponent)
+    {
+        return Some(opponent);
     }
+    available
+        .iter()
+        .filter_map(|a| Some((a.habitat, a.best_level?)))
+        // Ties go to the habitat listed first
+        .min_by_key(|(_, lvl)| std::cmp::Reverse(*lvl))
+        .map(|(h, _)| h)
 }
 
-/// Trait façade so this module doesn’t depend on your exact `sf_api` structs.
-/// Implement these for your session and gamestate wrappers.
-#[async_trait::async_trait]
-pub trait SessionLike: Send {
-    type QuestLike: Send + Sync;
-    type ExpeditionLike: Send + Sync;
-    type DungeonCand: Send + Sync;
-    type PetCand: Send + Sync;
-    type FightRes: Send + Sync;
+/// The habitat to explore with its strongest pet, as (habitat, the position
+/// of the next enemy, pet id)
+fn pet_exploration(pets: &Pets) -> Option<(HabitatType, u32, u32)> {
+    HabitatType::iter()
+        .filter_map(|h| {
+            let habitat = pets.habitats.get(h);
+            let HabitatExploration::Exploring { fights_won, .. } =
+                habitat.exploration
+            else {
+                return None;
+            };
+            let best = habitat.pets.iter().max_by_key(|p| p.level)?;
+            Some((h, fights_won + 1, best.level, best.id))
+        })
+        .min_by_key(|a| std::cmp::Reverse(a.2))
+        .map(|(h, enemy_pos, _, id)| (h, enemy_pos, id))
+}
 
-    async fn collect_tavern_reward(&mut self) -> Result<(), String>;
-    async fn list_tavern(&mut self) -> Result<Vec<Self::QuestLike>, String>;
-    async fn start_tavern(&mut self, id: u32) -> Result<(), String>;
+fn guild_action(ctx: &TickContext) -> Option<SFCommand> {
+    let TickContext {
+        ident,
+        gs,
+        cfg,
+        now,
+        ..
+    } = *ctx;
+    if !cfg.auto_guild {
+        return None;
+    }
+    let guild = gs.guild.as_ref()?;
+    if cfg.auto_guild_accept_defense {
+        debug!("Automation {ident:?}: Guild join defense");
+        return Some(SFCommand::GuildJoinDefense);
+    }
+    if cfg.auto_guild_accept_attack {
+        debug!("Automation {ident:?}: Guild join attack");
+        return Some(SFCommand::GuildJoinAttack);
+    }
+    if !cfg.auto_guild_hydra {
+        return None;
+    }
+    if guild.hydra.remaining_fights > 0 {
+        if !guild.hydra.next_battle.is_some_and(|t| t <= now) {
+            return None;
+        }
+        debug!("Automation {ident:?}: Guild hydra battle");
+        Some(SFCommand::GuildPetBattle {
+            use_mushroom: false,
+        })
+    } else if ctx.hydra_use_mush {
+        debug!("Automation {ident:?}: Guild hydra battle with mushroom");
+        Some(SFCommand::GuildPetBattle { use_mushroom: true })
+    } else {
+        None
+    }
+}
 
-    async fn collect_expedition_reward(&mut self) -> Result<(), String>;
-    async fn list_expeditions(&mut self) -> Result<Vec<Self::ExpeditionLike>, String>;
-    async fn start_expedition(&mut self, id: u32) -> Result<(), String>;
+/// The free daily rewards and the underworld
+fn extra_action(ctx: &TickContext) -> Option<SFCommand> {
+    let TickContext {
+        ident,
+        gs,
+        cfg,
+        now,
+        ..
+    } = *ctx;
+    // Only the free spin. Paid spins are never used
+    if cfg.auto_wheel
+        && gs.specials.wheel.next_free_spin.is_some_and(|t| t <= now)
+    {
+        debug!("Automation {ident:?}: Free wheel spin");
+        return Some(SFCommand::SpinWheelOfFortune {
+            payment: FortunePayment::FreeTurn,
+        });
+    }
 
-    async fn next_dungeon_candidate<GS: GameStateLike + Send>(
-        &mut self,
-        gs: &GS,
-    ) -> Result<Option<Self::DungeonCand>, String>;
-    async fn fight_dungeon(&mut self, ident: u32) -> Result<Self::FightRes, String>;
+    // Servers without an active calendar have no rewards and no timer, so
+    // nothing is ever collected there
+    let calendar = &gs.specials.calendar;
+    if cfg.auto_calendar
+        && !calendar.rewards.is_empty()
+        && calendar.next_possible.is_some_and(|t| t <= now)
+    {
+        debug!("Automation {ident:?}: Collect calendar reward");
+        return Some(SFCommand::CollectCalendar);
+    }
 
-    async fn next_pet_candidate<GS: GameStateLike + Send>(
-        &mut self,
-        gs: &GS,
-    ) -> Result<Option<Self::PetCand>, String>;
-    async fn fight_pet(&mut self, element: u8, slot: u8) -> Result<Self::FightRes, String>;
+    // Chests, that have already been opened, stay claimable by points, so
+    // they have to be skipped explicitly
+    let tasks = &gs.specials.tasks;
+    if cfg.auto_collect_tasks {
+        let daily = (0..tasks.daily.rewards.len()).find(|&i| {
+            !tasks.daily.rewards[i].opened && tasks.daily.can_open_chest(i)
+        });
+        if let Some(pos) = daily {
+            debug!("Automation {ident:?}: Collect daily task chest {pos}");
+            return Some(SFCommand::CollectDailyQuestReward { pos: pos as u8 });
+        }
+        let event = (0..tasks.event.rewards.len()).find(|&i| {
+            !tasks.event.rewards[i].opened && tasks.event.can_open_chest(i)
+        });
+        if let Some(pos) = event {
+            debug!("Automation {ident:?}: Collect event task chest {pos}");
+            return Some(SFCommand::CollectEventTaskReward { pos: pos as u8 });
+        }
+    }
+
+    let uw = gs.underworld.as_ref()?;
+    // Souls are collected before upgrading, because upgrades cost souls.
+    // Collecting them more often than hourly would only cost requests
+    if cfg.auto_underworld_souls
+        && uw
+            .production
+            .get(UnderworldResourceType::Souls)
+            .last_collectable
+            > 0
+        && uw.souls_current < uw.souls_limit
+        && ctx
+            .souls_collected
+            .is_none_or(|a| now - a >= TimeDelta::hours(1))
+    {
+        debug!("Automation {ident:?}: Collect underworld souls");
+        return Some(SFCommand::UnderworldCollect {
+            resource: UnderworldResourceType::Souls,
+        });
+    }
+    if cfg.auto_underworld_souls
+        && let Some(player_id) = ctx.lure_target
+    {
+        debug!("Automation {ident:?}: Underworld attack {player_id}");
+        return Some(SFCommand::UnderworldAttack { player_id });
+    }
 
-    async fn refresh_gamestate<GS: GameStateLike + Send>(&mut self, gs: &mut GS) -> Result<(), String>;
+    // Underworld upgrades wait, until all lures of the day, that have a
+    // target, are done
+    if !cfg.auto_underworld_upgrade
+        || ctx.lures_pending
+        || uw.upgrade_building.is_some()
+    {
+        return None;
+    }
+    let building_type =
+        cfg.underworld_upgrade_order().into_iter().find(|b| {
+            let cost = &uw.buildings.get(*b).upgrade_cost;
+            cost.souls <= uw.souls_current && cost.silver <= gs.character.silver
+        })?;
+    debug!(
+        "Automation {ident:?}: Upgrade underworld building {building_type:?}"
+    );
+    Some(SFCommand::UnderworldUpgradeBuilding { building_type })
 }
 
-pub trait GameStateLike {
-    fn tavern_end_time(&self) -> Option<chrono::DateTime<chrono::Local>>;
-    fn expedition_end_time(&self) -> Option<chrono::DateTime<chrono::Local>>;
-    fn dungeon_ready(&self) -> bool;
-    fn pet_ready(&self) -> bool;
+/// Logs why nothing is done, so that stuck automation can be debugged
+fn log_idle_summary(ctx: &TickContext) {
+    let TickContext { ident, gs, now, .. } = *ctx;
+    let portal = gs.dungeons.portal.as_ref().is_some_and(|p| p.can_fight);
+    let open_dng = LightDungeon::iter()
+        .map(Dungeon::from)
+        .chain(ShadowDungeon::iter().map(Dungeon::from))
+        .filter(|d| {
+            matches!(gs.dungeons.progress(*d), DungeonProgress::Open { .. })
+        })
+        .count();
+    let hydra = gs
+        .guild
+        .as_ref()
+        .and_then(|g| g.hydra.next_battle)
+        .is_some_and(|t| t <= now);
+    debug!(
+        "Automation {ident:?}: No action chosen. Summary -> portal: {portal}, \
+         dng_ready: {}, open_dng: {open_dng}, pets_pvp: {}, pets_explore: {}, \
+         hydra: {hydra}, thirst: {}s",
+        gs.dungeon_ready(now),
+        gs.pet_pvp_ready(now),
+        gs.pet_exploration_ready(now),
+        gs.tavern.thirst_for_adventure_sec,
+    );
 }
 
-/// Shape adapters for whatever your `sf_api` returns for quests/expeditions/dungeons/pets.
-pub trait QuestLike {
-    fn id(&self) -> u32;
-    fn minutes(&self) -> u32;
-    fn gold(&self) -> u64;
-    fn xp(&self) -> u64;
-    fn mushrooms(&self) -> u8;
+/// Junk is only sold, while the backpack has fewer free slots than this
+pub const SELL_JUNK_FREE_SLOTS: usize = 2;
+
+/// The backpack position of the weakest piece of equipment, that can be sold
+/// without losing anything. Nothing is sold, while the backpack has enough
+/// room left. Items the scrapbook is still missing are never sold
+pub fn pick_junk_item(
+    gs: &GameState,
+    scrapbook: &ScrapBook,
+    max_attributes: u32,
+) -> Option<usize> {
+    let slots = gs.character.inventory.backpack.iter().map(|item| {
+        let Some(item) = item else {
+            return BackpackSlot::Empty;
+        };
+        // Potions, gems, etc. have no equipment ident
+        let Some(ident) = item.equipment_ident() else {
+            return BackpackSlot::Other;
+        };
+        BackpackSlot::Equipment {
+            in_scrapbook: scrapbook.items.contains(&ident),
+            attributes: item.attributes.as_array().iter().sum(),
+        }
+    });
+    pick_junk_slot(slots, max_attributes)
 }
-pub trait ExpeditionLike {
-    fn id(&self) -> u32;
-    fn minutes(&self) -> u32;
-    fn gold(&self) -> u64;
-    fn xp(&self) -> u64;
-    fn mushrooms(&self) -> u8;
+
+/// What the junk picker needs to know about a backpack slot
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum BackpackSlot {
+    Empty,
+    /// Anything, that is not equipment
+    Other,
+    Equipment {
+        in_scrapbook: bool,
+        attributes: u32,
+    },
 }
-pub trait DungeonLike {
-    fn ident(&self) -> u32;
+
+fn pick_junk_slot(
+    slots: impl IntoIterator<Item = BackpackSlot>,
+    max_attributes: u32,
+) -> Option<usize> {
+    let slots: Vec<_> = slots.into_iter().collect();
+    let free = slots.iter().filter(|a| **a == BackpackSlot::Empty).count();
+    if free >= SELL_JUNK_FREE_SLOTS {
+        return None;
+    }
+    slots
+        .iter()
+        .enumerate()
+        .filter_map(|(pos, slot)| match *slot {
+            BackpackSlot::Equipment {
+                in_scrapbook: true,
+                attributes,
+            } if attributes <= max_attributes => Some((attributes, pos)),
+            _ => None,
+        })
+        .min()
+        .map(|(_, pos)| pos)
 }
-pub trait PetLike {
-    fn element(&self) -> u8;
-    fn slot(&self) -> u8;
-    fn element_str(&self) -> &'static str { "elem" }
+
+/// The priority group of an expedition reward
+pub fn expedition_reward_kind(typ: &RewardType) -> ExpeditionRewardKind {
+    match typ {
+        RewardType::Mushrooms => ExpeditionRewardKind::Mushrooms,
+        RewardType::Silver => ExpeditionRewardKind::Gold,
+        RewardType::Egg => ExpeditionRewardKind::Eggs,
+        RewardType::Gem
+        | RewardType::LegendaryGem
+        | RewardType::Frame
+        | RewardType::Mount => ExpeditionRewardKind::Items,
+        _ => ExpeditionRewardKind::Other,
+    }
 }
-pub trait FightResultLike {
-    fn win(&self) -> bool;
+
+/// The position of the reward, that comes first in the priority. Ties go to
+/// the reward listed first. `None` for an empty list
+pub fn pick_expedition_reward(
+    rewards: &[Reward],
+    prio: ExpeditionRewardPriority,
+) -> Option<usize> {
+    let kinds = rewards.iter().map(|a| expedition_reward_kind(&a.typ));
+    pick_reward_kind(kinds, prio)
 }
 
-/// Shared mission picker for quests and expeditions.
-fn pick_mission<T>(mut items: Vec<T>, strat: MissionStrategy) -> Option<T>
+fn pick_reward_kind(
+    kinds: impl IntoIterator<Item = ExpeditionRewardKind>,
+    prio: ExpeditionRewardPriority,
+) -> Option<usize> {
+    let order = prio.order();
+    kinds
+        .into_iter()
+        .enumerate()
+        .min_by_key(|(pos, kind)| {
+            let rank = order.iter().position(|a| a == kind);
+            (rank.unwrap_or(order.len()), *pos)
+        })
+        .map(|(pos, _)| pos)
+}
+
+/// Picks the best mission for the strategy. Missions costing mushrooms are
+/// never picked, so a list of only those returns `None`.
+///
+/// Every strategy works on the length in minutes, with lengths of zero
+/// counted as one second, so they score very high instead of infinite.
+///
+/// Ties in the score are broken deterministically: the shorter mission
+/// wins, then the one with more gold, then the one listed first. For
+/// example, two quests with the same gold per minute are decided by their
+/// length, and two equal quests by their position in the tavern
+pub fn pick_mission<T>(
+    items: impl IntoIterator<Item = T>,
+    strat: MissionStrategy,
+) -> Option<T>
 where
-    T: MissionLike + Clone,
+    T: MissionLike,
 {
-    // Save all mushrooms by default: do not consider missions that cost mushrooms.
-    items.retain(|q| q.mushrooms() as u32 == 0);
+    items
+        .into_iter()
+        .filter(|m| m.mushrooms() == 0)
+        .enumerate()
+        .map(|(pos, m)| (mission_score(&m, strat), pos, m))
+        .max_by(|(score_a, pos_a, a), (score_b, pos_b, b)| {
+            score_a
+                .total_cmp(score_b)
+                .then_with(|| b.seconds().cmp(&a.seconds()))
+                .then_with(|| a.gold().cmp(&b.gold()))
+                .then_with(|| pos_b.cmp(pos_a))
+        })
+        .map(|(_, _, m)| m)
+}
+
+fn mission_score<T: MissionLike>(mission: &T, strat: MissionStrategy) -> f64 {
+    let minutes = mission.seconds().max(1) as f64 / 60.0;
+    let gold = mission.gold() as f64;
+    let xp = mission.xp() as f64;
     match strat {
-        MissionStrategy::Shortest => {
-            items.sort_by_key(|q| (q.mushrooms(), q.minutes(), -(q.gold() as i64)));
-            items.into_iter().next()
-        }
-        MissionStrategy::MostGold => {
-            items.sort_by(|a,b| b.gold().cmp(&a.gold()));
-            items.into_iter().next()
-        }
-        MissionStrategy::BestGoldPerMinute => {
-            items.sort_by(|a,b| rate(b.gold(), b.minutes()).cmp(&rate(a.gold(), a.minutes())));
-            items.into_iter().next()
-        }
-        MissionStrategy::BestXpPerMinute => {
-            items.sort_by(|a,b| rate(b.xp(), b.minutes()).cmp(&rate(a.xp(), a.minutes())));
-            items.into_iter().next()
-        }
+        MissionStrategy::Shortest => -minutes,
+        MissionStrategy::MostGold => gold,
+        MissionStrategy::BestGoldPerMinute => gold / minutes,
+        MissionStrategy::BestXpPerMinute => xp / minutes,
         MissionStrategy::Smartest => {
-            let mut best: Option<(f64, T)> = None;
-            let mut max_gpm = 0f64;
-            let mut max_xpm = 0f64;
-            for q in &items { max_gpm = max_gpm.max(gpm(q)); max_xpm = max_xpm.max(xpm(q)); }
-            for q in items.into_iter() {
-                let t = q.minutes() as f64;
-                let g = if max_gpm > 0.0 { gpm(&q) / max_gpm } else { 0.0 };
-                let x = if max_xpm > 0.0 { xpm(&q) / max_xpm } else { 0.0 };
-                let speed = 1.0 / t.max(1.0);
-                let mush_penalty = if q.mushrooms() > 0 { 0.15 } else { 0.0 };
-                let score = 0.4*g + 0.4*x + 0.2*speed - mush_penalty;
-                match &mut best {
-                    None => best = Some((score, q)),
-                    Some((s, _)) if score > *s => best = Some((score, q)),
-                    _ => {}
-                }
-            }
-            best.map(|(_, q)| q)
+            let minutes = minutes.max(1.0);
+            0.45 * (gold / minutes) + 0.45 * (xp / minutes) + 0.10 / minutes
         }
     }
 }
 
-trait MissionLike {
-    fn id(&self) -> u32;
-    fn minutes(&self) -> u32;
+pub trait MissionLike {
+    fn seconds(&self) -> u32;
     fn gold(&self) -> u64;
     fn xp(&self) -> u64;
     fn mushrooms(&self) -> u8;
 }
 impl MissionLike for Quest {
-    fn id(&self) -> u32 { self.id }
-    fn minutes(&self) -> u32 { self.minutes }
-    fn gold(&self) -> u64 { self.gold }
-    fn xp(&self) -> u64 { self.xp }
-    fn mushrooms(&self) -> u8 { self.mushrooms }
-}
-impl MissionLike for Expedition {
-    fn id(&self) -> u32 { self.id }
-    fn minutes(&self) -> u32 { self.minutes }
-    fn gold(&self) -> u64 { self.gold }
-    fn xp(&self) -> u64 { self.xp }
-    fn mushrooms(&self) -> u8 { self.mushrooms }
-}
-
-fn rate(value: u64, minutes: u32) -> i64 {
-    if minutes == 0 { return i64::MAX; }
-    (value as i128 * 1_000_000i128 / minutes as i128) as i64
-}
-fn gpm<Q: MissionLike>(q: &Q) -> f64 {
-    if q.minutes() == 0 { return f64::INFINITY; }
-    q.gold() as f64 / q.minutes() as f64
-}
-fn xpm<Q: MissionLike>(q: &Q) -> f64 {
-    if q.minutes() == 0 { return f64::INFINITY; }
-    q.xp() as f64 / q.minutes() as f64
-}
\ No newline at end of file
+    fn seconds(&self) -> u32 {
+        self.seconds
+    }
+    fn gold(&self) -> u64 {
+        self.gold
+    }
+    fn xp(&self) -> u64 {
+        self.xp
+    }
+    fn mushrooms(&self) -> u8 {
+        self.mushrooms
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn quest(id: u32, seconds: u32, gold: u64, xp: u64) -> Quest {
+        Quest {
+            id,
+            seconds,
+            gold,
+            xp,
+            mushrooms: 0,
+        }
+    }
+
+    fn picked(quests: Vec<Quest>, strat: MissionStrategy) -> Option<u32> {
+        pick_mission(quests, strat).map(|a| a.id)
+    }
+
+    #[test]
+    fn equal_score_prefers_shorter() {
+        // 20 gold per minute each
+        let quests = vec![quest(0, 600, 200, 0), quest(1, 300, 100, 0)];
+        assert_eq!(picked(quests, MissionStrategy::BestGoldPerMinute), Some(1));
+
+        let quests = vec![quest(0, 600, 100, 0), quest(1, 300, 100, 0)];
+        assert_eq!(picked(quests, MissionStrategy::MostGold), Some(1));
+    }
+
+    #[test]
+    fn equal_score_and_length_prefers_more_gold() {
+        let quests = vec![quest(0, 300, 100, 50), quest(1, 300, 200, 50)];
+        assert_eq!(picked(quests, MissionStrategy::Shortest), Some(1));
+    }
+
+    #[test]
+    fn equal_quests_prefer_first_listed() {
+        let quests = vec![
+            quest(0, 300, 100, 50),
+            quest(1, 300, 100, 50),
+            quest(2, 300, 100, 50),
+        ];
+        for strat in [
+            MissionStrategy::Shortest,
+            MissionStrategy::MostGold,
+            MissionStrategy::BestGoldPerMinute,
+            MissionStrategy::BestXpPerMinute,
+            MissionStrategy::Smartest,
+        ] {
+            assert_eq!(picked(quests.clone(), strat), Some(0));
+        }
+    }
+
+    #[test]
+    fn zero_minute_quest_has_finite_score() {
+        let zero = quest(0, 0, 10, 10);
+        let normal = quest(1, 60, 500, 500);
+        for strat in [
+            MissionStrategy::Shortest,
+            MissionStrategy::MostGold,
+            MissionStrategy::BestGoldPerMinute,
+            MissionStrategy::BestXpPerMinute,
+            MissionStrategy::Smartest,
+        ] {
+            assert!(mission_score(&zero, strat).is_finite());
+        }
+        // 10 gold in one second beats 500 gold in one minute
+        let quests = vec![normal.clone(), zero.clone()];
+        assert_eq!(picked(quests, MissionStrategy::BestGoldPerMinute), Some(0));
+        let quests = vec![normal, zero];
+        assert_eq!(picked(quests, MissionStrategy::Shortest), Some(0));
+    }
+
+    #[test]
+    fn mushroom_missions_are_never_picked() {
+        let mut expensive =
+            vec![quest(0, 60, 1000, 1000), quest(1, 30, 900, 0)];
+        for q in &mut expensive {
+            q.mushrooms = 1;
+        }
+        assert_eq!(picked(expensive.clone(), MissionStrategy::MostGold), None);
+
+        expensive.push(quest(2, 600, 1, 1));
+        assert_eq!(picked(expensive, MissionStrategy::MostGold), Some(2));
+    }
+
+    #[test]
+    fn reward_kinds() {
+        use ExpeditionRewardKind as K;
+        assert_eq!(
+            expedition_reward_kind(&RewardType::Mushrooms),
+            K::Mushrooms
+        );
+        assert_eq!(expedition_reward_kind(&RewardType::Silver), K::Gold);
+        assert_eq!(expedition_reward_kind(&RewardType::Egg), K::Eggs);
+        for item in [
+            RewardType::Gem,
+            RewardType::LegendaryGem,
+            RewardType::Frame,
+            RewardType::Mount,
+        ] {
+            assert_eq!(expedition_reward_kind(&item), K::Items);
+        }
+    }
+
+    #[test]
+    fn reward_priority_order() {
+        use ExpeditionRewardKind as K;
+        use ExpeditionRewardPriority as P;
+        let rewards = [K::Other, K::Gold, K::Eggs, K::Mushrooms];
+        assert_eq!(pick_reward_kind(rewards, P::MushroomsGoldEggs), Some(3));
+        assert_eq!(pick_reward_kind(rewards, P::GoldMushroomsEggs), Some(1));
+        assert_eq!(pick_reward_kind(rewards, P::EggsMushroomsGold), Some(2));
+        assert_eq!(pick_reward_kind(rewards, P::ItemsMushroomsGold), Some(3));
+    }
+
+    #[test]
+    fn reward_items_and_artifacts() {
+        use ExpeditionRewardKind as K;
+        use ExpeditionRewardPriority as P;
+        let rewards = [K::Gold, K::Items, K::Other];
+        assert_eq!(pick_reward_kind(rewards, P::ItemsMushroomsGold), Some(1));
+        // Items only beat unknown rewards for the other priorities
+        let rewards = [K::Other, K::Items];
+        assert_eq!(pick_reward_kind(rewards, P::MushroomsGoldEggs), Some(1));
+    }
+
+    #[test]
+    fn reward_ties_and_empty_list() {
+        use ExpeditionRewardKind as K;
+        use ExpeditionRewardPriority as P;
+        let rewards = [K::Other, K::Gold, K::Gold];
+        assert_eq!(pick_reward_kind(rewards, P::MushroomsGoldEggs), Some(1));
+        assert_eq!(pick_reward_kind([K::Other], P::MushroomsGoldEggs), Some(0));
+        assert_eq!(pick_reward_kind([], P::MushroomsGoldEggs), None);
+        assert_eq!(pick_expedition_reward(&[], P::MushroomsGoldEggs), None);
+    }
+
+    #[test]
+    fn action_order_is_fixed_unless_randomized() {
+        use ActionGroup::*;
+        for _ in 0..10 {
+            assert_eq!(action_order(false), [Tavern, Dungeons, Pets, Guild]);
+        }
+    }
+
+    #[test]
+    fn randomized_action_order_keeps_every_group() {
+        use ActionGroup::*;
+        for _ in 0..10 {
+            let order = action_order(true);
+            for group in [Tavern, Dungeons, Pets, Guild] {
+                assert_eq!(order.iter().filter(|a| **a == group).count(), 1);
+            }
+        }
+    }
+
+    fn habitat(
+        habitat: HabitatType,
+        battled: bool,
+        best_level: Option<u16>,
+    ) -> PvpHabitat {
+        PvpHabitat {
+            habitat,
+            battled,
+            best_level,
+        }
+    }
+
+    #[test]
+    fn pvp_prefers_the_habitat_of_the_opponent() {
+        use HabitatType::*;
+        let habitats = [
+            habitat(Shadow, false, Some(50)),
+            habitat(Fire, false, Some(10)),
+        ];
+        assert_eq!(pick_pvp_habitat(habitats, Some(Fire), None), Some(Fire));
+        // Otherwise the strongest pet fights
+        assert_eq!(pick_pvp_habitat(habitats, None, None), Some(Shadow));
+        let habitats = [
+            habitat(Shadow, false, Some(50)),
+            habitat(Fire, true, Some(10)),
+        ];
+        assert_eq!(pick_pvp_habitat(habitats, Some(Fire), None), Some(Shadow));
+    }
+
+    #[test]
+    fn pvp_skips_battled_and_empty_habitats() {
+        use HabitatType::*;
+        let habitats = [
+            habitat(Shadow, true, Some(90)),
+            habitat(Light, false, None),
+            habitat(Earth, false, Some(20)),
+            habitat(Fire, false, Some(20)),
+        ];
+        // Equally strong pets go in habitat order
+        assert_eq!(pick_pvp_habitat(habitats, None, None), Some(Earth));
+        let habitats = [habitat(Shadow, true, Some(90))];
+        assert_eq!(pick_pvp_habitat(habitats, Some(Shadow), None), None);
+    }
+
+    #[test]
+    fn pvp_skips_habitats_below_the_min_level() {
+        use HabitatType::*;
+        let habitats = [
+            habitat(Shadow, false, Some(30)),
+            habitat(Fire, false, Some(9)),
+            habitat(Water, false, None),
+        ];
+        // Not even the habitat of the opponent fights below the minimum
+        assert_eq!(
+            pick_pvp_habitat(habitats, Some(Fire), Some(10)),
+            Some(Shadow)
+        );
+        assert_eq!(pick_pvp_habitat(habitats, Some(Fire), Some(9)), Some(Fire));
+        assert_eq!(pick_pvp_habitat(habitats, None, Some(31)), None);
+    }
+
+    fn junk(in_scrapbook: bool, attributes: u32) -> BackpackSlot {
+        BackpackSlot::Equipment {
+            in_scrapbook,
+            attributes,
+        }
+    }
+
+    #[test]
+    fn junk_needs_a_nearly_full_backpack() {
+        use BackpackSlot::Empty;
+        let slots = [junk(true, 10), Empty, Empty];
+        assert_eq!(pick_junk_slot(slots, 100), None);
+        let slots = [junk(true, 10), Empty, junk(true, 20)];
+        assert_eq!(pick_junk_slot(slots, 100), Some(0));
+    }
+
+    #[test]
+    fn junk_keeps_items_missing_from_the_scrapbook() {
+        let slots = [junk(false, 1), junk(true, 50), junk(false, 2)];
+        assert_eq!(pick_junk_slot(slots, 100), Some(1));
+        let slots = [junk(false, 1), junk(false, 2)];
+        assert_eq!(pick_junk_slot(slots, 100), None);
+    }
+
+    #[test]
+    fn junk_picks_the_weakest_item_below_the_limit() {
+        use BackpackSlot::Other;
+        let slots = [junk(true, 80), Other, junk(true, 30), junk(true, 200)];
+        assert_eq!(pick_junk_slot(slots, 100), Some(2));
+        // Only the limit itself still counts as junk
+        assert_eq!(pick_junk_slot([junk(true, 100)], 100), Some(0));
+        assert_eq!(pick_junk_slot([junk(true, 101), Other], 100), None);
+        // Equally weak items go in backpack order
+        let slots = [junk(true, 30), junk(true, 30)];
+        assert_eq!(pick_junk_slot(slots, 100), Some(0));
+    }
+}
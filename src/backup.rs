@@ -1,27 +1,44 @@
 use std::{
     collections::{BTreeMap, HashMap, HashSet},
-    sync::{Arc, Mutex},
+    path::{Path, PathBuf},
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicUsize, Ordering},
+    },
 };
 
 use async_compression::tokio::write::ZlibEncoder;
 use chrono::{DateTime, Local, Utc};
 use log::{debug, warn};
 use nohash_hasher::{IntMap, IntSet};
-use serde::{Deserialize, Serialize};
+use serde::{
+    Deserialize, Deserializer, Serialize,
+    de::{DeserializeOwned, DeserializeSeed, MapAccess, SeqAccess, Visitor},
+};
 use sf_api::gamestate::unlockables::EquipmentIdent;
 use tokio::{
-    io::{AsyncWriteExt, BufReader},
+    io::{AsyncReadExt, AsyncWriteExt, BufReader},
     task::yield_now,
 };
 
 use crate::{
-    CharacterInfo, CrawlingOrder, CrawlingStatus, QueID, WorkerQue,
+    CharacterInfo, CrawlingOrder, CrawlingStatus, QueID, WorkerQue, config,
     handle_new_char_info,
 };
 
+/// The progress of a running `restore_backup`, shared with the subscription,
+/// that reports it to the UI
+#[derive(Debug, Default)]
+pub struct RestoreProgress {
+    pub done: AtomicUsize,
+    pub total: AtomicUsize,
+}
+
 pub async fn restore_backup(
     backup: Option<Box<ZHofBackup>>,
     total_pages: usize,
+    progress: Arc<RestoreProgress>,
+    default_order: CrawlingOrder,
 ) -> RestoreData {
     if backup.is_none() {
         debug!("Reset crawling progress");
@@ -36,18 +53,27 @@ pub async fn restore_backup(
             invalid_pages: vec![],
             todo_accounts: vec![],
             invalid_accounts: vec![],
-            order: CrawlingOrder::Random,
+            order: default_order,
             export_time: None,
             characters: vec![],
             lvl_skipped_accounts: Default::default(),
             min_level: 0,
             max_level: 9999,
+            hard_min_level: 0,
+            incomplete: false,
         }),
     };
 
     let que_id = QueID::new();
     let mut todo_pages = new_info.todo_pages;
     let invalid_pages = new_info.invalid_pages;
+    if new_info.incomplete {
+        // We do not know which pages the broken characters were on, so we
+        // crawl everything again and keep what we could parse until then
+        let invalid: HashSet<_> = invalid_pages.iter().copied().collect();
+        todo_pages =
+            (0..total_pages).filter(|a| !invalid.contains(a)).collect();
+    }
     let todo_accounts = new_info.todo_accounts;
     let invalid_accounts = new_info.invalid_accounts;
     let order = new_info.order;
@@ -58,10 +84,14 @@ pub async fn restore_backup(
     let mut player_info = Default::default();
     let mut naked = Default::default();
 
+    progress
+        .total
+        .store(new_info.characters.len(), Ordering::Relaxed);
     for (idx, char) in new_info.characters.into_iter().enumerate() {
         if idx % 10_001 == 10_000 {
             // This loop can take a few seconds, so we make sure this does
             // not block the ui by yielding after a bit
+            progress.done.store(idx, Ordering::Relaxed);
             yield_now().await;
         }
         handle_new_char_info(
@@ -82,6 +112,7 @@ pub async fn restore_backup(
         lvl_skipped_accounts: new_info.lvl_skipped_accounts,
         min_level: new_info.min_level,
         max_level: new_info.max_level,
+        hard_min_level: new_info.hard_min_level,
     }
 }
 
@@ -103,6 +134,7 @@ pub struct RestoreData {
     pub lvl_skipped_accounts: BTreeMap<u32, Vec<String>>,
     pub min_level: u32,
     pub max_level: u32,
+    pub hard_min_level: u32,
 }
 
 impl RestoreData {
@@ -121,8 +153,12 @@ impl RestoreData {
                 in_flight_accounts: Default::default(),
                 max_level: self.max_level,
                 min_level: self.min_level,
+                hard_min_level: self.hard_min_level,
                 lvl_skipped_accounts: self.lvl_skipped_accounts,
                 self_init: false,
+                paused: false,
+                page_backoff: Default::default(),
+                account_backoff: Default::default(),
             })),
             player_info: self.player_info,
             equipment: self.equipment,
@@ -130,6 +166,8 @@ impl RestoreData {
             last_update: Local::now(),
             crawling_session: None,
             recent_failures: vec![],
+            paused: false,
+            finished: None,
         }
     }
 }
@@ -137,8 +175,10 @@ impl RestoreData {
 pub async fn get_newest_backup(
     server_ident: String,
     fetch_online: bool,
+    dir: Option<PathBuf>,
 ) -> Option<Box<ZHofBackup>> {
-    let backup = ZHofBackup::read(&server_ident).await;
+    let dir = dir.as_deref();
+    let backup = ZHofBackup::read(&server_ident, dir).await;
     if let Err(e) = &backup {
         warn!("{server_ident} could not read in local backup: {e}")
     }
@@ -162,9 +202,9 @@ pub async fn get_newest_backup(
         };
     debug!("{server_ident} fetch online backup: {fetch_online}");
     // If the online backup is newer, we fetch it and restore it
-    if fetch_online && fetch_online_hof(&server_ident).await.is_ok() {
+    if fetch_online && fetch_online_hof(&server_ident, dir).await.is_ok() {
         debug!("{server_ident} fetched online HoF");
-        backup = ZHofBackup::read(&server_ident).await.ok();
+        backup = ZHofBackup::read(&server_ident, dir).await.ok();
     }
     backup.map(Box::new)
 }
@@ -182,23 +222,51 @@ pub struct ZHofBackup {
     #[serde(default)]
     pub order: CrawlingOrder,
     pub export_time: Option<DateTime<Utc>>,
-    pub characters: Vec<CharacterInfo>,
     #[serde(default)]
     pub lvl_skipped_accounts: BTreeMap<u32, Vec<String>>,
     #[serde(default)]
     pub min_level: u32,
     #[serde(default = "default_max_lvl")]
     pub max_level: u32,
+    #[serde(default)]
+    pub hard_min_level: u32,
+    /// Written last, so that a truncated backup still contains the crawling
+    /// progress
+    pub characters: Vec<CharacterInfo>,
+    /// Set, if some characters of this backup could not be read
+    #[serde(skip)]
+    pub incomplete: bool,
 }
 
 fn default_max_lvl() -> u32 {
     9999
 }
 
+/// The most we decompress from a backup file. Anything larger is broken and
+/// only read as far as this limit
+const MAX_BACKUP_SIZE: u64 = 1 << 30;
+
+/// The path of the backup file for this server. Without a backup directory,
+/// backups are stored in the working directory
+fn backup_path(ident: &str, dir: Option<&Path>) -> PathBuf {
+    let file = format!("{ident}.zhof");
+    match dir {
+        Some(dir) => dir.join(file),
+        None => PathBuf::from(file),
+    }
+}
+
 impl ZHofBackup {
-    pub async fn write(&self, ident: &str) -> Result<(), std::io::Error> {
+    pub async fn write(
+        &self,
+        ident: &str,
+        dir: Option<&Path>,
+    ) -> Result<(), std::io::Error> {
         let serialized = serde_json::to_string(&self).unwrap();
-        let file = tokio::fs::File::create(format!("{}.zhof", ident)).await?;
+        if let Some(dir) = dir {
+            tokio::fs::create_dir_all(dir).await?;
+        }
+        let file = tokio::fs::File::create(backup_path(ident, dir)).await?;
         let mut encoder = ZlibEncoder::new(file);
         encoder.write_all(serialized.as_bytes()).await?;
         encoder.flush().await?;
@@ -206,26 +274,169 @@ impl ZHofBackup {
         Ok(())
     }
 
-    pub async fn read(ident: &str) -> Result<ZHofBackup, std::io::Error> {
-        let file = tokio::fs::File::open(format!("{}.zhof", ident)).await?;
+    pub async fn read(
+        ident: &str,
+        dir: Option<&Path>,
+    ) -> Result<ZHofBackup, std::io::Error> {
+        let file = tokio::fs::File::open(backup_path(ident, dir)).await?;
         let reader = BufReader::new(file);
         let mut decoder =
             async_compression::tokio::bufread::ZlibDecoder::new(reader);
         let mut buffer = Vec::new();
-        tokio::io::AsyncReadExt::read_to_end(&mut decoder, &mut buffer).await?;
+        decoder
+            .take(MAX_BACKUP_SIZE)
+            .read_to_end(&mut buffer)
+            .await?;
+
+        match serde_json::from_slice(&buffer) {
+            Ok(deserialized) => Ok(deserialized),
+            Err(e) => {
+                warn!("{ident} backup is damaged ({e}), restoring what we can");
+                Self::read_partial(&buffer).ok_or(e.into())
+            }
+        }
+    }
+
+    /// Reads a backup, that does not match the expected layout, or has been
+    /// cut off, by keeping every field and character, that can still be
+    /// parsed. The file is parsed one value at a time, so everything in front
+    /// of the damage is kept
+    fn read_partial(buffer: &[u8]) -> Option<ZHofBackup> {
+        let mut partial = PartialBackup {
+            backup: ZHofBackup {
+                todo_pages: vec![],
+                invalid_pages: vec![],
+                todo_accounts: vec![],
+                invalid_accounts: vec![],
+                order: CrawlingOrder::default(),
+                export_time: None,
+                lvl_skipped_accounts: Default::default(),
+                min_level: 0,
+                max_level: default_max_lvl(),
+                hard_min_level: 0,
+                characters: vec![],
+                incomplete: true,
+            },
+            fields: 0,
+            total_characters: 0,
+        };
+        let mut de = serde_json::Deserializer::from_slice(buffer);
+        if let Err(e) = (&mut partial).deserialize(&mut de) {
+            debug!("Backup is readable up to: {e}");
+        }
+        if partial.fields == 0 {
+            return None;
+        }
+        debug!(
+            "Restored {}/{} characters",
+            partial.backup.characters.len(),
+            partial.total_characters
+        );
+        Some(partial.backup)
+    }
+}
+
+/// The state of a damaged backup, while it is read by `read_partial`
+struct PartialBackup {
+    backup: ZHofBackup,
+    /// The amount of fields, that have been found
+    fields: usize,
+    /// The amount of characters, that have been found, including the ones,
+    /// that could not be parsed
+    total_characters: usize,
+}
+
+/// Overwrites the field, if the value has the expected layout
+fn set_field<T: DeserializeOwned>(field: &mut T, value: serde_json::Value) {
+    if let Ok(value) = serde_json::from_value(value) {
+        *field = value;
+    }
+}
+
+impl<'de> DeserializeSeed<'de> for &mut PartialBackup {
+    type Value = ();
+
+    fn deserialize<D: Deserializer<'de>>(self, d: D) -> Result<(), D::Error> {
+        d.deserialize_map(self)
+    }
+}
+
+impl<'de> Visitor<'de> for &mut PartialBackup {
+    type Value = ();
+
+    fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str("a HoF backup")
+    }
 
-        let deserialized = serde_json::from_slice(&buffer)?;
-        Ok(deserialized)
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<(), A::Error> {
+        while let Some(key) = map.next_key::<String>()? {
+            self.fields += 1;
+            if key == "characters" {
+                map.next_value_seed(PartialCharacters(&mut *self))?;
+                continue;
+            }
+            let value: serde_json::Value = map.next_value()?;
+            let b = &mut self.backup;
+            match key.as_str() {
+                "todo_pages" => set_field(&mut b.todo_pages, value),
+                "invalid_pages" => set_field(&mut b.invalid_pages, value),
+                "todo_accounts" => set_field(&mut b.todo_accounts, value),
+                "invalid_accounts" => set_field(&mut b.invalid_accounts, value),
+                "order" => set_field(&mut b.order, value),
+                "export_time" => set_field(&mut b.export_time, value),
+                "lvl_skipped_accounts" => {
+                    set_field(&mut b.lvl_skipped_accounts, value)
+                }
+                "min_level" => set_field(&mut b.min_level, value),
+                "max_level" => set_field(&mut b.max_level, value),
+                "hard_min_level" => set_field(&mut b.hard_min_level, value),
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Reads the characters of a damaged backup one by one and skips the ones,
+/// that can not be parsed
+struct PartialCharacters<'a>(&'a mut PartialBackup);
+
+impl<'de> DeserializeSeed<'de> for PartialCharacters<'_> {
+    type Value = ();
+
+    fn deserialize<D: Deserializer<'de>>(self, d: D) -> Result<(), D::Error> {
+        d.deserialize_seq(self)
+    }
+}
+
+impl<'de> Visitor<'de> for PartialCharacters<'_> {
+    type Value = ();
+
+    fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str("a list of characters")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<(), A::Error> {
+        while let Some(value) = seq.next_element::<serde_json::Value>()? {
+            self.0.total_characters += 1;
+            if let Ok(character) = serde_json::from_value(value) {
+                self.0.backup.characters.push(character);
+            }
+        }
+        Ok(())
     }
 }
 
 async fn fetch_online_hof_date(
     server_ident: &str,
 ) -> Result<DateTime<Utc>, Box<dyn std::error::Error>> {
-    let resp = reqwest::get(format!(
-        "https://hof-cache.marenga.dev/{server_ident}.version"
-    ))
-    .await?;
+    let resp = config::http_client()
+        .build()?
+        .get(format!(
+            "https://hof-cache.marenga.dev/{server_ident}.version"
+        ))
+        .send()
+        .await?;
 
     match resp.error_for_status() {
         Ok(r) => {
@@ -239,18 +450,100 @@ async fn fetch_online_hof_date(
 
 async fn fetch_online_hof(
     server_ident: &str,
+    dir: Option<&Path>,
 ) -> Result<(), Box<dyn std::error::Error>> {
-    let resp = reqwest::get(format!(
-        "https://hof-cache.marenga.dev/{server_ident}.zhof"
-    ))
-    .await?;
+    let resp = config::http_client()
+        .build()?
+        .get(format!("https://hof-cache.marenga.dev/{server_ident}.zhof"))
+        .send()
+        .await?;
 
     match resp.error_for_status() {
         Ok(r) => {
             let bytes = r.bytes().await?;
-            tokio::fs::write(format!("{server_ident}.zhof"), bytes).await?;
+            if let Some(dir) = dir {
+                tokio::fs::create_dir_all(dir).await?;
+            }
+            tokio::fs::write(backup_path(server_ident, dir), bytes).await?;
             Ok(())
         }
         Err(e) => Err(e.into()),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn character(uid: u32) -> String {
+        format!(
+            r#"{{"equipment":[],"name":"char{uid}","uid":{uid},"level":1}}"#
+        )
+    }
+
+    /// A backup in the order, it is written in
+    fn backup_json() -> Vec<u8> {
+        format!(
+            concat!(
+                r#"{{"todo_pages":[3,4],"invalid_pages":[1],"#,
+                r#""todo_accounts":["a"],"invalid_accounts":["b"],"#,
+                r#""export_time":null,"lvl_skipped_accounts":{{"5":["c"]}},"#,
+                r#""min_level":10,"max_level":500,"hard_min_level":5,"#,
+                r#""characters":[{},{{"uid":"broken"}},{}]}}"#,
+            ),
+            character(1),
+            character(2)
+        )
+        .into_bytes()
+    }
+
+    #[test]
+    fn partial_skips_broken_characters() {
+        let backup = ZHofBackup::read_partial(&backup_json()).unwrap();
+        let uids: Vec<_> = backup.characters.iter().map(|a| a.uid).collect();
+        assert_eq!(uids, [1, 2]);
+        assert!(backup.incomplete);
+    }
+
+    #[test]
+    fn partial_keeps_progress_of_cut_off_backup() {
+        let full = backup_json();
+        let cut = full.len() - 20;
+        let backup = ZHofBackup::read_partial(&full[..cut]).unwrap();
+        assert_eq!(backup.todo_pages, [3, 4]);
+        assert_eq!(backup.invalid_pages, [1]);
+        assert_eq!(backup.todo_accounts, ["a"]);
+        assert_eq!(backup.invalid_accounts, ["b"]);
+        assert_eq!(backup.lvl_skipped_accounts[&5], ["c"]);
+        assert_eq!(backup.min_level, 10);
+        assert_eq!(backup.max_level, 500);
+        assert_eq!(backup.hard_min_level, 5);
+        let uids: Vec<_> = backup.characters.iter().map(|a| a.uid).collect();
+        assert_eq!(uids, [1]);
+    }
+
+    #[test]
+    fn partial_keeps_defaults_for_broken_fields() {
+        let buffer = br#"{"min_level": "x", "todo_pages": [7], "max_le"#;
+        let backup = ZHofBackup::read_partial(buffer).unwrap();
+        assert_eq!(backup.min_level, 0);
+        assert_eq!(backup.max_level, 9999);
+        assert_eq!(backup.todo_pages, [7]);
+        assert!(backup.characters.is_empty());
+    }
+
+    #[test]
+    fn partial_rejects_garbage() {
+        assert!(ZHofBackup::read_partial(b"not a backup").is_none());
+        assert!(ZHofBackup::read_partial(b"").is_none());
+    }
+
+    #[test]
+    fn characters_are_written_last() {
+        let backup = ZHofBackup::read_partial(&backup_json()).unwrap();
+        let written = serde_json::to_string(&backup).unwrap();
+        let chars = written.find("\"characters\"").unwrap();
+        assert!(written.find("\"hard_min_level\"").unwrap() < chars);
+        assert!(written.find("\"todo_pages\"").unwrap() < chars);
+    }
+}
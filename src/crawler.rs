@@ -1,6 +1,10 @@
 use std::{
-    sync::{Arc, Mutex},
-    time::{Duration, SystemTime},
+    hash::Hash,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicU8, Ordering},
+    },
+    time::{Duration, Instant, SystemTime},
 };
 
 use chrono::Utc;
@@ -18,6 +22,8 @@ pub struct Crawler {
     pub que: Arc<Mutex<WorkerQue>>,
     pub state: Arc<CrawlerState>,
     pub server_id: ServerID,
+    /// Mirrors `config.crawl_page_share`
+    pub page_share: Arc<AtomicU8>,
 }
 
 impl Crawler {
@@ -26,7 +32,21 @@ impl Crawler {
             // Thi: CrawlActions is in a seperate scope to immediately drop the
             // guard
             let mut lock = self.que.lock().unwrap();
+            let page_share = self.page_share.load(Ordering::Relaxed);
+            let ready_pages = lock.page_backoff.take_ready();
+            lock.todo_pages.extend(ready_pages);
+            let ready_accounts = lock.account_backoff.take_ready();
+            lock.todo_accounts.extend(ready_accounts);
             loop {
+                if lock.paused {
+                    break CrawlAction::Wait;
+                }
+                if lock.wants_page(page_share)
+                    && let Some(idx) = lock.todo_pages.pop()
+                {
+                    lock.in_flight_pages.push(idx);
+                    break CrawlAction::Page(idx, lock.que_id);
+                }
                 match lock.todo_accounts.pop() {
                     Some(entry) => {
                         if entry.chars().all(|a| a.is_ascii_digit()) {
@@ -94,6 +114,9 @@ impl Crawler {
 
                 let mut lock = self.que.lock().unwrap();
                 for acc in gs.hall_of_fames.players.drain(..) {
+                    if acc.level < lock.hard_min_level {
+                        continue;
+                    }
                     if acc.level > lock.max_level || acc.level < lock.min_level
                     {
                         match lock.lvl_skipped_accounts.entry(acc.level) {
@@ -109,6 +132,7 @@ impl Crawler {
                     }
                 }
                 lock.in_flight_pages.retain(|a| a != page);
+                lock.page_backoff.reset(page);
                 Message::PageCrawled
             }
             CrawlAction::Character(name, que_id) => {
@@ -176,6 +200,7 @@ impl Crawler {
                         if lock.que_id == *que_id {
                             lock.invalid_accounts.retain(|a| a != name);
                             lock.in_flight_accounts.remove(name);
+                            lock.account_backoff.reset(name);
                             lock.invalid_accounts.push(name.to_string());
                         }
                         return Message::CrawlerNoPlayerResult;
@@ -215,7 +240,12 @@ impl CrawlerState {
     ) -> Result<Self, SFError> {
         let password = name.chars().rev().collect::<String>();
         let mut session = Session::new(&name, &password, server.clone());
-        debug!("Logging in {name} on {}", session.server_url());
+        debug!(
+            "Logging in {name} on {} via {}",
+            session.server_url(),
+            config::active_proxy()
+                .map_or("no proxy".to_string(), config::redact_proxy)
+        );
         if let Ok(resp) = session.login().await {
             debug!("Successfully logged in {name} on {}", session.server_url());
             let gs = GameState::new(resp)?;
@@ -349,10 +379,100 @@ pub struct WorkerQue {
     pub lvl_skipped_accounts: BTreeMap<u32, Vec<String>>,
     pub min_level: u32,
     pub max_level: u32,
+    /// Characters below this level are dropped, when their page is parsed.
+    /// Unlike the ones outside of `min_level`/`max_level`, they can not be
+    /// restored without crawling their pages again
+    pub hard_min_level: u32,
     pub self_init: bool,
+    /// Mirrors `paused` of the crawling status, so that the crawler threads
+    /// idle without touching the que
+    pub paused: bool,
+    /// Rate limited pages, that wait before they are crawled again
+    pub page_backoff: Backoff<usize>,
+    /// Rate limited characters, that wait before they are fetched again
+    pub account_backoff: Backoff<String>,
+}
+
+/// The wait after the first rate limit of a page or character
+const BACKOFF_BASE_MS: u64 = 2_000;
+/// The longest wait of an item, no matter how often it was rate limited
+const BACKOFF_MAX_MS: u64 = 300_000;
+
+/// Keeps rate limited items out of the que for a while. The wait doubles
+/// every time the same item is rate limited again, until it succeeds
+#[derive(Debug)]
+pub struct Backoff<T> {
+    attempts: HashMap<T, u32>,
+    waiting: Vec<(Instant, T)>,
+}
+
+impl<T> Default for Backoff<T> {
+    fn default() -> Self {
+        Self {
+            attempts: HashMap::new(),
+            waiting: Vec::new(),
+        }
+    }
+}
+
+impl<T: Eq + Hash + Clone> Backoff<T> {
+    /// Holds the item back, because it was rate limited
+    pub fn delay(&mut self, item: T) {
+        let attempts = self.attempts.entry(item.clone()).or_default();
+        *attempts += 1;
+        let factor = 1u64 << (*attempts - 1).min(16);
+        let wait = BACKOFF_BASE_MS.saturating_mul(factor).min(BACKOFF_MAX_MS);
+        self.waiting
+            .push((Instant::now() + Duration::from_millis(wait), item));
+    }
+
+    /// Removes and returns the items, that have waited long enough
+    pub fn take_ready(&mut self) -> Vec<T> {
+        if self.waiting.is_empty() {
+            return vec![];
+        }
+        let now = Instant::now();
+        let (ready, waiting) = std::mem::take(&mut self.waiting)
+            .into_iter()
+            .partition::<Vec<_>, _>(|(at, _)| *at <= now);
+        self.waiting = waiting;
+        ready.into_iter().map(|a| a.1).collect()
+    }
+
+    /// Forgets the previous rate limits of the item, once it succeeded or
+    /// has been given up on
+    pub fn reset(&mut self, item: &T) {
+        if !self.attempts.is_empty() {
+            self.attempts.remove(item);
+        }
+    }
+
+    /// The items currently waiting
+    pub fn waiting(&self) -> impl Iterator<Item = &T> {
+        self.waiting.iter().map(|a| &a.1)
+    }
+
+    pub fn len(&self) -> usize {
+        self.waiting.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.waiting.is_empty()
+    }
 }
 
 impl WorkerQue {
+    /// Characters are normally fetched before any new page. With a
+    /// `page_share` (in percent) above 0, pages are fetched first, until they
+    /// make up that share of the requests in flight
+    pub fn wants_page(&self, page_share: u8) -> bool {
+        let in_flight =
+            self.in_flight_pages.len() + self.in_flight_accounts.len();
+        !self.todo_pages.is_empty()
+            && self.in_flight_pages.len() * 100
+                < usize::from(page_share) * (in_flight + 1)
+    }
+
     pub fn create_backup(
         &self,
         player_info: &IntMap<u32, CharacterInfo>,
@@ -368,6 +488,8 @@ impl WorkerQue {
             lvl_skipped_accounts: self.lvl_skipped_accounts.clone(),
             min_level: self.min_level,
             max_level: self.max_level,
+            hard_min_level: self.hard_min_level,
+            incomplete: false,
         };
 
         for acc in &self.in_flight_accounts {
@@ -378,6 +500,11 @@ impl WorkerQue {
             backup.todo_pages.push(*page)
         }
 
+        backup.todo_pages.extend(self.page_backoff.waiting());
+        backup
+            .todo_accounts
+            .extend(self.account_backoff.waiting().cloned());
+
         backup
     }
 
@@ -386,6 +513,16 @@ impl WorkerQue {
             + self.todo_accounts.len()
             + self.in_flight_pages.len() * PER_PAGE
             + self.in_flight_accounts.len()
+            + self.page_backoff.len() * PER_PAGE
+            + self.account_backoff.len()
+    }
+
+    /// Whether there is nothing left to crawl, not even after a backoff
+    pub fn is_done(&self) -> bool {
+        self.todo_pages.is_empty()
+            && self.todo_accounts.is_empty()
+            && self.page_backoff.is_empty()
+            && self.account_backoff.is_empty()
     }
 }
 
@@ -430,3 +567,63 @@ async fn sleep_until_rate_limit_reset() {
 
     sleep(Duration::from_secs(timeout)).await;
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The waits of the items, that are currently held back
+    fn waits(backoff: &Backoff<u32>, start: Instant) -> Vec<u64> {
+        backoff
+            .waiting
+            .iter()
+            .map(|(at, _)| (*at - start).as_millis() as u64)
+            .collect()
+    }
+
+    #[test]
+    fn backoff_doubles_per_rate_limit() {
+        let mut backoff = Backoff::default();
+        let start = Instant::now();
+        for _ in 0..3 {
+            backoff.delay(1);
+        }
+        let waits = waits(&backoff, start);
+        // Leaves room for the time the test takes between the calls
+        let expected =
+            [BACKOFF_BASE_MS, 2 * BACKOFF_BASE_MS, 4 * BACKOFF_BASE_MS];
+        for (wait, expected) in waits.into_iter().zip(expected) {
+            assert!((expected..expected + 1_000).contains(&wait));
+        }
+        assert_eq!(backoff.len(), 3);
+        assert!(backoff.take_ready().is_empty());
+    }
+
+    #[test]
+    fn backoff_is_capped() {
+        let mut backoff = Backoff::default();
+        let start = Instant::now();
+        for _ in 0..40 {
+            backoff.delay(1);
+        }
+        let longest = waits(&backoff, start).into_iter().max().unwrap();
+        assert!((BACKOFF_MAX_MS..BACKOFF_MAX_MS + 1_000).contains(&longest));
+    }
+
+    #[test]
+    fn backoff_reset_starts_over() {
+        let mut backoff = Backoff::default();
+        backoff.delay(1);
+        backoff.delay(1);
+        backoff.delay(2);
+        backoff.reset(&1);
+        assert!(!backoff.attempts.contains_key(&1));
+        assert_eq!(backoff.attempts.get(&2), Some(&1));
+
+        backoff.waiting.clear();
+        let start = Instant::now();
+        backoff.delay(1);
+        let wait = waits(&backoff, start)[0];
+        assert!((BACKOFF_BASE_MS..BACKOFF_BASE_MS + 1_000).contains(&wait));
+    }
+}
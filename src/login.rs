@@ -1,8 +1,9 @@
 use std::{
-    sync::{Arc, Mutex, atomic::AtomicU64},
+    sync::{Arc, Mutex},
     time::Duration,
 };
 
+use chrono::{DateTime, TimeDelta, Utc};
 use iced::{
     Alignment, Command, Element, Length, Renderer, Theme, theme,
     widget::{
@@ -10,17 +11,26 @@ use iced::{
         text_input,
     },
 };
+use log::{info, warn};
 use sf_api::{
     error::SFError,
     gamestate::GameState,
-    session::{PWHash, ServerConnection, Session},
+    session::{PWHash, Session},
     sso::{SFAccount, SSOAuth, SSOProvider},
 };
 use tokio::time::sleep;
 
 use crate::{
     AccountID, AccountIdent, AccountInfo, AccountPage, Helper, ServerIdent,
-    View, config::AccountConfig, get_server_code, message::Message, top_bar,
+    View,
+    config::{
+        self, ACCOUNT_IMPORT_CSV, ACCOUNT_IMPORT_TOML, AccountConfig,
+        account_import_file, read_account_import,
+    },
+    get_server_code,
+    message::Message,
+    player::AccountStatus,
+    top_bar,
 };
 
 pub struct LoginState {
@@ -30,12 +40,26 @@ pub struct LoginState {
     pub server: String,
     pub remember_me: bool,
     pub error: Option<String>,
+    /// The outcome of a successful test login or import. Failures end up in
+    /// `error`
+    pub test_result: Option<String>,
+    /// Imported accounts are logged in right away
+    pub login_imported: bool,
     pub active_sso: Vec<SSOLogin>,
     pub import_que: Vec<Session>,
     pub google_sso: Arc<Mutex<SSOStatus>>,
     pub steam_sso: Arc<Mutex<SSOStatus>>,
 }
 
+impl LoginState {
+    /// Old results would refer to different credentials after an input
+    /// changes
+    pub fn clear_messages(&mut self) {
+        self.error = None;
+        self.test_result = None;
+    }
+}
+
 pub enum SSOStatus {
     Waiting { url: String },
     Initializing,
@@ -60,6 +84,57 @@ pub enum SSOLoginStatus {
     Success,
 }
 
+/// The first delay before a failed login is retried. Every further attempt
+/// doubles it
+pub const LOGIN_RETRY_BASE_SECS: u64 = 30;
+pub const LOGIN_RETRY_MAX_SECS: u64 = 30 * 60;
+/// The amount of retries, after which a transient error is treated as fatal
+pub const LOGIN_RETRY_ATTEMPTS: u32 = 10;
+
+/// Whether the login error should go away by itself, like a lost connection,
+/// maintenance or throttling. Everything else, like wrong credentials, needs
+/// the user
+pub fn is_transient_login_error(error: &SFError) -> bool {
+    /// Parts of the server errors, that are only temporary
+    const TRANSIENT: [&str; 5] = [
+        "maintenance", "too many requests", "try again later",
+        "cannot do this right now", "temporarily unavailable",
+    ];
+    match error {
+        SFError::ConnectionError => true,
+        SFError::ServerError(error) => {
+            let error = error.to_lowercase();
+            TRANSIENT.iter().any(|a| error.contains(a))
+        }
+        _ => false,
+    }
+}
+
+/// How far the clock of the server is ahead of the local one. The game only
+/// sends timestamps in the time zone of the server, so this uses the `Date`
+/// header of the server instead, which is always in UTC
+pub async fn measure_clock_skew(url: String) -> Option<TimeDelta> {
+    let client = config::http_client()
+        .timeout(Duration::from_secs(10))
+        .build()
+        .ok()?;
+    let sent = Utc::now();
+    let resp = match client.head(url).send().await {
+        Ok(resp) => resp,
+        Err(e) => {
+            warn!("Could not measure the clock of the server: {e}");
+            return None;
+        }
+    };
+    let received = Utc::now();
+    let date = resp.headers().get(reqwest::header::DATE)?.to_str().ok()?;
+    let server = DateTime::parse_from_rfc2822(date).ok()?.with_timezone(&Utc);
+    // The header only has whole seconds, so it is half a second behind on
+    // average
+    let server = server + TimeDelta::milliseconds(500);
+    Some(server - (sent + (received - sent) / 2))
+}
+
 impl LoginState {
     pub fn view(
         &self,
@@ -124,6 +199,17 @@ impl LoginState {
             },
         );
 
+        let status_msg = || {
+            let msg = match (&self.error, &self.test_result) {
+                (Some(err), _) => format!("Error: {err}"),
+                (None, Some(res)) => res.clone(),
+                (None, None) => String::new(),
+            };
+            row!(horizontal_space(), text(msg), horizontal_space())
+                .width(Length::Fill)
+                .align_items(Alignment::Center)
+        };
+
         let current_login = match self.login_typ {
             LoginType::SFAccount => {
                 let title = text("S&F Account Login").size(20);
@@ -137,28 +223,25 @@ impl LoginState {
 
                 let sso_login_button =
                     button("Login").on_press(Message::LoginSFSubmit).padding(4);
+                let test_button = button("Test")
+                    .on_press(Message::LoginSFTest)
+                    .padding(4)
+                    .style(theme::Button::Secondary);
+                let buttons = row!(sso_login_button, test_button).spacing(10);
 
                 let remember_me = checkbox("Remember me", self.remember_me)
                     .on_toggle(Message::RememberMe);
                 let options_row = row!(remember_me)
                     .width(Length::Fill)
                     .align_items(Alignment::Start);
-                let error_msg = row!(
-                    horizontal_space(),
-                    text(
-                        self.error
-                            .as_ref()
-                            .map(|a| format!("Error: {a}"))
-                            .unwrap_or_default()
-                    ),
-                    horizontal_space()
-                )
-                .width(Length::Fill)
-                .align_items(Alignment::Center);
 
                 column![
-                    title, name_input, pw_input, options_row, sso_login_button,
-                    error_msg
+                    title,
+                    name_input,
+                    pw_input,
+                    options_row,
+                    buttons,
+                    status_msg()
                 ]
             }
             LoginType::Regular => {
@@ -176,6 +259,12 @@ impl LoginState {
                 let regular_login_button = button("Login")
                     .on_press(Message::LoginRegularSubmit)
                     .padding(4);
+                let test_button = button("Test")
+                    .on_press(Message::LoginRegularTest)
+                    .padding(4)
+                    .style(theme::Button::Secondary);
+                let buttons =
+                    row!(regular_login_button, test_button).spacing(10);
 
                 let remember_me = checkbox("Remember me", self.remember_me)
                     .on_toggle(Message::RememberMe);
@@ -183,9 +272,32 @@ impl LoginState {
                     .width(Length::Fill)
                     .align_items(Alignment::Start);
 
+                let import_button = button("Import accounts")
+                    .on_press(Message::ImportAccounts)
+                    .padding(4)
+                    .style(theme::Button::Secondary);
+                let login_imported =
+                    checkbox("Log in after import", self.login_imported)
+                        .on_toggle(Message::SetLoginImported);
+                let import_row = row!(import_button, login_imported)
+                    .spacing(10)
+                    .align_items(Alignment::Center);
+                let import_info = text(format!(
+                    "Reads {ACCOUNT_IMPORT_CSV} (name,server,password) or \
+                     {ACCOUNT_IMPORT_TOML}"
+                ))
+                .size(12);
+
                 column![
-                    title, name_input, pw_input, server_input, options_row,
-                    regular_login_button
+                    title,
+                    name_input,
+                    pw_input,
+                    server_input,
+                    options_row,
+                    buttons,
+                    status_msg(),
+                    import_row,
+                    import_info
                 ]
             }
             LoginType::Steam => {
@@ -395,7 +507,11 @@ impl SSOValidator {
         &self,
     ) -> Result<Option<(Vec<Result<Session, SFError>>, String)>, SFError> {
         sleep(Duration::from_millis(fastrand::u64(500..=1000))).await;
-        let mut auth = SSOAuth::new(self.provider).await?;
+        let mut auth = SSOAuth::new_with_options(
+            self.provider,
+            config::connection_options(),
+        )
+        .await?;
         {
             *self.status.lock().unwrap() = SSOStatus::Waiting {
                 url: auth.auth_url().to_string(),
@@ -436,7 +552,7 @@ impl Helper {
         let name = name.trim().to_string();
         let server = server.trim().to_string();
 
-        let Some(con) = ServerConnection::new(&server) else {
+        let Some(con) = config::server_connection(&server) else {
             self.login_state.error = Some("Invalid Server URL".to_string());
             return Command::none();
         };
@@ -447,6 +563,115 @@ impl Helper {
         self.login(session, remember, PlayerAuth::Normal(pw_hash), auto_login)
     }
 
+    /// Logs in with the credentials of the regular login form and throws the
+    /// session away again. Nothing is added to the servers
+    pub fn test_login_regular(&mut self) -> Command<Message> {
+        let name = self.login_state.name.trim().to_string();
+        let server = self.login_state.server.trim().to_string();
+        self.login_state.error = None;
+        self.login_state.test_result = None;
+
+        let Some(con) = config::server_connection(&server) else {
+            self.login_state.error = Some("Invalid Server URL".to_string());
+            return Command::none();
+        };
+        let pw_hash = PWHash::new(&self.login_state.password);
+        let mut session =
+            sf_api::session::Session::new_hashed(&name, pw_hash, con);
+        let permits = self.login_permits.clone();
+        Command::perform(
+            async move {
+                let _permit = permits.acquire_owned().await;
+                session.login().await?;
+                Ok(format!("Logged in {name} successfully"))
+            },
+            |res: Result<String, SFError>| {
+                Message::LoginTestResult(res.map_err(|a| a.to_string()))
+            },
+        )
+    }
+
+    /// Logs in with the credentials of the S&F account form, without
+    /// importing any of its characters
+    pub fn test_login_sf(&mut self) -> Command<Message> {
+        let name = self.login_state.name.trim().to_string();
+        let pw_hash = PWHash::new(&self.login_state.password);
+        self.login_state.error = None;
+        self.login_state.test_result = None;
+
+        let permits = self.login_permits.clone();
+        Command::perform(
+            async move {
+                let _permit = permits.acquire_owned().await;
+                let account = SFAccount::login_hashed_with_options(
+                    name.clone(),
+                    pw_hash,
+                    config::connection_options(),
+                )
+                .await
+                .map_err(|a| a.to_string())?;
+                let chars =
+                    account.characters().await.map_err(|a| a.to_string())?;
+                Ok(format!(
+                    "Logged in {name} successfully ({} characters)",
+                    chars.len()
+                ))
+            },
+            Message::LoginTestResult,
+        )
+    }
+
+    /// Adds all new accounts of the import file to the config and logs them
+    /// in, if the user wants that
+    pub fn import_accounts(&mut self) -> Command<Message> {
+        self.login_state.clear_messages();
+        let file = account_import_file();
+        let creds = match read_account_import(file) {
+            Ok(creds) => creds,
+            Err(e) => {
+                self.login_state.error = Some(e);
+                return Command::none();
+            }
+        };
+        let (added, skipped) = self.config.import_accounts(creds);
+        if !added.is_empty() {
+            self.config_dirty = true;
+        }
+        let mut msg = format!(
+            "Imported {} accounts, skipped {skipped} duplicates",
+            added.len()
+        );
+        // The import file contains the passwords in plain text, which are
+        // not needed anymore, once they are hashed in the config
+        match std::fs::remove_file(file) {
+            Ok(()) => msg.push_str(&format!(". Deleted {file}")),
+            Err(e) => {
+                warn!("Could not delete {file}: {e}");
+                msg.push_str(&format!(
+                    ". Could not delete {file}. Delete it yourself, as it \
+                     contains your passwords in plain text"
+                ));
+            }
+        }
+        info!("{msg}");
+        self.login_state.test_result = Some(msg);
+
+        if !self.login_state.login_imported {
+            return Command::none();
+        }
+        // Same stagger as the auto login on startup
+        let commands = added.into_iter().enumerate().map(|(pos, account)| {
+            Command::perform(
+                sleep(Duration::from_millis(pos as u64 * 200)),
+                move |_| Message::Login {
+                    account,
+                    auto_login: true,
+                },
+            )
+        });
+        Command::batch(commands)
+    }
+
     pub fn login(
         &mut self,
         mut session: sf_api::session::Session,
@@ -455,7 +680,8 @@ impl Helper {
         auto_login: bool,
     ) -> Command<Message> {
         let server_ident = ServerIdent::new(session.server_url().as_str());
-        let Some(connection) = ServerConnection::new(&server_ident.url) else {
+        let Some(connection) = config::server_connection(&server_ident.url)
+        else {
             self.login_state.error =
                 Some("Server Url is not valid".to_string());
             return Command::none();
@@ -465,6 +691,14 @@ impl Helper {
             .chars()
             .map(|a| a.to_ascii_lowercase())
             .collect();
+        match config::active_proxy() {
+            Some(proxy) => info!(
+                "Logging in {name} on {} via {}",
+                server_ident.ident,
+                config::redact_proxy(proxy)
+            ),
+            None => info!("Logging in {name} on {}", server_ident.ident),
+        }
 
         let account_id = AccountID::new();
         let account_ident = AccountIdent {
@@ -492,38 +726,225 @@ impl Helper {
             };
         }
         server.accounts.insert(info.ident.account, info);
-        static WAITING: AtomicU64 = AtomicU64::new(0);
+        let permits = self.login_permits.clone();
+        let url = session.server_url().to_string();
 
         Command::perform(
             async move {
-                // This likely has some logic issues
-                let w =
-                    WAITING.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
-                if w > 0 {
-                    sleep(Duration::from_secs(w)).await;
-                }
-                let resp = session.login().await.inspect(|_| {
-                    WAITING.fetch_sub(1, std::sync::atomic::Ordering::SeqCst);
-                })?;
+                // The permit is held until the login is done, successful or
+                // not
+                let _permit = permits.acquire_owned().await;
+                let resp = session.login().await?;
                 let gs = GameState::new(resp)?;
                 let gs = Box::new(gs);
-                Ok((gs, Box::new(session)))
+                let clock_skew = measure_clock_skew(url).await;
+                Ok((gs, Box::new(session), clock_skew))
             },
             move |a: Result<_, SFError>| match a {
-                Ok((gs, session)) => Message::LoggininSuccess {
+                Ok((gs, session, clock_skew)) => Message::LoggininSuccess {
                     ident: account_ident,
                     gs,
                     session,
                     remember,
+                    clock_skew,
                 },
                 Err(err) => Message::LoggininFailure {
                     ident: account_ident,
+                    transient: is_transient_login_error(&err),
                     error: err.to_string(),
                 },
             },
         )
     }
 
+    /// Logs the account in again after the delay. The timer is ignored, if
+    /// the account was logged in by other means, or another timer was
+    /// started for it in the meantime
+    pub fn schedule_relogin(
+        &mut self,
+        ident: AccountIdent,
+        delay: Duration,
+    ) -> Command<Message> {
+        let Some(account) = self
+            .servers
+            .get_mut(&ident.server_id)
+            .and_then(|a| a.accounts.get_mut(&ident.account))
+        else {
+            return Command::none();
+        };
+        self.relogin_timers += 1;
+        let timer = self.relogin_timers;
+        account.relogin_timer = Some(timer);
+        Command::perform(sleep(delay), move |_| Message::ScheduledRelogin {
+            ident,
+            timer,
+        })
+    }
+
+    /// Logs an account, that ran into a fatal error, in again. Regular
+    /// accounts reuse their password hash. SSO characters have no session to
+    /// fall back on, so the SSO login of the account they belong to is redone
+    pub fn retry_login(&mut self, ident: AccountIdent) -> Command<Message> {
+        let Some(server) = self.servers.get_mut(&ident.server_id) else {
+            return Command::none();
+        };
+        let Some(account) = server.accounts.get(&ident.account) else {
+            return Command::none();
+        };
+        if !matches!(
+            &*account.status.lock().unwrap(),
+            AccountStatus::FatalError(_)
+                | AccountStatus::WaitingToRetry { .. }
+                | AccountStatus::LoggedOutIdle { .. }
+        ) {
+            return Command::none();
+        }
+        let name = account.name.clone();
+        let url = server.ident.url.clone();
+        let server_ident = ServerIdent::new(&url);
+
+        let sso_acc = self.config.sso_account_of(&name, &server_ident);
+
+        let pw_hash = match &account.auth {
+            PlayerAuth::Normal(pw_hash) => Some(pw_hash.clone()),
+            PlayerAuth::SSO if sso_acc.is_none() => {
+                self.login_state.error = Some(format!(
+                    "No stored SSO account for {name} to log in with"
+                ));
+                return Command::none();
+            }
+            PlayerAuth::SSO => None,
+        };
+
+        server.accounts.remove(&ident.account);
+        let viewing = match &mut self.current_view {
+            View::Account { ident: current, .. } => *current == ident,
+            View::Overview { selected, .. } => {
+                selected.remove(&ident);
+                false
+            }
+            _ => false,
+        };
+
+        match (pw_hash, sso_acc) {
+            (Some(pw_hash), _) => {
+                self.login_regular(name, url, pw_hash, false, !viewing)
+            }
+            (None, Some((sso_name, pw_hash))) => {
+                if viewing {
+                    self.current_view = View::Login;
+                }
+                // A finished SSO login would otherwise block the new one
+                let sso_ident = SSOIdent::SF(sso_name.clone());
+                self.login_state.active_sso.retain(|a| {
+                    a.ident != sso_ident
+                        || matches!(a.status, SSOLoginStatus::Loading)
+                });
+                self.login_sf_acc(sso_name, pw_hash, false, true)
+            }
+            (None, None) => Command::none(),
+        }
+    }
+
+    /// SSO characters can not log in again by themselves, once their SSO
+    /// session expired. This redoes the SSO login of the account they belong
+    /// to. The new session is imported into the existing account by
+    /// `import_reauthed()`, once it arrives
+    pub fn reauth_sso(
+        &mut self,
+        ident: AccountIdent,
+        interactive: bool,
+    ) -> Command<Message> {
+        let Some(server) = self.servers.get_mut(&ident.server_id) else {
+            return Command::none();
+        };
+        let Some(account) = server.accounts.get_mut(&ident.account) else {
+            return Command::none();
+        };
+        account.needs_reauth = true;
+        let name = account.name.clone();
+        let server_ident = ServerIdent::new(&server.ident.url);
+
+        if let Some((sso_name, pw_hash)) =
+            self.config.sso_account_of(&name, &server_ident)
+        {
+            let sso_ident = SSOIdent::SF(sso_name.clone());
+            if self.login_state.active_sso.iter().any(|a| {
+                a.ident == sso_ident
+                    && matches!(a.status, SSOLoginStatus::Loading)
+            }) {
+                return Command::none();
+            }
+            self.login_state.active_sso.retain(|a| a.ident != sso_ident);
+            let view = std::mem::replace(&mut self.current_view, View::Login);
+            let login = self.login_sf_acc(sso_name, pw_hash, false, false);
+            // The SF login switches to its account list, which is only
+            // relevant, when the user asked for this
+            if !interactive {
+                self.current_view = view;
+            }
+            return login;
+        }
+
+        // Google & Steam logins are not stored, so the user has to go through
+        // the provider again
+        self.login_state
+            .active_sso
+            .retain(|a| matches!(a.ident, SSOIdent::SF(_)));
+        if interactive {
+            self.login_state.error = Some(format!(
+                "Log in with Google or Steam again to re-authenticate {name}"
+            ));
+            self.current_view = View::Login;
+        }
+        Command::none()
+    }
+
+    /// Logs the SSO sessions in, that belong to accounts waiting for a
+    /// re-authentication, and hands them to the existing accounts
+    pub fn import_reauthed(&mut self) -> Command<Message> {
+        let mut commands = vec![];
+        for server in self.servers.0.values_mut() {
+            for account in server.accounts.values_mut() {
+                if !account.needs_reauth {
+                    continue;
+                }
+                let pos = self.login_state.import_que.iter().position(|s| {
+                    ServerIdent::new(s.server_url().as_str()) == server.ident
+                        && s.username().to_lowercase() == account.name
+                });
+                let Some(pos) = pos else {
+                    continue;
+                };
+                let mut session = self.login_state.import_que.remove(pos);
+                account.needs_reauth = false;
+                *account.status.lock().unwrap() = AccountStatus::LoggingInAgain;
+                let ident = account.ident;
+                info!("Importing the new SSO session of {ident}");
+                let permits = self.login_permits.clone();
+                commands.push(Command::perform(
+                    async move {
+                        let _permit = permits.acquire_owned().await;
+                        let resp = session.login().await?;
+                        let gs = GameState::new(resp)?;
+                        Ok((Box::new(gs), Box::new(session)))
+                    },
+                    move |res: Result<_, SFError>| match res {
+                        Ok((gs, session)) => {
+                            Message::PlayerRelogSuccess { ident, gs, session }
+                        }
+                        Err(err) => Message::LoggininFailure {
+                            ident,
+                            transient: is_transient_login_error(&err),
+                            error: err.to_string(),
+                        },
+                    },
+                ));
+            }
+        }
+        Command::batch(commands)
+    }
+
     pub fn login_sf_acc(
         &mut self,
         name: String,
@@ -548,9 +969,16 @@ impl Helper {
 
         let n2 = name.clone();
         let p2 = pwhash.clone();
+        let permits = self.login_permits.clone();
         Command::perform(
             async move {
-                let account = SFAccount::login_hashed(n2, p2).await?;
+                let _permit = permits.acquire_owned().await;
+                let account = SFAccount::login_hashed_with_options(
+                    n2,
+                    p2,
+                    config::connection_options(),
+                )
+                .await?;
                 account.characters().await.into_iter().flatten().collect()
             },
             move |res| match res {
@@ -575,3 +1003,40 @@ pub enum PlayerAuth {
     Normal(PWHash),
     SSO,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn server_error(msg: &str) -> SFError {
+        SFError::ServerError(msg.to_string())
+    }
+
+    #[test]
+    fn transient_login_errors() {
+        assert!(is_transient_login_error(&SFError::ConnectionError));
+        assert!(is_transient_login_error(&server_error(
+            "Server is in maintenance"
+        )));
+        assert!(is_transient_login_error(&server_error("Too many requests")));
+        assert!(is_transient_login_error(&server_error(
+            "Please try again later"
+        )));
+        assert!(is_transient_login_error(&server_error(
+            "cannot do this right now2"
+        )));
+    }
+
+    #[test]
+    fn permanent_login_errors() {
+        assert!(!is_transient_login_error(&server_error("wrong password")));
+        assert!(!is_transient_login_error(&server_error("player not found")));
+        assert!(!is_transient_login_error(&server_error(
+            "connection to this account is blocked"
+        )));
+        assert!(!is_transient_login_error(&server_error(
+            "you can change your name later"
+        )));
+        assert!(!is_transient_login_error(&SFError::EmptyResponse));
+    }
+}
@@ -0,0 +1,92 @@
+use std::{collections::VecDeque, sync::Mutex};
+
+use chrono::{DateTime, Local};
+use log::{Level, LevelFilter, Log, Metadata, Record};
+
+/// The amount of lines kept for the log viewer. Older lines are dropped
+pub const LOG_BUFFER_LINES: usize = 2000;
+
+static LOG_BUFFER: Mutex<VecDeque<LogLine>> = Mutex::new(VecDeque::new());
+
+#[derive(Debug, Clone)]
+pub struct LogLine {
+    pub time: DateTime<Local>,
+    pub level: Level,
+    pub target: String,
+    pub msg: String,
+}
+
+impl std::fmt::Display for LogLine {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} | {:5} | {} | {}",
+            self.time.format("%Y-%m-%d %H:%M:%S"),
+            self.level,
+            self.target,
+            self.msg
+        )
+    }
+}
+
+/// Forwards everything to log4rs and keeps the recent lines in memory, so
+/// that they can be viewed without a terminal
+struct BufferedLogger {
+    inner: log4rs::Logger,
+}
+
+impl BufferedLogger {
+    fn should_buffer(metadata: &Metadata) -> bool {
+        if metadata.target().starts_with("sf_scrapbook_helper") {
+            metadata.level() <= Level::Debug
+        } else {
+            metadata.level() <= Level::Warn
+        }
+    }
+}
+
+impl Log for BufferedLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        Self::should_buffer(metadata) || self.inner.enabled(metadata)
+    }
+
+    fn log(&self, record: &Record) {
+        if Self::should_buffer(record.metadata()) {
+            let line = LogLine {
+                time: Local::now(),
+                level: record.level(),
+                target: record.target().to_string(),
+                msg: record.args().to_string(),
+            };
+            let mut buffer = LOG_BUFFER.lock().unwrap();
+            if buffer.len() >= LOG_BUFFER_LINES {
+                buffer.pop_front();
+            }
+            buffer.push_back(line);
+        }
+        self.inner.log(record);
+    }
+
+    fn flush(&self) {
+        self.inner.flush();
+    }
+}
+
+pub fn init(config: log4rs::Config) -> Result<(), log::SetLoggerError> {
+    let inner = log4rs::Logger::new(config);
+    let max_level = inner.max_log_level().max(LevelFilter::Debug);
+    log::set_boxed_logger(Box::new(BufferedLogger { inner }))?;
+    log::set_max_level(max_level);
+    Ok(())
+}
+
+/// The buffered lines at or above the given level, oldest first
+pub fn recent_lines(level: Level) -> Vec<LogLine> {
+    LOG_BUFFER
+        .lock()
+        .unwrap()
+        .iter()
+        .filter(|a| a.level <= level)
+        .cloned()
+        .collect()
+}
@@ -1,9 +1,11 @@
 // No changes needed; context-only patch removed; skipping.
 #![windows_subsystem = "windows"]
+mod automation;
 mod backup;
 mod config;
 mod crawler;
 mod login;
+mod logs;
 mod message;
 mod player;
 mod server;
@@ -11,18 +13,25 @@ mod ui;
 
 use std::{
     collections::{BTreeMap, HashMap, HashSet, hash_map::Entry},
-    sync::{Arc, Mutex, atomic::AtomicU64},
+    sync::{
+        Arc, Mutex, OnceLock,
+        atomic::{self, AtomicBool, AtomicU8, AtomicU64},
+    },
     time::Duration,
 };
 
 use chrono::{Local, NaiveDate, Utc};
 use clap::{Parser, Subcommand};
-use config::{AccountConfig, Config};
+use config::{
+    AccountConfig, BattleOrdering, Config, LastView, MINUTE_REFRESH_MS,
+    SFCharIdent,
+};
 use crawler::{CrawlAction, Crawler, CrawlerState, CrawlingOrder, WorkerQue};
 use iced::{
-    Alignment, Application, Command, Element, Length, Settings, Subscription,
-    Theme, executor, subscription, theme,
-    widget::{button, container, horizontal_space, row, text},
+    Alignment, Application, Command, Element, Length, Point, Settings, Size,
+    Subscription, Theme, executor, subscription, theme,
+    widget::{button, container, horizontal_space, row, text, text_editor},
+    window,
 };
 use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
 use log::{debug, info, trace};
@@ -44,7 +53,6 @@ use serde::{Deserialize, Serialize};
 use server::{CrawlingStatus, ServerIdent, ServerInfo, Servers};
 use sf_api::{
     gamestate::{character::Class, unlockables::EquipmentIdent},
-    session::ServerConnection,
     sso::{SSOProvider, ServerLookup},
 };
 use tokio::time::sleep;
@@ -52,6 +60,7 @@ use tokio::time::sleep;
 use crate::{
     config::{AccountCreds, AvailableTheme},
     message::Message,
+    ui::{OverviewSort, characters::CharacterSort},
 };
 pub const PER_PAGE: usize = 51;
 
@@ -84,6 +93,9 @@ pub struct ServerSelect {
     all: bool,
     #[arg(short, long, value_delimiter = ' ', num_args = 1..)]
     urls: Option<Vec<String>>,
+    /// Crawl just this server to completion, write the backup and exit
+    #[arg(short, long)]
+    server: Option<String>,
 }
 
 impl Args {
@@ -139,11 +151,21 @@ fn main() -> iced::Result {
 
     let is_headless = args.is_headless();
     let config = get_log_config(is_headless);
-    if let Err(e) = log4rs::init_config(config) {
+    if let Err(e) = logs::init(config) {
         eprintln!("Warning: failed to initialize logging: {}", e);
     }
     info!("Starting up");
 
+    if let Ok(config) = Config::restore()
+        && let Some(proxy) = config.proxy()
+    {
+        config::activate_proxy(proxy);
+        info!(
+            "Routing all connections through the proxy {}",
+            config::redact_proxy(proxy)
+        );
+    }
+
     let mut settings = Settings::with_flags(args);
     settings.window.min_size = Some(iced::Size {
         width: 700.0,
@@ -151,6 +173,23 @@ fn main() -> iced::Result {
     });
     settings.default_text_size = 13.0f32.into();
     settings.window.visible = !is_headless;
+    // Closing is handled by `Message::SaveAllAndExit`, so that crawling
+    // progress can be written to disk first
+    settings.window.exit_on_close_request = false;
+
+    if !is_headless && let Ok(config) = Config::restore() {
+        if let (Some(width), Some(height)) =
+            (config.window_width, config.window_height)
+        {
+            settings.window.size =
+                Size::new(width.max(700) as f32, height.max(700) as f32);
+        }
+        if let (Some(x), Some(y)) = (config.window_x, config.window_y) {
+            _ = SAVED_WINDOW_POS.set(Point::new(x as f32, y as f32));
+            settings.window.position =
+                window::Position::SpecificWith(restore_window_position);
+        }
+    }
 
     let raw_img = include_bytes!("../assets/icon.ico");
     let img =
@@ -166,14 +205,63 @@ fn main() -> iced::Result {
     Helper::run(settings)
 }
 
+/// The window position from the config. `Position::SpecificWith` only
+/// takes a plain fn, so this can not be captured
+static SAVED_WINDOW_POS: OnceLock<Point> = OnceLock::new();
+
+/// Places the window at the saved position, but keeps it on the primary
+/// monitor. The saved position may be off-screen, if the monitor it was on
+/// has been disconnected since
+fn restore_window_position(window: Size, monitor: Size) -> Point {
+    let saved = SAVED_WINDOW_POS.get().copied().unwrap_or_default();
+    let max_x = (monitor.width - window.width).max(0.0);
+    let max_y = (monitor.height - window.height).max(0.0);
+    Point::new(saved.x.clamp(0.0, max_x), saved.y.clamp(0.0, max_y))
+}
+
 struct Helper {
     servers: Servers,
     current_view: View,
     login_state: LoginState,
     config: Config,
-    should_update: bool,
+    /// The version of a newer release, if there is one
+    available_update: Option<String>,
     class_images: ClassImages,
     cli_crawling: Option<CLICrawling>,
+    /// Mirrors `config.automation_paused`, so that running automation
+    /// subscriptions can see changes without being recreated
+    automation_paused: Arc<AtomicBool>,
+    /// Limits the amount of logins running at the same time
+    login_permits: Arc<tokio::sync::Semaphore>,
+    /// Mirrors `config.crawl_page_share` for the running crawlers
+    crawl_page_share: Arc<AtomicU8>,
+    /// The window was closed and the backups are being written
+    saving_on_exit: bool,
+    /// Theme files found in the theme directory, when the settings were
+    /// opened
+    custom_themes: Vec<AvailableTheme>,
+    /// Notifications, that will be shown together once things calm down
+    pending_notifications: Vec<String>,
+    /// (server, name) => transient login failures in a row. Survives the
+    /// account being replaced by `retry_login()`
+    login_retries: HashMap<(ServerID, String), u32>,
+    /// The id of the last relogin timer, that was started
+    relogin_timers: u64,
+    /// The config has changed since it was last written to disk. Changes
+    /// are written by `Message::FlushConfig`, so that many changes in a
+    /// short time only cause a single write
+    config_dirty: bool,
+    /// Counts the resize and move events of the window. The geometry is only
+    /// saved, once no newer event arrived for `WINDOW_SAVE_DELAY_MS`
+    window_changes: u64,
+    /// A logout button has been clicked once and now waits for the
+    /// confirming second click
+    logout_armed: bool,
+    /// The account page of the last session, which is shown once that
+    /// account has logged in. Dropped as soon as the user changes the view
+    restore_view: Option<(SFCharIdent, AccountPage)>,
+    /// The editor for the notes of the account, whose options are shown
+    notes_editor: Option<(AccountIdent, text_editor::Content)>,
 }
 
 struct CLICrawling {
@@ -181,6 +269,9 @@ struct CLICrawling {
     mbp: MultiProgress,
     threads: usize,
     active: usize,
+    /// Exit with a non-zero code, as soon as anything goes wrong. Used, when
+    /// a single explicit server is crawled
+    exit_on_error: bool,
 }
 
 struct ClassImages {
@@ -264,9 +355,25 @@ enum View {
     Overview {
         selected: HashSet<AccountIdent>,
         action: Option<ActionSelection>,
+        /// Only rows, where the name or server code contain this are shown
+        filter: String,
+        sort: OverviewSort,
+        sort_desc: bool,
+    },
+    /// Every character in one flat table
+    Characters {
+        /// Only rows, where the name, server code or class contain this are
+        /// shown
+        filter: String,
+        sort: CharacterSort,
+        sort_desc: bool,
     },
     Login,
     Settings,
+    Logs {
+        /// The least severe level shown
+        level: log::Level,
+    },
 }
 
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
@@ -275,14 +382,32 @@ pub enum ActionSelection {
     Character(AccountIdent),
 }
 
-#[derive(Debug, PartialEq, Eq, Clone, Copy)]
-enum AccountPage {
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AccountPage {
     Scrapbook,
     Underworld,
     Automation,
     Options,
 }
 
+/// Keyboard shortcuts. Keys typed into text inputs never trigger these
+#[derive(Debug, Clone, Copy)]
+enum Shortcut {
+    /// Esc: Does the same as the back button of the account, overview and
+    /// settings views
+    Back,
+    /// Ctrl+1..4: Switches between the pages of the account view
+    SubPage(AccountPage),
+    /// Ctrl+F: Focuses the filter of the overview
+    FocusFilter,
+}
+
+/// The id of the name/server filter input in the overview
+const OVERVIEW_FILTER_ID: &str = "overview_filter";
+/// How long the window has to stay put, before its geometry is saved
+const WINDOW_SAVE_DELAY_MS: u64 = 1000;
+
 fn get_server_code(server: &str) -> String {
     let server = server.trim_start_matches("https:");
     let server = server.trim_start_matches("http:");
@@ -363,6 +488,8 @@ impl Application for Helper {
                 password: String::new(),
                 server: "f1.sfgame.net".to_string(),
                 error: None,
+                test_result: None,
+                login_imported: false,
                 remember_me: true,
                 active_sso: vec![],
                 import_que: vec![],
@@ -370,16 +497,34 @@ impl Application for Helper {
                 steam_sso: Arc::new(Mutex::new(SSOStatus::Initializing)),
             },
             current_view: View::Login,
-            should_update: false,
+            available_update: None,
             class_images: ClassImages::new(),
+            automation_paused: Arc::new(AtomicBool::new(
+                config.automation_paused,
+            )),
+            crawl_page_share: Arc::new(AtomicU8::new(config.crawl_page_share)),
+            login_permits: Arc::new(tokio::sync::Semaphore::new(
+                config.max_concurrent_logins(),
+            )),
+            saving_on_exit: false,
+            custom_themes: vec![],
+            pending_notifications: vec![],
+            login_retries: Default::default(),
+            relogin_timers: 0,
+            config_dirty: false,
+            window_changes: 0,
+            logout_armed: false,
+            restore_view: None,
+            notes_editor: None,
             config,
             cli_crawling: None,
         };
 
-        let fetch_update =
-            Command::perform(async { check_update().await }, |res| {
-                Message::UpdateResult(res.unwrap_or_default())
-            });
+        let ignored = config.ignored_update_version.clone();
+        let fetch_update = Command::perform(
+            async move { check_update(ignored).await },
+            |res| Message::UpdateResult(res.unwrap_or_default()),
+        );
         let mut commands = vec![fetch_update];
 
         if let Some(CLICommand::Crawl {
@@ -393,9 +538,17 @@ impl Application for Helper {
                 mbp: MultiProgress::new(),
                 active: concurrency,
                 threads,
+                exit_on_error: false,
             };
 
-            if let Some(servers) = servers.urls {
+            if let Some(server) = servers.server {
+                info.todo_servers = vec![server];
+                info.active = 1;
+                info.exit_on_error = true;
+                commands.push(Command::perform(async {}, move |_| {
+                    Message::NextCLICrawling
+                }))
+            } else if let Some(servers) = servers.urls {
                 info.todo_servers = servers;
 
                 for _ in 0..concurrency {
@@ -475,9 +628,29 @@ impl Application for Helper {
             helper.current_view = View::Overview {
                 selected: Default::default(),
                 action: Default::default(),
+                filter: Default::default(),
+                sort: Default::default(),
+                sort_desc: false,
             };
         }
 
+        match helper.config.last_view.clone() {
+            LastView::Overview => {}
+            LastView::Settings => helper.current_view = View::Settings,
+            LastView::Account { ident, page } => {
+                // Characters, that are not logged in automatically, would
+                // never show up, so the overview is kept for them
+                let server_id = ServerIdent::new(&ident.server).id;
+                if helper
+                    .config
+                    .get_char_conf(&ident.name, server_id)
+                    .is_some_and(|a| a.login)
+                {
+                    helper.restore_view = Some((ident, page));
+                }
+            }
+        }
+
         (helper, Command::batch(commands))
     }
 
@@ -493,36 +666,77 @@ impl Application for Helper {
         &mut self,
         message: Self::Message,
     ) -> iced::Command<Self::Message> {
-        self.handle_msg(message)
+        let command = self.handle_msg(message);
+        self.remember_view();
+        self.sync_notes_editor();
+        command
     }
 
     fn view(
         &self,
     ) -> iced::Element<'_, Self::Message, Self::Theme, iced::Renderer> {
+        if self.saving_on_exit {
+            return container(text("Saving...").size(30))
+                .width(Length::Fill)
+                .height(Length::Fill)
+                .center_x()
+                .center_y()
+                .into();
+        }
         self.view_current_page()
     }
 
     fn subscription(&self) -> Subscription<Self::Message> {
         #[derive(Debug, Hash, PartialEq, Eq)]
         enum SubIdent {
-            AutoPoll(AccountIdent),
-            AutoBattle(AccountIdent),
+            AutoPoll(AccountIdent, u64),
+            AutoBattle(AccountIdent, bool, i64),
             AutoLure(AccountIdent),
-            AutoMissions(AccountIdent),
+            AutoMissions(
+                AccountIdent,
+                u64,
+                u64,
+                Option<(chrono::NaiveTime, chrono::NaiveTime)>,
+                i64,
+            ),
             SSOCheck(SSOProvider),
             Crawling(usize, ServerID),
+            Autosave(ServerID, u64),
+            RestoreProgress(ServerID),
+            Notifications,
+            ConfigFlush,
+            UiRefresh(u64),
+            PollWatchdog(u64),
+            IdleLogout(u64),
         }
 
         let mut subs = vec![];
+        let automation_delay = self.config.automation_delay_ms();
+        let first_tick = self.config.automation_first_tick_ms();
+        let pause_poll = self.config.automation_pause_poll_ms();
+        let automation_retry = self.config.automation_retry_ms();
+        let max_wait = self.config.automation_max_wait();
+        let poll_interval = self.config.poll_interval_ms();
 
         for (server_id, server) in &self.servers.0 {
             for acc in server.accounts.values() {
+                // Parked accounts stay logged in, but do nothing by themselves
+                if !self.config.char_enabled(&acc.name, *server_id)
+                    || matches!(
+                        &*acc.status.lock().unwrap(),
+                        AccountStatus::LoggedOutIdle { .. }
+                    )
+                {
+                    continue;
+                }
+
                 if self.config.auto_poll {
                     let subscription = subscription::unfold(
-                        SubIdent::AutoPoll(acc.ident),
+                        SubIdent::AutoPoll(acc.ident, poll_interval),
                         AutoPoll {
                             player_status: acc.status.clone(),
                             ident: acc.ident,
+                            interval_ms: poll_interval,
                         },
                         move |a: AutoPoll| async move { (a.check().await, a) },
                     );
@@ -532,11 +746,32 @@ impl Application for Helper {
                 if let Some(si) = &acc.scrapbook_info
                     && si.auto_battle
                 {
+                    let use_mushrooms = self
+                        .config
+                        .get_char_conf(&acc.name, *server_id)
+                        .is_some_and(|cfg| {
+                            match &*acc.status.lock().unwrap() {
+                                AccountStatus::Idle(_, gs)
+                                | AccountStatus::Busy(gs, _) => {
+                                    acc.can_fight_with_mushroom(gs, cfg)
+                                }
+                                _ => false,
+                            }
+                        });
+                    let clock_skew = acc.clock_skew().unwrap_or_default();
                     let subscription = subscription::unfold(
-                        SubIdent::AutoBattle(acc.ident),
+                        // The skew is only known after the login, so the
+                        // checker restarts, once it has been measured
+                        SubIdent::AutoBattle(
+                            acc.ident,
+                            use_mushrooms,
+                            clock_skew.num_seconds(),
+                        ),
                         AutoAttackChecker {
                             player_status: acc.status.clone(),
                             ident: acc.ident,
+                            use_mushrooms,
+                            clock_skew,
                         },
                         move |a: AutoAttackChecker| async move {
                             (a.check().await, a)
@@ -562,11 +797,32 @@ impl Application for Helper {
                 }
 
                 if let Some(cc) = self.config.get_char_conf(&acc.name, server.ident.id)
-                    && (cc.auto_tavern || cc.auto_expeditions || cc.auto_dungeons || cc.auto_pets || cc.auto_guild)
+                    && (cc.auto_tavern || cc.auto_expeditions || cc.any_dungeon_fights() || cc.auto_pets || cc.auto_guild || cc.auto_wheel || cc.auto_calendar || cc.auto_collect_tasks || cc.auto_sell_junk || cc.auto_underworld_upgrade || cc.auto_underworld_souls)
                 {
                     let subscription = subscription::unfold(
-                        SubIdent::AutoMissions(acc.ident),
-                        AutoMissionsChecker { player_status: acc.status.clone(), ident: acc.ident, first_tick: true },
+                        // The delays and window are part of the ident, so that
+                        // changing them restarts the checker with the new
+                        // values
+                        SubIdent::AutoMissions(
+                            acc.ident,
+                            *automation_delay.start(),
+                            *automation_delay.end(),
+                            cc.automation_window,
+                            acc.clock_skew().unwrap_or_default().num_seconds(),
+                        ),
+                        AutoMissionsChecker {
+                            player_status: acc.status.clone(),
+                            ident: acc.ident,
+                            first_tick: true,
+                            paused: self.automation_paused.clone(),
+                            delay_ms: automation_delay.clone(),
+                            first_tick_ms: first_tick.clone(),
+                            pause_poll_ms: pause_poll.clone(),
+                            retry_ms: automation_retry.clone(),
+                            max_wait,
+                            clock_skew: acc.clock_skew().unwrap_or_default(),
+                            window: cc.automation_window,
+                        },
                         move |mut a: AutoMissionsChecker| async move { (a.check().await, a) },
                     );
                     subs.push(subscription);
@@ -577,6 +833,7 @@ impl Application for Helper {
                 crawling_session,
                 threads,
                 que,
+                player_info,
                 ..
             } = &server.crawling
             {
@@ -590,11 +847,48 @@ impl Application for Helper {
                             que: que.clone(),
                             state: session.clone(),
                             server_id: *server_id,
+                            page_share: self.crawl_page_share.clone(),
                         },
                         move |mut a: Crawler| async move { (a.crawl().await, a) },
                     );
                     subs.push(subscription);
                 }
+
+                // An empty backup would replace the last useful one
+                let interval = self.config.autosave_interval_min;
+                if interval > 0 && !player_info.is_empty() {
+                    let server_id = *server_id;
+                    let subscription = subscription::unfold(
+                        SubIdent::Autosave(server_id, interval),
+                        (),
+                        move |_| async move {
+                            sleep(Duration::from_secs(interval * 60)).await;
+                            (Message::SaveHoF(server_id), ())
+                        },
+                    );
+                    subs.push(subscription);
+                }
+            }
+
+            if let CrawlingStatus::Restoring { progress, .. } = &server.crawling
+            {
+                let server_id = *server_id;
+                let subscription = subscription::unfold(
+                    SubIdent::RestoreProgress(server_id),
+                    progress.clone(),
+                    move |progress| async move {
+                        sleep(Duration::from_millis(250)).await;
+                        let msg = Message::RestoreProgress {
+                            server: server_id,
+                            done: progress.done.load(atomic::Ordering::Relaxed),
+                            total: progress
+                                .total
+                                .load(atomic::Ordering::Relaxed),
+                        };
+                        (msg, progress)
+                    },
+                );
+                subs.push(subscription);
             }
         }
 
@@ -621,7 +915,7 @@ impl Application for Helper {
                         }
                         Ok(None) => Message::SSORetry,
                         Err(e) => Message::SSOAuthError {
-                            _error: e.to_string(),
+                            error: e.to_string(),
                         },
                     };
 
@@ -631,11 +925,169 @@ impl Application for Helper {
             subs.push(subscription);
         }
 
+        // Nothing changes in between, but the timers have to count down. Only
+        // the account page shows seconds, everything else would just redraw
+        // the same minutes
+        let ui_refresh = match self.current_view {
+            View::Account { .. } => self.config.ui_refresh_ms(),
+            _ => self.config.ui_refresh_ms().max(MINUTE_REFRESH_MS),
+        };
+        subs.push(subscription::unfold(
+            SubIdent::UiRefresh(ui_refresh),
+            (),
+            move |_| async move {
+                sleep(Duration::from_millis(ui_refresh)).await;
+                (Message::UiTick, ())
+            },
+        ));
+
+        if self.config.auto_poll && self.config.poll_watchdog_min > 0 {
+            subs.push(subscription::unfold(
+                SubIdent::PollWatchdog(self.config.poll_watchdog_min),
+                (),
+                move |_| async move {
+                    sleep(Duration::from_secs(60)).await;
+                    (Message::PollWatchdog, ())
+                },
+            ));
+        }
+
+        if let Some(idle_min) = self.config.auto_logout_idle_min {
+            subs.push(subscription::unfold(
+                SubIdent::IdleLogout(idle_min),
+                (),
+                move |_| async move {
+                    sleep(Duration::from_secs(60)).await;
+                    (Message::IdleLogoutCheck, ())
+                },
+            ));
+        }
+
+        if self.config_dirty {
+            let subscription = subscription::unfold(
+                SubIdent::ConfigFlush,
+                (),
+                move |_| async move {
+                    sleep(Duration::from_secs(2)).await;
+                    (Message::FlushConfig, ())
+                },
+            );
+            subs.push(subscription);
+        }
+
+        if !self.pending_notifications.is_empty() {
+            // Everything, that becomes ready within this delay, is collapsed
+            // into a single notification
+            let subscription = subscription::unfold(
+                SubIdent::Notifications,
+                (),
+                move |_| async move {
+                    sleep(Duration::from_secs(5)).await;
+                    (Message::FlushNotifications, ())
+                },
+            );
+            subs.push(subscription);
+        }
+
+        subs.push(iced::keyboard::on_key_press(|key, modifiers| {
+            use iced::keyboard::{Key, key::Named};
+            let shortcut = match key.as_ref() {
+                Key::Named(Named::Escape) => Shortcut::Back,
+                Key::Character(c) if modifiers.command() => match c {
+                    "f" => Shortcut::FocusFilter,
+                    "1" => Shortcut::SubPage(AccountPage::Scrapbook),
+                    "2" => Shortcut::SubPage(AccountPage::Underworld),
+                    "3" => Shortcut::SubPage(AccountPage::Automation),
+                    "4" => Shortcut::SubPage(AccountPage::Options),
+                    _ => return None,
+                },
+                _ => return None,
+            };
+            Some(Message::Shortcut(shortcut))
+        }));
+
+        subs.push(iced::event::listen_with(|event, _| match event {
+            iced::Event::Window(
+                _,
+                window::Event::Resized { width, height },
+            ) => Some(Message::WindowResized { width, height }),
+            iced::Event::Window(_, window::Event::Moved { x, y }) => {
+                Some(Message::WindowMoved { x, y })
+            }
+            iced::Event::Window(_, window::Event::CloseRequested) => {
+                Some(Message::SaveAllAndExit)
+            }
+            _ => None,
+        }));
+
         Subscription::batch(subs)
     }
 }
 
 impl Helper {
+    /// Stores the current view in the config, so that the next session can
+    /// start there
+    fn remember_view(&mut self) {
+        let last_view = match &self.current_view {
+            View::Overview { .. } => {
+                // The overview is only shown, until the account of the last
+                // session has logged in
+                if self.restore_view.is_some() {
+                    return;
+                }
+                LastView::Overview
+            }
+            View::Settings => LastView::Settings,
+            View::Account { ident, page } => {
+                let Some((server, account)) = self.servers.get_ident(ident)
+                else {
+                    return;
+                };
+                LastView::Account {
+                    ident: SFCharIdent {
+                        name: account.name.clone(),
+                        server: server.ident.url.clone(),
+                    },
+                    page: *page,
+                }
+            }
+            View::Login | View::Logs { .. } | View::Characters { .. } => return,
+        };
+        if !matches!(self.current_view, View::Overview { .. }) {
+            self.restore_view = None;
+        }
+        if self.config.last_view != last_view {
+            self.config.last_view = last_view;
+            self.config_dirty = true;
+        }
+    }
+
+    /// Loads the notes of the account, whose options have just been opened,
+    /// into the editor
+    fn sync_notes_editor(&mut self) {
+        let View::Account {
+            ident,
+            page: AccountPage::Options,
+        } = &self.current_view
+        else {
+            self.notes_editor = None;
+            return;
+        };
+        if self.notes_editor.as_ref().is_some_and(|a| a.0 == *ident) {
+            return;
+        }
+        let Some((server, account)) = self.servers.get_ident(ident) else {
+            return;
+        };
+        let notes = self
+            .config
+            .get_char_conf(&account.name, server.ident.id)
+            .map(|a| a.notes.as_str())
+            .unwrap_or_default();
+        self.notes_editor =
+            Some((*ident, text_editor::Content::with_text(notes)));
+    }
+
     fn force_init_crawling(
         &mut self,
         url: &str,
@@ -643,7 +1095,7 @@ impl Helper {
         pb: ProgressBar,
     ) -> Option<Command<Message>> {
         let ident = ServerIdent::new(url);
-        let connection = ServerConnection::new(url)?;
+        let connection = config::server_connection(url)?;
         pb.enable_steady_tick(Duration::from_millis(30));
         pb.set_prefix(ident.ident.to_string());
         set_full_bar(&pb, "Crawling", 0);
@@ -667,7 +1119,11 @@ impl Helper {
             lvl_skipped_accounts: Default::default(),
             min_level: Default::default(),
             max_level: 9999,
+            hard_min_level: 0,
             self_init: true,
+            paused: false,
+            page_backoff: Default::default(),
+            account_backoff: Default::default(),
         };
 
         server.crawling = CrawlingStatus::Crawling {
@@ -680,10 +1136,24 @@ impl Helper {
             last_update: Local::now(),
             crawling_session: None,
             recent_failures: Default::default(),
+            paused: false,
+            finished: None,
         };
         Some(server.set_threads(threads, &self.config.base_name))
     }
 
+    /// Saves the window geometry, once the window has not been resized or
+    /// moved for a while. Dragging the window causes a flood of events,
+    /// which would otherwise all be written to disk
+    fn window_changed(&mut self) -> Command<Message> {
+        self.window_changes += 1;
+        let change = self.window_changes;
+        Command::perform(
+            sleep(Duration::from_millis(WINDOW_SAVE_DELAY_MS)),
+            move |_| Message::WindowSettled(change),
+        )
+    }
+
     fn has_accounts(&self) -> bool {
         self.servers.0.iter().any(|a| !a.1.accounts.is_empty())
     }
@@ -735,17 +1205,30 @@ impl Helper {
             );
             let mut best_players = find_best(
                 &per_player_counts, player_info, result_limit, &invalid,
+                si.battle_ordering,
             );
 
-            best_players.sort_by(|a, b| {
-                b.missing
-                    .cmp(&a.missing)
-                    .then(a.info.stats.cmp(&b.info.stats))
-                    .then(a.info.level.cmp(&b.info.level))
-            });
+            // Safest first targets are already ordered by `find_best`
+            if si.battle_ordering == BattleOrdering::ItemsFirst {
+                best_players.sort_by(|a, b| {
+                    b.missing
+                        .cmp(&a.missing)
+                        .then(a.info.stats.cmp(&b.info.stats))
+                        .then(a.info.level.cmp(&b.info.level))
+                        .then_with(|| a.info.name.cmp(&b.info.name))
+                });
+            }
 
             si.best = best_players;
 
+            if si.show_missing {
+                si.rarest_missing = rarest_missing(
+                    equipment,
+                    &si.scrapbook.items,
+                    result_limit * 4,
+                );
+            }
+
             for target in &si.best {
                 if target.is_old()
                     && !lock.todo_accounts.contains(&target.info.name)
@@ -784,11 +1267,133 @@ impl Helper {
 
         account.last_updated = Local::now();
 
-        if (has_old || player_info.is_empty()) && *threads == 0 {
-            return server.set_threads(1, &self.config.base_name);
+        // A zero override means the user explicitly stopped this server
+        let restart_threads = self
+            .config
+            .crawl_thread_override(ident.server_id)
+            .map_or(1, |a| a.min(1));
+        if (has_old || player_info.is_empty())
+            && *threads == 0
+            && restart_threads > 0
+        {
+            return server.set_threads(restart_threads, &self.config.base_name);
         }
         Command::none()
     }
+
+    /// Sends the oldest queued manual attack, if the arena has a free fight
+    fn fire_manual_attack(
+        &mut self,
+        ident: AccountIdent,
+    ) -> Option<Command<Message>> {
+        let account = self
+            .servers
+            .get_mut(&ident.server_id)?
+            .accounts
+            .get_mut(&ident.account)?;
+        if account.manual_attack_queue.is_empty() {
+            return None;
+        }
+        let status = account.status.lock().unwrap();
+        let AccountStatus::Idle(_, gs) = &*status else {
+            return None;
+        };
+        let next = gs.arena.next_free_fight.unwrap_or_default();
+        if next > account.server_now() + Duration::from_millis(200) {
+            return None;
+        }
+        drop(status);
+        let target = account.manual_attack_queue.remove(0);
+        Some(self.handle_msg(Message::PlayerAttack { ident, target }))
+    }
+
+    /// Copies the battle orders of all the accounts into the clipboard.
+    /// Targets are server specific, so every character gets its own section
+    fn copy_battle_orders(&self, idents: &[AccountIdent]) -> Command<Message> {
+        use std::fmt::Write;
+
+        let mut sections: Vec<_> = idents
+            .iter()
+            .filter_map(|ident| {
+                let (server, account) = self.servers.get_ident(ident)?;
+                let order = self.battle_order(ident)?;
+                Some((&server.ident.ident, &account.name, order))
+            })
+            .collect();
+        sections.sort();
+
+        let mut res = String::new();
+        for (server, name, order) in sections {
+            if !res.is_empty() {
+                res.push('\n');
+            }
+            _ = writeln!(res, "{server} - {name}");
+            _ = writeln!(res, "{}", order.join("/"));
+        }
+        iced::clipboard::write(res)
+    }
+
+    /// The names of the players this account should fight, in the order that
+    /// gains the most scrapbook items
+    fn battle_order(&self, ident: &AccountIdent) -> Option<Vec<String>> {
+        let (server, account) = self.servers.get_ident(ident)?;
+
+        let CrawlingStatus::Crawling {
+            player_info,
+            equipment,
+            que,
+            ..
+        } = &server.crawling
+        else {
+            return None;
+        };
+
+        let si = account.scrapbook_info.as_ref()?;
+
+        let mut best = si.best.first().cloned();
+        let mut scrapbook = si.scrapbook.items.clone();
+
+        let mut per_player_counts = calc_per_player_count(
+            player_info, equipment, &scrapbook, si,
+            self.config.blacklist_threshold,
+        );
+
+        let mut target_list = Vec::new();
+        let mut loop_count = 0;
+        let lock = que.lock().unwrap();
+        let invalid =
+            lock.invalid_accounts.iter().map(|a| a.as_str()).collect();
+
+        while let Some(AttackTarget { missing, info }) = best {
+            if loop_count > 300 || missing == 0 {
+                break;
+            }
+            loop_count += 1;
+
+            for eq in &info.equipment {
+                if scrapbook.contains(eq) {
+                    continue;
+                }
+                let Some(players) = equipment.get(eq) else {
+                    continue;
+                };
+                for player in players {
+                    let ppc = per_player_counts.entry(*player).or_insert(1);
+                    *ppc = ppc.saturating_sub(1);
+                }
+            }
+
+            scrapbook.extend(info.equipment);
+            target_list.push(info.name);
+            let best_players = find_best(
+                &per_player_counts, player_info, 1, &invalid,
+                si.battle_ordering,
+            );
+            best = best_players.into_iter().next();
+        }
+        drop(lock);
+        Some(target_list)
+    }
 }
 
 pub fn calc_per_player_count(
@@ -899,6 +1504,7 @@ impl ServerInfo {
         let CrawlingStatus::Crawling {
             threads,
             crawling_session,
+            finished,
             ..
         } = &mut self.crawling
         else {
@@ -908,6 +1514,9 @@ impl ServerInfo {
         let not_logged_in = *threads == 0 && crawling_session.is_none();
 
         *threads = new_count;
+        if new_count > 0 {
+            *finished = None;
+        }
 
         let base_name = base_name.to_string();
         let con = self.connection.clone();
@@ -949,7 +1558,34 @@ fn find_best(
     player_info: &IntMap<u32, CharacterInfo>,
     max_out: usize,
     invalid: &HashSet<&str>,
+    ordering: BattleOrdering,
 ) -> Vec<AttackTarget> {
+    if ordering == BattleOrdering::SafestFirst {
+        let mut candidates: Vec<_> = per_player_counts
+            .iter()
+            .filter(|(_, count)| **count > 0)
+            .filter_map(|(player, count)| {
+                Some((player_info.get(player)?, *count))
+            })
+            .filter(|(info, _)| !invalid.contains(&info.name.as_str()))
+            .collect();
+        candidates.sort_by(|(a, a_count), (b, b_count)| {
+            a.level
+                .cmp(&b.level)
+                .then(a.stats.cmp(&b.stats))
+                .then(b_count.cmp(a_count))
+                .then_with(|| a.name.cmp(&b.name))
+        });
+        return candidates
+            .into_iter()
+            .take(max_out)
+            .map(|(info, missing)| AttackTarget {
+                missing,
+                info: info.to_owned(),
+            })
+            .collect();
+    }
+
     // Prune the counts to make computation faster
     let mut max = 1;
     let mut counts = [(); 10].map(|_| vec![]);
@@ -1019,6 +1655,31 @@ fn top_bar(
     .into()
 }
 
+/// The items missing in the scrapbook, ordered by how few crawled players own
+/// them
+pub fn rarest_missing(
+    equipment: &HashMap<
+        EquipmentIdent,
+        HashSet<u32, ahash::RandomState>,
+        ahash::RandomState,
+    >,
+    scrapbook: &HashSet<EquipmentIdent>,
+    limit: usize,
+) -> Vec<(EquipmentIdent, usize)> {
+    let mut res: Vec<_> = equipment
+        .iter()
+        .filter(|(eq, players)| {
+            !players.is_empty() && !scrapbook.contains(eq) && eq.model_id < 100
+        })
+        .map(|(eq, players)| (*eq, players.len()))
+        .collect();
+    res.sort_by(|(a, a_count), (b, b_count)| {
+        a_count.cmp(b_count).then(a.model_id.cmp(&b.model_id))
+    });
+    res.truncate(limit);
+    res
+}
+
 pub fn handle_new_char_info(
     char: CharacterInfo,
     equipment: &mut HashMap<
@@ -1149,9 +1810,13 @@ fn get_log_config(is_headless: bool) -> log4rs::Config {
         .unwrap()
 }
 
-async fn check_update() -> Result<bool, Box<dyn std::error::Error>> {
+/// Returns the newest released version, if it is newer than both the running
+/// and the ignored version
+async fn check_update(
+    ignored: Option<String>,
+) -> Result<Option<String>, Box<dyn std::error::Error>> {
     sleep(Duration::from_millis(fastrand::u64(500..=5000))).await;
-    let client = reqwest::ClientBuilder::new()
+    let client = config::http_client()
         .user_agent("sf-scrapbook-helper")
         .build()?;
     let url =
@@ -1167,14 +1832,26 @@ async fn check_update() -> Result<bool, Box<dyn std::error::Error>> {
 
     let tags: Vec<GitTag> = serde_json::from_str(&text)?;
 
-    let mut should_update = false;
-    if let Some(newest) = tags.first() {
-        let git_version =
-            semver::Version::parse(newest.name.trim_start_matches('v'))?;
-        let own_version = semver::Version::parse(env!("CARGO_PKG_VERSION"))?;
-        should_update = own_version < git_version;
+    let Some(newest) = tags.first() else {
+        return Ok(None);
+    };
+    let git_version = parse_version(&newest.name)?;
+    let own_version = semver::Version::parse(env!("CARGO_PKG_VERSION"))?;
+    if own_version >= git_version {
+        return Ok(None);
     }
-    Ok(should_update)
+    // An ignored version, that can not be parsed, ignores nothing
+    if let Some(ignored) =
+        ignored.as_deref().and_then(|a| parse_version(a).ok())
+        && ignored >= git_version
+    {
+        return Ok(None);
+    }
+    Ok(Some(git_version.to_string()))
+}
+
+fn parse_version(version: &str) -> Result<semver::Version, semver::Error> {
+    semver::Version::parse(version.trim().trim_start_matches('v'))
 }
 
 pub fn set_full_bar(bar: &ProgressBar, title: &str, length: usize) {
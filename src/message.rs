@@ -1,29 +1,65 @@
 use std::{fmt::Write, sync::Arc, time::Duration};
 
 use chrono::Local;
-use config::{CharacterConfig, SFAccCharacter, SFCharIdent, MissionStrategy};
+use config::{
+    BattleOrdering, CITY_GUARD_MAX_HOURS, CharacterConfig, DungeonTarget,
+    GUARD_THIRST_GAP_MAX_MIN, LureExportFormat, MissionStrategy, PET_MAX_LEVEL,
+    QuestingPreference, SELL_JUNK_ATTRIBUTES_LIMIT, SFAccCharacter,
+    SFCharIdent, UI_REFRESH_MAX_MS, UI_REFRESH_MIN_MS,
+};
 use crawler::CrawlerError;
-use iced::Command;
+use iced::{Command, widget::text_editor};
 use log::{debug, error, info, trace, warn};
 use sf_api::{
-    gamestate::GameState,
+    gamestate::{GameState, underworld::UnderworldBuildingType},
     session::{PWHash, Response, Session},
     sso::SSOProvider,
 };
 use tokio::time::sleep;
-use ui::OverviewAction;
+use ui::{
+    OverviewAction, OverviewSort,
+    characters::{CharacterSort, character_rows},
+};
 
 use self::{
-    backup::{get_newest_backup, restore_backup, RestoreData},
-    login::{SSOIdent, SSOLogin, SSOLoginStatus},
+    backup::{
+        RestoreData, RestoreProgress, ZHofBackup, get_newest_backup,
+        restore_backup,
+    },
+    login::{
+        LOGIN_RETRY_ATTEMPTS, LOGIN_RETRY_BASE_SECS, LOGIN_RETRY_MAX_SECS,
+        SSOIdent, SSOLogin, SSOLoginStatus,
+    },
     ui::underworld::LureTarget,
 };
 use crate::{
     crawler::CrawlerState,
-    player::{ScrapbookInfo, UnderworldInfo},
+    player::{
+        ActivityKind, MANUAL_ATTACK_QUEUE_MAX, MANUAL_BLACKLIST,
+        MAX_LURES_PER_DAY, MushroomUse, ScrapbookInfo, UnderworldInfo,
+        automation_timers, describe_command, is_primary_command, mushroom_use,
+        server_day, status_summary,
+    },
     *,
 };
 
+/// The amount of failed relogins, after which the SSO session of a character
+/// is considered to be expired
+const SSO_RELOG_ATTEMPTS: u64 = 3;
+
+/// How long automation waits for auto battle to use a free arena fight. The
+/// auto battle checker tries at most 3s after the fight became available
+const AUTO_BATTLE_YIELD_MS: u64 = 5000;
+
+/// The pause between two fights while farming a target
+const FARMING_DELAY_MS: u64 = 1500;
+/// How often farming waits for a busy session, before it gives up
+const FARMING_BUSY_WAITS: u32 = 20;
+
+/// How long before the next timer an account, that was logged out while
+/// idle, logs in again
+const IDLE_RELOG_LEAD_SECS: i64 = 120;
+
 #[derive(Debug, Clone)]
 pub enum Message {
     MultiAction {
@@ -36,13 +72,73 @@ pub enum Message {
     },
     NextCLICrawling,
     AdvancedLevelRestrict(bool),
+    SetCrawlPageShare(u8),
+    SetAutomationPaused(bool),
+    SetNotificationsEnabled(bool),
+    SetConfirmLogout(bool),
+    SetAutomationDryRun(bool),
+    SetRandomizeActionOrder(bool),
+    SetThousandsSeparator(String),
+    SetProxyUrl(String),
+    /// Makes the logout buttons ask for a confirming click
+    ArmLogout(bool),
+    /// Shows all pending notifications as one desktop notification
+    FlushNotifications,
+    SetNotifyCrawlFinished(bool),
+    /// Writes the config to disk, if it has changed
+    FlushConfig,
+    /// Redraws the UI. Does nothing else
+    UiTick,
+    SetUiRefresh(u64),
+    /// Logs in every account again, that has not been polled successfully
+    /// for `poll_watchdog_min`
+    PollWatchdog,
+    SetPollWatchdog(u64),
+    /// Logs out every account, that has nothing to do for at least
+    /// `auto_logout_idle_min`
+    IdleLogoutCheck,
+    SetAutoLogoutIdle(u64),
+    SetAutomationMinDelay(u64),
+    SetLoginStagger(u64),
+    SetPollInterval(u64),
+    SetMaxConcurrentLogins(usize),
+    /// The logins, that were running while the limit was lowered, finished
+    LoginPermitsShrunk,
+    SetAutomationQueueMax(usize),
+    SetAutomationMaxDelay(u64),
     ShowClasses(bool),
+    ShowResourceColumns(bool),
+    SetOverviewCompact(bool),
+    SetShowActivityTimeline(bool),
     CrawlerSetMinMax {
         server: ServerID,
         min: u32,
         max: u32,
     },
-    UpdateResult(bool),
+    /// Sets the level, below which characters are never fetched
+    CrawlerSetHardMinLevel {
+        server: ServerID,
+        lvl: u32,
+    },
+    /// Moves the invalid accounts of the que back to the todo accounts. Does
+    /// nothing, if the que has been replaced in the meantime
+    CrawlerRequeueInvalid {
+        server: ServerID,
+        que_id: QueID,
+    },
+    /// Fetches the newest (online) HoF backup and adds the characters, that
+    /// have not been crawled yet
+    FetchNewestBackup {
+        server: ServerID,
+        que_id: QueID,
+    },
+    MergeBackup {
+        server: ServerID,
+        que_id: QueID,
+        backup: Option<Box<ZHofBackup>>,
+    },
+    UpdateResult(Option<String>),
+    IgnoreUpdate,
     PlayerSetMaxUndergroundLvl {
         ident: AccountIdent,
         lvl: u16,
@@ -57,6 +153,19 @@ pub enum Message {
         ident: Vec<AccountIdent>,
         val: bool,
     },
+    OverviewFilterChanged(String),
+    Shortcut(Shortcut),
+    /// Sorts the overview by this column. Selecting the current column again
+    /// flips the direction
+    SetOverviewSort(OverviewSort),
+    ViewCharacters,
+    CharactersFilterChanged(String),
+    /// Sorts the character table by this column. Selecting the current
+    /// column again flips the direction
+    SetCharactersSort(CharacterSort),
+    /// Writes the character table, as it is currently filtered and sorted,
+    /// to a CSV file
+    ExportCharacters,
     SSOLoginFailure {
         name: String,
         error: String,
@@ -73,11 +182,62 @@ pub enum Message {
     CopyBattleOrder {
         ident: AccountIdent,
     },
+    RestoreProgress {
+        server: ServerID,
+        done: usize,
+        total: usize,
+    },
+    ExportAttackLog {
+        ident: AccountIdent,
+    },
+    /// Writes the items of the scrapbook to a JSON file
+    ExportScrapbook {
+        ident: AccountIdent,
+    },
+    /// Merges the items of a previously exported JSON file into the
+    /// scrapbook
+    ImportScrapbook {
+        ident: AccountIdent,
+    },
+    ScrapbookBlacklistInput {
+        ident: AccountIdent,
+        nv: String,
+    },
+    ScrapbookShowMissing {
+        ident: AccountIdent,
+        val: bool,
+    },
+    /// Adds the player named in the blacklist input to the blacklist
+    ScrapbookBlacklistAdd {
+        ident: AccountIdent,
+    },
+    ScrapbookBlacklistRemove {
+        ident: AccountIdent,
+        uid: u32,
+    },
+    ScrapbookFightNameInput {
+        ident: AccountIdent,
+        nv: String,
+    },
+    /// Attacks the player named in the fight input. Players, that have not
+    /// been crawled yet, are attacked by name only
+    PlayerAttackByName {
+        ident: AccountIdent,
+    },
     BackupRes {
         server: ServerID,
         error: Option<String>,
     },
     SaveHoF(ServerID),
+    /// Restarts crawling a server, that has failed, from the newest backup
+    RetryCrawling(ServerID),
+    CopyCrawlingError(ServerID),
+    /// Writes the backups of all servers, that are crawling and then closes
+    /// the window
+    SaveAllAndExit,
+    /// Writes the final backups, once no other backup is written anymore
+    ExitBackups,
+    Exit,
     PlayerSetMaxLvl {
         ident: AccountIdent,
         max: u16,
@@ -86,10 +246,32 @@ pub enum Message {
         ident: AccountIdent,
         max: u32,
     },
+    PlayerSetBattleOrdering {
+        ident: AccountIdent,
+        nv: BattleOrdering,
+    },
+    PlayerSetFarming {
+        ident: AccountIdent,
+        nv: bool,
+    },
+    FarmNextFight {
+        ident: AccountIdent,
+    },
+    PlayerSetLureExportFormat {
+        ident: AccountIdent,
+        nv: LureExportFormat,
+    },
+    PlayerSetLureExportOld {
+        ident: AccountIdent,
+        nv: bool,
+    },
     PlayerAttack {
         ident: AccountIdent,
         target: AttackTarget,
     },
+    ClearManualAttacks {
+        ident: AccountIdent,
+    },
     PlayerLure {
         ident: AccountIdent,
         target: LureTarget,
@@ -102,11 +284,18 @@ pub enum Message {
     },
     SSORetry,
     SSOAuthError {
-        _error: String,
+        error: String,
     },
     SetMaxThreads(usize),
     SetStartThreads(usize),
+    SetAutosaveInterval(u64),
+    /// Writes the configured characters and their automations to a CSV file.
+    /// Credentials are never part of this
+    ExportAccountSummary,
     SetBlacklistThr(usize),
+    SetAutoBattleFreshness(f32),
+    SetBattleLuckFactor(f32),
+    SetDefaultCrawlOrder(CrawlingOrder),
     SetAutoFetch(bool),
     SetAutoPoll(bool),
     ViewSubPage {
@@ -127,7 +316,20 @@ pub enum Message {
         auto_login: bool,
     },
     ViewSettings,
+    ViewLogs,
+    SetLogLevel(log::Level),
+    CopyLogs,
     ChangeTheme(AvailableTheme),
+    WindowResized {
+        width: u32,
+        height: u32,
+    },
+    WindowMoved {
+        x: i32,
+        y: i32,
+    },
+    /// The window has not been resized or moved since the given change
+    WindowSettled(u64),
     ViewOverview,
     CrawlerRevived {
         server_id: ServerID,
@@ -174,14 +376,33 @@ pub enum Message {
     },
     RememberMe(bool),
     ClearHof(ServerID),
+    /// Sets the crawl thread override of the character. The crawler of the
+    /// server uses the lowest override of all its characters
     CrawlerSetThreads {
-        server: ServerID,
+        ident: AccountIdent,
         new_count: usize,
     },
+    CrawlerSetPaused {
+        server: ServerID,
+        paused: bool,
+    },
     PageCrawled,
     RemoveAccount {
         ident: AccountIdent,
     },
+    /// Redoes the SSO login of an SSO character, whose session expired
+    ReauthSSO {
+        ident: AccountIdent,
+    },
+    /// Logs an account in again, after it ran into a fatal error
+    RetryLogin {
+        ident: AccountIdent,
+    },
+    /// A relogin timer started by `schedule_relogin()` ran out
+    ScheduledRelogin {
+        ident: AccountIdent,
+        timer: u64,
+    },
     CharacterCrawled {
         server: ServerID,
         que_id: QueID,
@@ -207,16 +428,27 @@ pub enum Message {
     LoginServerChange(String),
     LoginSFSubmit,
     LoginRegularSubmit,
+    /// Checks the credentials of the login form, without keeping the session
+    LoginSFTest,
+    LoginRegularTest,
+    LoginTestResult(Result<String, String>),
+    /// Adds the regular accounts of the import file to the config
+    ImportAccounts,
+    SetLoginImported(bool),
     LoginViewChanged(LoginType),
     LoggininSuccess {
         ident: AccountIdent,
         gs: Box<GameState>,
         session: Box<Session>,
         remember: bool,
+        /// See `AccountInfo::server_skew`
+        clock_skew: Option<chrono::TimeDelta>,
     },
     LoggininFailure {
         ident: AccountIdent,
         error: String,
+        /// See `is_transient_login_error`
+        transient: bool,
     },
     ResetCrawling {
         server: ServerID,
@@ -227,6 +459,11 @@ pub enum Message {
         server: ServerID,
         nv: bool,
     },
+    ConfigSetEnabled {
+        name: String,
+        server: ServerID,
+        nv: bool,
+    },
     ConfigSetAutoBattle {
         name: String,
         server: ServerID,
@@ -237,6 +474,11 @@ pub enum Message {
         server: ServerID,
         nv: bool,
     },
+    /// Edits the notes of the character in the notes editor
+    NotesEdited {
+        ident: AccountIdent,
+        action: text_editor::Action,
+    },
 
     // NEW automation config messages
     ConfigSetAutoTavern {
@@ -249,21 +491,88 @@ pub enum Message {
         server: ServerID,
         nv: bool,
     },
+    /// Toggles the portal, the tower and all other dungeons at once
     ConfigSetAutoDungeons {
         name: String,
         server: ServerID,
         nv: bool,
     },
+    ConfigSetAutoPortal {
+        name: String,
+        server: ServerID,
+        nv: bool,
+    },
+    ConfigSetAutoTower {
+        name: String,
+        server: ServerID,
+        nv: bool,
+    },
+    ConfigSetAutoDungeonsOther {
+        name: String,
+        server: ServerID,
+        nv: bool,
+    },
     ConfigSetAutoPets {
         name: String,
         server: ServerID,
         nv: bool,
     },
+    ConfigSetPetsSkipUnwinnable {
+        name: String,
+        server: ServerID,
+        nv: bool,
+    },
+    ConfigSetPetsPvpMinLevel {
+        name: String,
+        server: ServerID,
+        nv: u16,
+    },
     ConfigSetAutoGuild {
         name: String,
         server: ServerID,
         nv: bool,
     },
+    ConfigSetAutoWheel {
+        name: String,
+        server: ServerID,
+        nv: bool,
+    },
+    ConfigSetAutoCalendar {
+        name: String,
+        server: ServerID,
+        nv: bool,
+    },
+    ConfigSetAutoCollectTasks {
+        name: String,
+        server: ServerID,
+        nv: bool,
+    },
+    ConfigSetAutoSellJunk {
+        name: String,
+        server: ServerID,
+        nv: bool,
+    },
+    ConfigSetSellJunkMaxAttributes {
+        name: String,
+        server: ServerID,
+        nv: u32,
+    },
+    ConfigSetAutoUnderworldSouls {
+        name: String,
+        server: ServerID,
+        nv: bool,
+    },
+    ConfigSetAutoUnderworldUpgrade {
+        name: String,
+        server: ServerID,
+        nv: bool,
+    },
+    /// Moves the building one place up in the underworld upgrade order
+    ConfigUnderworldUpgradeUp {
+        name: String,
+        server: ServerID,
+        building: UnderworldBuildingType,
+    },
     ConfigSetAutoGuildAcceptDefense {
         name: String,
         server: ServerID,
@@ -279,12 +588,22 @@ pub enum Message {
         server: ServerID,
         nv: bool,
     },
+    ConfigSetGuildHydraUseMushrooms {
+        name: String,
+        server: ServerID,
+        nv: bool,
+    },
     // Tavern options
     ConfigSetUseTavernGlasses {
         name: String,
         server: ServerID,
         nv: bool,
     },
+    ConfigSetQuestOverwriteInventory {
+        name: String,
+        server: ServerID,
+        nv: bool,
+    },
     // Expeditions options
     ConfigSetUseExpeditionGlasses {
         name: String,
@@ -301,6 +620,21 @@ pub enum Message {
         server: ServerID,
         nv: MissionStrategy,
     },
+    ConfigSetDungeonTarget {
+        name: String,
+        server: ServerID,
+        nv: DungeonTarget,
+    },
+    ConfigSetQuestingPreference {
+        name: String,
+        server: ServerID,
+        nv: QuestingPreference,
+    },
+    ConfigSetAutomationWindow {
+        name: String,
+        server: ServerID,
+        nv: Option<(chrono::NaiveTime, chrono::NaiveTime)>,
+    },
     ConfigSetAutoBuyBeerMushrooms {
         name: String,
         server: ServerID,
@@ -313,6 +647,16 @@ pub enum Message {
         server: ServerID,
         nv: u32,
     },
+    ConfigSetCityGuardHours {
+        name: String,
+        server: ServerID,
+        nv: u8,
+    },
+    ConfigSetMinGuardThirstGap {
+        name: String,
+        server: ServerID,
+        nv: u16,
+    },
     ConfigSetMaxMushroomsDungeonSkip {
         name: String,
         server: ServerID,
@@ -323,6 +667,26 @@ pub enum Message {
         server: ServerID,
         nv: u32,
     },
+    ConfigSetMaxMushroomsHydra {
+        name: String,
+        server: ServerID,
+        nv: u32,
+    },
+    ConfigSetMaxMushroomsArena {
+        name: String,
+        server: ServerID,
+        nv: u32,
+    },
+    ConfigSetMaxMushroomsFarming {
+        name: String,
+        server: ServerID,
+        nv: u32,
+    },
+    ConfigSetAutoBattleUseMushrooms {
+        name: String,
+        server: ServerID,
+        nv: bool,
+    },
 
     AutoLureIdle,
     AutoLurePossible {
@@ -331,6 +695,18 @@ pub enum Message {
     CopyBestLures {
         ident: AccountIdent,
     },
+    CopyCharacterStatus {
+        ident: AccountIdent,
+    },
+    SetConfigCopyTarget {
+        ident: AccountIdent,
+        target: SFCharIdent,
+        nv: bool,
+    },
+    CopyCharConfigTo {
+        from: SFCharIdent,
+        to: Vec<SFCharIdent>,
+    },
     SetAction(Option<ActionSelection>),
     // Periodic automation tick (Tavern/Expeditions/Dungeons/Pets)
     RunAutomationTick { ident: AccountIdent },
@@ -348,6 +724,11 @@ impl Helper {
                 };
                 log::debug!("Automation {:?}: tick start", ident);
 
+                if self.config.automation_paused {
+                    log::debug!("Automation {:?}: paused, skipping tick", ident);
+                    return Command::none();
+                }
+
                 let Some(cfg) = self
                     .config
                     .get_char_conf(&account.name, server.ident.id)
@@ -355,693 +736,120 @@ impl Helper {
                     return Command::none();
                 };
 
-                if !(cfg.auto_tavern || cfg.auto_expeditions || cfg.auto_dungeons || cfg.auto_pets || cfg.auto_guild) {
+                if !cfg.enabled {
+                    log::debug!("Automation {:?}: account is parked, skipping tick", ident);
+                    return Command::none();
+                }
+
+                // The AutoMissions checker triggers the next tick, once the
+                // window opens again
+                if !config::in_time_window(cfg.automation_window, Local::now().time()) {
+                    log::debug!("Automation {:?}: outside of the automation window, skipping tick", ident);
+                    return Command::none();
+                }
+
+                if !(cfg.auto_tavern || cfg.auto_expeditions || cfg.any_dungeon_fights() || cfg.auto_pets || cfg.auto_guild || cfg.auto_wheel || cfg.auto_calendar || cfg.auto_collect_tasks || cfg.auto_sell_junk || cfg.auto_underworld_upgrade || cfg.auto_underworld_souls) {
                     return Command::none();
                 }
 
                 use chrono::Local;
-                use sf_api::command::{Command as SFCommand, ExpeditionSetting, TimeSkip};
-                use sf_api::gamestate::tavern::{AvailableTasks, CurrentAction, ExpeditionStage};
-                use sf_api::gamestate::dungeons::{DungeonProgress, LightDungeon, ShadowDungeon, Dungeon};
-                use sf_api::gamestate::unlockables::{HabitatType, HabitatExploration};
-                use sf_api::misc::EnumMapGet;
-                use strum::IntoEnumIterator;
-                use sf_api::gamestate::items::Enchantment;
+                use sf_api::command::Command as SFCommand;
 
                 let mut status = account.status.lock().unwrap();
 
                 let AccountStatus::Idle(_, gs) = &*status else {
                     log::debug!("Automation {:?}: account not idle, retrying shortly", ident);
                     drop(status);
+                    let delay = fastrand::u64(self.config.automation_delay_ms());
                     let rerun = Command::perform(
                         async move {
-                            tokio::time::sleep(std::time::Duration::from_millis(fastrand::u64(500..=1500))).await;
+                            tokio::time::sleep(std::time::Duration::from_millis(delay)).await;
                         },
                         move |_| Message::RunAutomationTick { ident }
                     );
                     return rerun;
                 };
 
-                let now = Local::now();
+                let now = account.server_now();
                 log::debug!("Automation {:?}: current_action = {:?}", ident, gs.tavern.current_action);
 
-                // Decide next automation command
-                let next_cmd: Option<SFCommand> = {
-                    // Handle ongoing quest completion or skipping
-                    match &gs.tavern.current_action {
-                        CurrentAction::Quest { busy_until, .. } => {
-                            if *busy_until <= now {
-                                Some(SFCommand::FinishQuest { skip: None })
-                            } else {
-                                // Consider skipping long waits (glass only; never mushrooms)
-                                let remaining = (*busy_until - now)
-                                    .to_std()
-                                    .unwrap_or_default();
-                                if remaining.as_secs() > 60 {
-                                    if cfg.use_glasses_for_tavern
-                                        && gs.tavern.quicksand_glasses > 0
-                                    {
-                                        log::debug!(
-                                            "Automation {:?}: Quest waiting {}s -> skip with glass (tavern glasses enabled)",
-                                            ident,
-                                            remaining.as_secs()
-                                        );
-                                        Some(SFCommand::FinishQuest {
-                                            skip: Some(TimeSkip::Glass),
-                                        })
-                                    } else {
-                                        log::debug!(
-                                            "Automation {:?}: Quest waiting {}s -> no skip (tavern glasses disabled or none available)",
-                                            ident,
-                                            remaining.as_secs()
-                                        );
-                                        None
-                                    }
-                                } else {
-                                    None
-                                }
-                            }
-                        }
-                        CurrentAction::Expedition => {
-                            // Continue/advance an active expedition if possible
-                            if let Some(active) = gs.tavern.expeditions.active() {
-                                match active.current_stage() {
-                                    ExpeditionStage::Boss(_) => {
-                                        log::debug!("Automation {:?}: Expedition boss -> continue", ident);
-                                        Some(SFCommand::ExpeditionContinue)
-                                    }
-                                    ExpeditionStage::Rewards(rewards) => {
-                                        if rewards.is_empty() {
-                                            log::debug!("Automation {:?}: Expedition rewards empty", ident);
-                                            None
-                                        } else {
-                                            // Choose reward based on configured priority
-                                            let mut best_idx = 0usize;
-                                            let mut best_rank = i32::MIN;
-                                            let prio = cfg.expedition_reward_priority;
-                                            for (i, r) in rewards.iter().enumerate() {
-                                                let s = format!("{:?}", r).to_lowercase();
-                                                let is_mush = s.contains("mushroom");
-                                                let is_gold = s.contains("gold") || s.contains("silver");
-                                                let is_egg = s.contains("egg");
-                                                let rank = match prio {
-                                                    crate::config::ExpeditionRewardPriority::MushroomsGoldEggs => {
-                                                        if is_mush { 3 } else if is_gold { 2 } else if is_egg { 1 } else { 0 }
-                                                    }
-                                                    crate::config::ExpeditionRewardPriority::GoldMushroomsEggs => {
-                                                        if is_gold { 3 } else if is_mush { 2 } else if is_egg { 1 } else { 0 }
-                                                    }
-                                                    crate::config::ExpeditionRewardPriority::EggsMushroomsGold => {
-                                                        if is_egg { 3 } else if is_mush { 2 } else if is_gold { 1 } else { 0 }
-                                                    }
-                                                };
-                                                if rank > best_rank { best_rank = rank; best_idx = i; }
-                                            }
-                                            log::debug!("Automation {:?}: Expedition pick reward index {} of {} (priority {:?})", ident, best_idx, rewards.len(), prio);
-                                            Some(SFCommand::ExpeditionPickReward { pos: best_idx })
-                                        }
-                                    }
-                                    ExpeditionStage::Encounters(encs) => {
-                                        if encs.is_empty() {
-                                            log::debug!("Automation {:?}: Expedition encounters empty", ident);
-                                            None
-                                        } else {
-                                            log::debug!("Automation {:?}: Expedition pick first encounter ({} options)", ident, encs.len());
-                                            Some(SFCommand::ExpeditionPickEncounter { pos: 0 })
-                                        }
-                                    }
-                                    ExpeditionStage::Waiting(until) => {
-                                        let remaining = (until - now)
-                                            .to_std()
-                                            .unwrap_or_default();
-                                        if cfg.use_glasses_for_expeditions
-                                            && remaining.as_secs() > 60
-                                            && gs.tavern.quicksand_glasses > 0
-                                        {
-                                            log::debug!("Automation {:?}: Expedition waiting {}s -> skip with glass", ident, remaining.as_secs());
-                                            Some(SFCommand::ExpeditionSkipWait {
-                                                typ: TimeSkip::Glass,
-                                            })
-                                        } else {
-                                            log::debug!("Automation {:?}: Expedition waiting {}s -> no skip", ident, remaining.as_secs());
-                                            None
-                                        }
-                                    }
-                                    ExpeditionStage::Finished
-                                    | ExpeditionStage::Unknown => None,
-                                }
-                            } else {
-                                None
-                            }
-                        }
-                        CurrentAction::CityGuard { hours: _hours, busy_until } => {
-                            let mut cmd: Option<SFCommand> = None;
-
-                            // If guard duty is finished, collect pay first
-                            if *busy_until <= now {
-                                log::debug!("Automation {:?}: CityGuard finished -> FinishWork", ident);
-                                cmd = Some(SFCommand::FinishWork);
-                            }
-
-                            if cfg.auto_dungeons {
-                                if let Some(portal) = &gs.dungeons.portal {
-                                    if portal.can_fight {
-                                        log::debug!("Automation {:?}: Portal fight ready (during CityGuard)", ident);
-                                        cmd = Some(SFCommand::FightPortal);
-                                    }
-                                }
-                                if cmd.is_none() {
-                                    let next_ready = gs
-                                        .dungeons
-                                        .next_free_fight
-                                        .map(|t| t <= now)
-                                        .unwrap_or(true);
-                                    let mut use_mush = false;
-                                    let can_fight_now = if next_ready {
-                                        true
-                                    } else if cfg.max_mushrooms_dungeon_skip > 0 && gs.character.mushrooms > 0 {
-                                        log::debug!("Automation {:?}: Dungeons not ready, using mushroom to skip (during CityGuard)", ident);
-                                        use_mush = true;
-                                        true
-                                    } else { false };
-
-                                    if can_fight_now {
-                                        if let DungeonProgress::Open { finished } = gs.dungeons.progress(LightDungeon::Tower) {
-                                            log::debug!("Automation {:?}: Tower ready at level {} (during CityGuard)", ident, finished);
-                                            cmd = Some(SFCommand::FightTower { current_level: finished as u8, use_mush });
-                                        } else {
-                                            let mut best: Option<(Dungeon, u16)> = None;
-                                            for d in LightDungeon::iter() {
-                                                if d == LightDungeon::Tower { continue; }
-                                                if let DungeonProgress::Open { finished } = gs.dungeons.progress(d) {
-                                                    let entry = (Dungeon::from(d), finished);
-                                                    best = match best { None => Some(entry), Some((_, f)) if finished < f => Some(entry), x => x };
-                                                }
-                                            }
-                                            for d in ShadowDungeon::iter() {
-                                                if let DungeonProgress::Open { finished } = gs.dungeons.progress(d) {
-                                                    let entry = (Dungeon::from(d), finished);
-                                                    best = match best { None => Some(entry), Some((_, f)) if finished < f => Some(entry), x => x };
-                                                }
-                                            }
-                                            if let Some((dng, _)) = best {
-                                                log::debug!("Automation {:?}: Dungeon chosen during CityGuard: {:?}", ident, dng);
-                                                cmd = Some(SFCommand::FightDungeon { dungeon: dng, use_mushroom: use_mush });
-                                            } else {
-                                                log::debug!("Automation {:?}: Dungeons ready but no open dungeon/tower found (during CityGuard)", ident);
-                                            }
-                                        }
-                                    } else {
-                                        log::debug!("Automation {:?}: Dungeons not ready (during CityGuard) (next_free_fight: {:?}, mushrooms: {})", ident, gs.dungeons.next_free_fight, gs.character.mushrooms);
-                                    }
-                                }
-                            }
+                // A free arena fight is left to auto battle, which would
+                // otherwise rarely find the session idle
+                let battle_waiting = matches!(server.crawling, CrawlingStatus::Crawling { .. })
+                    && gs.arena.next_free_fight.is_none_or(|a| a <= now)
+                    && account.scrapbook_info.as_ref().is_some_and(|si| {
+                        si.auto_battle && si.has_fresh_targets(self.config.battle_freshness())
+                    });
+                if battle_waiting {
+                    log::debug!("Automation {:?}: free arena fight pending, yielding to auto battle", ident);
+                    drop(status);
+                    let delay = AUTO_BATTLE_YIELD_MS + fastrand::u64(self.config.automation_delay_ms());
+                    return Command::perform(
+                        async move {
+                            tokio::time::sleep(std::time::Duration::from_millis(delay)).await;
+                        },
+                        move |_| Message::RunAutomationTick { ident }
+                    );
+                }
 
-                            if cmd.is_none() && cfg.auto_pets {
-                                if let Some(pets) = &gs.pets {
-                                    let free_now = pets.opponent.next_free_battle.map(|t| t <= now).unwrap_or(true);
-                                    if free_now {
-                                        log::debug!("Automation {:?}: Pets PvP free (during CityGuard)", ident);
-                                        let mut target_hab: Option<HabitatType> = None;
-                                        if let Some(h) = pets.opponent.habitat {
-                                            if !pets.habitats.get(h).battled_opponent { target_hab = Some(h); }
-                                        }
-                                        if target_hab.is_none() {
-                                            use strum::IntoEnumIterator;
-                                            let mut best: Option<(HabitatType, u16)> = None;
-                                            for h in HabitatType::iter() {
-                                                let hab = pets.habitats.get(h);
-                                                if hab.battled_opponent { continue; }
-                                                if let Some(p) = hab.pets.iter().max_by_key(|p| p.level) {
-                                                    best = match best { None => Some((h, p.level)), Some((_, lvl)) if p.level > lvl => Some((h, p.level)), x => x };
-                                                }
-                                            }
-                                            if let Some((h, _)) = best { target_hab = Some(h); }
-                                        }
-                                        if let Some(h) = target_hab {
-                                            log::debug!("Automation {:?}: Pets PvP habitat {:?} (during CityGuard)", ident, h);
-                                            cmd = Some(SFCommand::FightPetOpponent { habitat: h, opponent_id: pets.opponent.id });
-                                        } else {
-                                            log::debug!("Automation {:?}: Pets PvP ready but no eligible habitat (during CityGuard)", ident);
-                                        }
-                                    }
+                // Beer and skips share one budget per server day
+                let server_day = server_day(gs);
+                let mush_left = cfg.mushroom_budget().saturating_sub(
+                    account.shared_mushrooms_spent_on(server_day),
+                );
+                let can_spend_mush = mush_left > 0 && gs.character.mushrooms > 0;
+                // Only pay for another hydra attempt if the free fights are
+                // used up and the hydra can actually be fought right now
+                let hydra_use_mush = cfg.guild_hydra_use_mushrooms
+                    && account.can_spend_mushroom(gs, cfg, MushroomUse::Hydra)
+                    && gs.guild.as_ref().is_some_and(|g| {
+                        g.hydra.remaining_fights == 0
+                            && g.hydra.next_battle.is_some_and(|t| t <= now)
+                    });
 
-                                    if cmd.is_none() {
-                                        let next_ready = pets.next_free_exploration.map(|t| t <= now).unwrap_or(true);
-                                        let mut use_mush = false;
-                                        let can_explore = if next_ready { true } else if cfg.max_mushrooms_pet_skip > 0 && gs.character.mushrooms > 0 { use_mush = true; true } else { false };
-                                        if can_explore {
-                                            log::debug!("Automation {:?}: Pets exploration free (during CityGuard)", ident);
-                                            use strum::IntoEnumIterator;
-                                            let mut pick: Option<(HabitatType, u32, u16, u32)> = None;
-                                            for hab in HabitatType::iter() {
-                                                if let HabitatExploration::Exploring { fights_won, .. } = pets.habitats.get(hab).exploration {
-                                                    if let Some(best) = pets.habitats.get(hab).pets.iter().max_by_key(|p| p.level) {
-                                                        let entry = (hab, fights_won + 1, best.level, best.id);
-                                                        pick = match pick {
-                                                            None => Some(entry),
-                                                            Some((_, _, lvl, _)) if best.level > lvl => Some(entry),
-                                                            x => x,
-                                                        };
-                                                    }
-                                                }
-                                            }
-                                            if let Some((hab, enemy_pos, _best_lvl, best_id)) = pick {
-                                                if use_mush { log::debug!("Automation {:?}: Pets exploration not ready, using mushroom to skip (during CityGuard)", ident); }
-                                                log::debug!("Automation {:?}: Pets explore habitat {:?} fight_pos {} pet_id {} (during CityGuard)", ident, hab, enemy_pos, best_id);
-                                                cmd = Some(SFCommand::FightPetDungeon { use_mush, habitat: hab, enemy_pos, player_pet_id: best_id });
-                                            } else {
-                                                log::debug!("Automation {:?}: Pets exploration ready but no habitat currently exploring (during CityGuard)", ident);
-                                            }
-                                        } else {
-                                            log::debug!("Automation {:?}: Pets exploration not ready (during CityGuard) (next_free_exploration: {:?})", ident, pets.next_free_exploration);
-                                        }
-                                    }
-                                }
-                            }
+                let freshness = self.config.battle_freshness();
+                let underworld = account.underworld_info.as_ref();
+                // With auto lure on, it picks the targets itself
+                let lure_target = underworld
+                    .filter(|a| !a.auto_lure && cfg.auto_underworld_souls)
+                    .and_then(|a| a.lure_target(freshness))
+                    .map(|a| a.uid);
+                let lures_pending = underworld.is_some_and(|a| {
+                    (a.auto_lure || cfg.auto_underworld_souls)
+                        && a.lure_target(freshness).is_some()
+                });
+                let ctx = automation::TickContext {
+                    ident,
+                    gs,
+                    cfg,
+                    now,
+                    can_spend_mush,
+                    hydra_use_mush,
+                    scrapbook: account
+                        .scrapbook_info
+                        .as_ref()
+                        .map(|a| &a.scrapbook),
+                    souls_collected: account.souls_collected,
+                    lure_target,
+                    lures_pending,
+                    // Shuffled once per tick. A free arena fight still
+                    // comes first, see `battle_waiting`
+                    order: automation::action_order(
+                        self.config.randomize_action_order,
+                    ),
+                };
+                let cmd = automation::tick(&ctx).unwrap_or(SFCommand::Update);
+                log::debug!("Automation {:?}: chosen command: {:?}", ident, cmd);
 
-                            if cmd.is_none() && cfg.auto_guild {
-                                if gs.guild.is_some() && cfg.auto_guild_accept_defense {
-                                    log::debug!("Automation {:?}: Guild join defense (during CityGuard)", ident);
-                                    cmd = Some(SFCommand::GuildJoinDefense);
-                                }
-                                if cmd.is_none() && gs.guild.is_some() && cfg.auto_guild_accept_attack {
-                                    log::debug!("Automation {:?}: Guild join attack (during CityGuard)", ident);
-                                    cmd = Some(SFCommand::GuildJoinAttack);
-                                }
-                                if cmd.is_none() && cfg.auto_guild_hydra {
-                                    if let Some(guild) = &gs.guild {
-                                        if guild.hydra.remaining_fights > 0 {
-                                            if let Some(next) = guild.hydra.next_battle {
-                                                if next <= now {
-                                                    log::debug!("Automation {:?}: Guild hydra battle (during CityGuard)", ident);
-                                                    cmd = Some(SFCommand::GuildPetBattle { use_mushroom: false });
-                                                }
-                                            }
-                                        }
-                                    }
-                                }
-                            }
-
-                            if cmd.is_none() {
-                                let portal = gs.dungeons.portal.as_ref().map(|p| p.can_fight).unwrap_or(false);
-                                let dng_ready = gs.dungeons.next_free_fight.map(|t| t <= now).unwrap_or(true);
-                                let open_dng = {
-                                    let mut open = 0u32;
-                                    for d in LightDungeon::iter() {
-                                        if let DungeonProgress::Open { .. } = gs.dungeons.progress(d) { open += 1; }
-                                    }
-                                    for d in ShadowDungeon::iter() {
-                                        if let DungeonProgress::Open { .. } = gs.dungeons.progress(d) { open += 1; }
-                                    }
-                                    open
-                                };
-                                let pets_pvp_ready = gs.pets.as_ref().and_then(|p| p.opponent.next_free_battle).map(|t| t <= now).unwrap_or(false);
-                                let pets_explore_ready = gs.pets.as_ref().and_then(|p| p.next_free_exploration).map(|t| t <= now).unwrap_or(false);
-                                let hydra_ready = gs.guild.as_ref().and_then(|g| g.hydra.next_battle).map(|t| t <= now).unwrap_or(false);
-                                let thirst = gs.tavern.thirst_for_adventure_sec;
-                                log::debug!(
-                                    "Automation {:?}: CityGuard active. No Tavern tasks allowed. Summary -> portal: {}, dng_ready: {}, open_dng: {}, pets_pvp: {}, pets_explore: {}, hydra: {}, thirst: {}s",
-                                    ident, portal, dng_ready, open_dng, pets_pvp_ready, pets_explore_ready, hydra_ready, thirst
-                                );
-                            }
-
-                            cmd
-                        }
-                        CurrentAction::Unknown(_) | CurrentAction::Idle => {
-                            let mut cmd: Option<SFCommand> = None;
-
-                            if cfg.auto_dungeons {
-                                if let Some(portal) = &gs.dungeons.portal {
-                                    if portal.can_fight {
-                                        log::debug!("Automation {:?}: Portal fight ready", ident);
-                                        cmd = Some(SFCommand::FightPortal);
-                                    }
-                                }
-                                if cmd.is_none() {
-                                    let next_ready = gs
-                                        .dungeons
-                                        .next_free_fight
-                                        .map(|t| t <= now)
-                                        .unwrap_or(true);
-                                    let mut use_mush = false;
-                                    let can_fight_now = if next_ready { true } else if cfg.max_mushrooms_dungeon_skip > 0 && gs.character.mushrooms > 0 { log::debug!("Automation {:?}: Dungeons not ready, using mushroom to skip", ident); use_mush = true; true } else { false };
-
-                                    if can_fight_now {
-                                        if let DungeonProgress::Open { finished } = gs.dungeons.progress(LightDungeon::Tower) {
-                                            log::debug!("Automation {:?}: Tower ready at level {}", ident, finished);
-                                            cmd = Some(SFCommand::FightTower { current_level: finished as u8, use_mush });
-                                        } else {
-                                            let mut best: Option<(Dungeon, u16)> = None;
-                                            for d in LightDungeon::iter() {
-                                                if d == LightDungeon::Tower { continue; }
-                                                if let DungeonProgress::Open { finished } = gs.dungeons.progress(d) {
-                                                    let entry = (Dungeon::from(d), finished);
-                                                    best = match best { None => Some(entry), Some((_, f)) if finished < f => Some(entry), x => x };
-                                                }
-                                            }
-                                            for d in ShadowDungeon::iter() {
-                                                if let DungeonProgress::Open { finished } = gs.dungeons.progress(d) {
-                                                    let entry = (Dungeon::from(d), finished);
-                                                    best = match best { None => Some(entry), Some((_, f)) if finished < f => Some(entry), x => x };
-                                                }
-                                            }
-                                            if let Some((dng, _)) = best {
-                                                log::debug!("Automation {:?}: Dungeon chosen: {:?}", ident, dng);
-                                                cmd = Some(SFCommand::FightDungeon { dungeon: dng, use_mushroom: use_mush });
-                                            } else {
-                                                // Ready by timer/mush, but nothing open
-                                                log::debug!("Automation {:?}: Dungeons ready but no open dungeon/tower found", ident);
-                                            }
-                                        }
-                                    } else {
-                                        log::debug!("Automation {:?}: Dungeons not ready (next_free_fight: {:?}, mushrooms: {})", ident, gs.dungeons.next_free_fight, gs.character.mushrooms);
-                                    }
-                                }
-                            }
-
-                            if cmd.is_none() && cfg.auto_pets {
-                                if let Some(pets) = &gs.pets {
-                                    let free_now = pets.opponent.next_free_battle.map(|t| t <= now).unwrap_or(true);
-                                    if free_now {
-                                        log::debug!("Automation {:?}: Pets PvP free", ident);
-                                        let mut target_hab: Option<HabitatType> = None;
-                                        if let Some(h) = pets.opponent.habitat {
-                                            if !pets.habitats.get(h).battled_opponent { target_hab = Some(h); }
-                                        }
-                                        if target_hab.is_none() {
-                                            use strum::IntoEnumIterator;
-                                            let mut best: Option<(HabitatType, u16)> = None;
-                                            for h in HabitatType::iter() {
-                                                let hab = pets.habitats.get(h);
-                                                if hab.battled_opponent { continue; }
-                                                if let Some(p) = hab.pets.iter().max_by_key(|p| p.level) {
-                                                    best = match best { None => Some((h, p.level)), Some((_, lvl)) if p.level > lvl => Some((h, p.level)), x => x };
-                                                }
-                                            }
-                                            if let Some((h, _)) = best { target_hab = Some(h); }
-                                        }
-                                        if let Some(h) = target_hab {
-                                            log::debug!("Automation {:?}: Pets PvP habitat {:?}", ident, h);
-                                            cmd = Some(SFCommand::FightPetOpponent { habitat: h, opponent_id: pets.opponent.id });
-                                        } else {
-                                            log::debug!("Automation {:?}: Pets PvP ready but no eligible habitat (all battled or none with pets)", ident);
-                                        }
-                                    }
-
-                                    if cmd.is_none() {
-                                        let next_ready = pets.next_free_exploration.map(|t| t <= now).unwrap_or(true);
-                                        let mut use_mush = false;
-                                        let can_explore = if next_ready { true } else if cfg.max_mushrooms_pet_skip > 0 && gs.character.mushrooms > 0 { use_mush = true; true } else { false };
-                                        if can_explore {
-                                            log::debug!("Automation {:?}: Pets exploration free", ident);
-                                            use strum::IntoEnumIterator;
-                                            let mut pick: Option<(HabitatType, u32, u16, u32)> = None;
-                                            for hab in HabitatType::iter() {
-                                                if let HabitatExploration::Exploring { fights_won, .. } = pets.habitats.get(hab).exploration {
-                                                    if let Some(best) = pets.habitats.get(hab).pets.iter().max_by_key(|p| p.level) {
-                                                        let entry = (hab, fights_won + 1, best.level, best.id);
-                                                        pick = match pick {
-                                                            None => Some(entry),
-                                                            Some((_, _, lvl, _)) if best.level > lvl => Some(entry),
-                                                            x => x,
-                                                        };
-                                                    }
-                                                }
-                                            }
-                                            if let Some((hab, enemy_pos, _best_lvl, best_id)) = pick {
-                                                if use_mush { log::debug!("Automation {:?}: Pets exploration not ready, using mushroom to skip", ident); }
-                                                log::debug!("Automation {:?}: Pets explore habitat {:?} fight_pos {} pet_id {}", ident, hab, enemy_pos, best_id);
-                                                cmd = Some(SFCommand::FightPetDungeon { use_mush, habitat: hab, enemy_pos, player_pet_id: best_id });
-                                            } else {
-                                                log::debug!("Automation {:?}: Pets exploration ready but no habitat currently exploring", ident);
-                                            }
-                                        } else {
-                                            log::debug!("Automation {:?}: Pets exploration not ready (next_free_exploration: {:?})", ident, pets.next_free_exploration);
-                                        }
-                                    }
-                                }
-                            }
-
-                            if cmd.is_none() {
-                                cmd = match gs.tavern.available_tasks() {
-                                    AvailableTasks::Expeditions(_) if cfg.auto_expeditions => {
-                                        if gs.tavern.questing_preference == ExpeditionSetting::PreferQuests
-                                            && gs.tavern.can_change_questing_preference() {
-                                            log::debug!("Automation {:?}: Switching to Expeditions", ident);
-                                            Some(SFCommand::SetQuestsInsteadOfExpeditions { value: ExpeditionSetting::PreferExpeditions })
-                                        } else if gs.tavern.thirst_for_adventure_sec > 0 {
-                                            log::debug!("Automation {:?}: Starting Expedition 0", ident);
-                                            Some(SFCommand::ExpeditionStart { pos: 0 })
-                                        } else { None }
-                                    }
-                                    AvailableTasks::Quests(qs) if cfg.auto_tavern => {
-                                        let pick_idx = {
-                                            let mut best: Option<(usize, f64)> = None;
-                                            for (i, q) in qs.iter().enumerate() {
-                                                let minutes = (q.base_length.max(1) as f64) / 60.0;
-                                                let gold = q.base_silver as f64;
-                                                let xp = q.base_experience as f64;
-                                                let score = match cfg.mission_strategy {
-                                                    MissionStrategy::Shortest => -minutes,
-                                                    MissionStrategy::MostGold => gold,
-                                                    MissionStrategy::BestGoldPerMinute => { if minutes > 0.0 { gold / minutes } else { f64::MAX } }
-                                                    MissionStrategy::BestXpPerMinute => { if minutes > 0.0 { xp / minutes } else { f64::MAX } }
-                                                    MissionStrategy::Smartest => { let speed = 1.0 / minutes.max(1.0); 0.45 * (gold / minutes.max(1.0)) + 0.45 * (xp / minutes.max(1.0)) + 0.10 * speed }
-                                                };
-                                                log::trace!("Automation {:?}: Quest {} len={}s gold={} xp={} -> score={}", ident, i, q.base_length, q.base_silver, q.base_experience, score);
-                                                match best { None => best = Some((i, score)), Some((_, s)) if score > s => best = Some((i, score)), _ => {} }
-                                            }
-                                            best.map(|a| a.0).unwrap_or(0)
-                                        };
-                                        let picked = &qs[pick_idx];
-                                        if picked.base_length > gs.tavern.thirst_for_adventure_sec {
-                                            let extra_beer = gs.character.equipment.has_enchantment(Enchantment::ThirstyWanderer) as u8;
-                                            let beer_cap = 10 + extra_beer;
-                                            if cfg.auto_buy_beer_mushrooms && cfg.max_mushrooms_beer > 0 && gs.character.mushrooms > 0 && gs.tavern.beer_drunk < beer_cap {
-                                                log::debug!("Automation {:?}: Buying beer (drunk {}, cap {})", ident, gs.tavern.beer_drunk, beer_cap);
-                                                Some(SFCommand::BuyBeer)
-                                            } else {
-                                                let mut alt_best: Option<(usize, f64)> = None;
-                                                for (i, q) in qs.iter().enumerate() {
-                                                    if q.base_length <= gs.tavern.thirst_for_adventure_sec {
-                                                        let minutes = (q.base_length.max(1) as f64) / 60.0;
-                                                        let gold = q.base_silver as f64;
-                                                        let xp = q.base_experience as f64;
-                                                        let score = match cfg.mission_strategy {
-                                                            MissionStrategy::Shortest => -minutes,
-                                                            MissionStrategy::MostGold => gold,
-                                                            MissionStrategy::BestGoldPerMinute => { if minutes > 0.0 { gold / minutes } else { f64::MAX } }
-                                                            MissionStrategy::BestXpPerMinute => { if minutes > 0.0 { xp / minutes } else { f64::MAX } }
-                                                            MissionStrategy::Smartest => { let speed = 1.0 / minutes.max(1.0); 0.45 * (gold / minutes.max(1.0)) + 0.45 * (xp / minutes.max(1.0)) + 0.10 * speed }
-                                                        };
-                                                        match alt_best { None => alt_best = Some((i, score)), Some((_, s)) if score > s => alt_best = Some((i, score)), _ => {} }
-                                                    }
-                                                }
-                                                if let Some((idx, _)) = alt_best {
-                                                    let q = &qs[idx];
-                                                    log::debug!("Automation {:?}: Fallback quest {} within thirst (len {}s)", ident, idx, q.base_length);
-                                                    Some(SFCommand::StartQuest { quest_pos: idx, overwrite_inv: true })
-                                                } else {
-                                                    log::debug!("Automation {:?}: No quest fits remaining thirst ({}s) and not buying beer -> waiting", ident, gs.tavern.thirst_for_adventure_sec);
-                                                    None
-                                                }
-                                            }
-                                        } else {
-                                            log::debug!("Automation {:?}: Starting quest {} (len {}s)", ident, pick_idx, picked.base_length);
-                                            Some(SFCommand::StartQuest { quest_pos: pick_idx, overwrite_inv: true })
-                                        }
-                                    }
-                                    _ => None,
-                                };
-                            }
-
-                            // If thirst is empty and beer is unavailable, start 1h CityGuard before any Guild actions
-                            if cmd.is_none() && (cfg.auto_tavern || cfg.auto_expeditions) {
-                                let thirst = gs.tavern.thirst_for_adventure_sec;
-                                if thirst == 0 {
-                                    let extra_beer = gs.character.equipment.has_enchantment(Enchantment::ThirstyWanderer) as u8;
-                                    let beer_cap = 10 + extra_beer;
-                                    let beer_left = beer_cap.saturating_sub(gs.tavern.beer_drunk);
-                                    let can_buy_more_beer = cfg.auto_buy_beer_mushrooms && cfg.max_mushrooms_beer > 0 && gs.character.mushrooms > 0 && gs.tavern.beer_drunk < beer_cap;
-                                    if beer_left == 0 || !can_buy_more_beer {
-                                        log::debug!("Automation {:?}: Thirst empty and beer exhausted/unavailable -> Start 1h CityGuard", ident);
-                                        #[allow(unused_variables)]
-                                        {
-                                            if cmd.is_none() {
-                                                cmd = Some(SFCommand::StartWork { hours: 1 });
-                                            }
-                                        }
-                                    } else {
-                                        log::debug!("Automation {:?}: Thirst empty but beer available (drunk {} / cap {}, mushrooms {}, auto_buy {}, beer_budget {}) -> no CityGuard", ident, gs.tavern.beer_drunk, beer_cap, gs.character.mushrooms, cfg.auto_buy_beer_mushrooms, cfg.max_mushrooms_beer);
-                                    }
-                                }
-                            }
-
-                            // Run Guild actions after Tavern/Expeditions and CityGuard decision so primary tasks aren't starved
-                            if cmd.is_none() && cfg.auto_guild {
-                                if gs.guild.is_some() && cfg.auto_guild_accept_defense {
-                                    log::debug!("Automation {:?}: Guild join defense", ident);
-                                    cmd = Some(SFCommand::GuildJoinDefense);
-                                }
-                                if cmd.is_none() && gs.guild.is_some() && cfg.auto_guild_accept_attack {
-                                    log::debug!("Automation {:?}: Guild join attack", ident);
-                                    cmd = Some(SFCommand::GuildJoinAttack);
-                                }
-                                if cmd.is_none() && cfg.auto_guild_hydra {
-                                    if let Some(guild) = &gs.guild {
-                                        if guild.hydra.remaining_fights > 0 {
-                                            if let Some(next) = guild.hydra.next_battle {
-                                                if next <= now {
-                                                    log::debug!("Automation {:?}: Guild hydra battle", ident);
-                                                    cmd = Some(SFCommand::GuildPetBattle { use_mushroom: false });
-                                                }
-                                            }
-                                        }
-                                    }
-                                }
-                            }
-
-                            if cmd.is_none() {
-                                let portal = gs.dungeons.portal.as_ref().map(|p| p.can_fight).unwrap_or(false);
-                                let dng_ready = gs.dungeons.next_free_fight.map(|t| t <= now).unwrap_or(true);
-                                let open_dng = {
-                                    let mut open = 0u32;
-                                    for d in LightDungeon::iter() {
-                                        if let DungeonProgress::Open { .. } = gs.dungeons.progress(d) { open += 1; }
-                                    }
-                                    for d in ShadowDungeon::iter() {
-                                        if let DungeonProgress::Open { .. } = gs.dungeons.progress(d) { open += 1; }
-                                    }
-                                    open
-                                };
-                                let pets_pvp_ready = gs.pets.as_ref().and_then(|p| p.opponent.next_free_battle).map(|t| t <= now).unwrap_or(false);
-                                let pets_explore_ready = gs.pets.as_ref().and_then(|p| p.next_free_exploration).map(|t| t <= now).unwrap_or(false);
-                                let hydra_ready = gs.guild.as_ref().and_then(|g| g.hydra.next_battle).map(|t| t <= now).unwrap_or(false);
-                                let thirst = gs.tavern.thirst_for_adventure_sec;
-                                log::debug!(
-                                    "Automation {:?}: No action chosen. Summary -> portal: {}, dng_ready: {}, open_dng: {}, pets_pvp: {}, pets_explore: {}, hydra: {}, thirst: {}s",
-                                    ident, portal, dng_ready, open_dng, pets_pvp_ready, pets_explore_ready, hydra_ready, thirst
-                                );
-                            }
-
-                            cmd
-                        }
-                    }
-                };
-
-                // Allow side-actions (dungeons/pets/guild) to run even while Tavern is busy
-                let mut cmd = next_cmd;
-                if cmd.is_none() {
-                    // Try Dungeons first
-                    if cfg.auto_dungeons {
-                        if let Some(portal) = &gs.dungeons.portal {
-                            if portal.can_fight {
-                                log::debug!("Automation {:?}: Portal fight ready (side-action)", ident);
-                                cmd = Some(SFCommand::FightPortal);
-                            }
-                        }
-                        if cmd.is_none() {
-                            let next_ready = gs.dungeons.next_free_fight.map(|t| t <= now).unwrap_or(true);
-                            let mut use_mush = false;
-                            let can_fight_now = if next_ready { true } else if cfg.max_mushrooms_dungeon_skip > 0 && gs.character.mushrooms > 0 { use_mush = true; true } else { false };
-                            if can_fight_now {
-                                use sf_api::gamestate::dungeons::{LightDungeon, ShadowDungeon, DungeonProgress};
-                                if let DungeonProgress::Open { finished } = gs.dungeons.progress(LightDungeon::Tower) {
-                                    log::debug!("Automation {:?}: Tower ready at level {} (side-action)", ident, finished);
-                                    cmd = Some(SFCommand::FightTower { current_level: finished as u8, use_mush });
-                                } else {
-                                    use strum::IntoEnumIterator;
-                                    let mut best: Option<(sf_api::gamestate::dungeons::Dungeon, u16)> = None;
-                                    for d in LightDungeon::iter() {
-                                        if d == LightDungeon::Tower { continue; }
-                                        if let DungeonProgress::Open { finished } = gs.dungeons.progress(d) {
-                                            let entry = (sf_api::gamestate::dungeons::Dungeon::from(d), finished);
-                                            best = match best { None => Some(entry), Some((_, f)) if finished < f => Some(entry), x => x };
-                                        }
-                                    }
-                                    for d in ShadowDungeon::iter() {
-                                        if let DungeonProgress::Open { finished } = gs.dungeons.progress(d) {
-                                            let entry = (sf_api::gamestate::dungeons::Dungeon::from(d), finished);
-                                            best = match best { None => Some(entry), Some((_, f)) if finished < f => Some(entry), x => x };
-                                        }
-                                    }
-                                    if let Some((dng, _)) = best {
-                                        log::debug!("Automation {:?}: Dungeon chosen (side-action): {:?}", ident, dng);
-                                        cmd = Some(SFCommand::FightDungeon { dungeon: dng, use_mushroom: use_mush });
-                                    }
-                                }
-                            }
-                        }
-                    }
-                    // Try Pets next if still none
-                    if cmd.is_none() && cfg.auto_pets {
-                        if let Some(pets) = &gs.pets {
-                            // Prefer PvP if any habitat has not battled opponent yet, else exploration timer
-                            use sf_api::gamestate::unlockables::HabitatType;
-                            use strum::IntoEnumIterator;
-                            let mut any_pvp_left = false;
-                            for h in HabitatType::iter() {
-                                let hab = pets.habitats.get(h);
-                                if !hab.battled_opponent { any_pvp_left = true; break; }
-                            }
-                            if any_pvp_left {
-                                let free_now = pets.opponent.next_free_battle.map(|t| t <= now).unwrap_or(true);
-                                if free_now {
-                                    // Choose a habitat for PvP
-                                    let mut target_hab: Option<HabitatType> = None;
-                                    if let Some(h) = pets.opponent.habitat { if !pets.habitats.get(h).battled_opponent { target_hab = Some(h); } }
-                                    if target_hab.is_none() {
-                                        let mut best: Option<(HabitatType, u16)> = None;
-                                        for h in HabitatType::iter() {
-                                            let hab = pets.habitats.get(h);
-                                            if hab.battled_opponent { continue; }
-                                            if let Some(p) = hab.pets.iter().max_by_key(|p| p.level) {
-                                                best = match best { None => Some((h, p.level)), Some((_, lvl)) if p.level > lvl => Some((h, p.level)), x => x };
-                                            }
-                                        }
-                                        if let Some((h, _)) = best { target_hab = Some(h); }
-                                    }
-                                    if let Some(h) = target_hab {
-                                        log::debug!("Automation {:?}: Pets PvP habitat {:?} (side-action)", ident, h);
-                                        cmd = Some(SFCommand::FightPetOpponent { habitat: h, opponent_id: pets.opponent.id });
-                                    }
-                                }
-                            } else {
-                                // No PvP left; consider exploration if ready
-                                let next_ready = pets.next_free_exploration.map(|t| t <= now).unwrap_or(true);
-                                let mut use_mush = false;
-                                let can_explore = if next_ready { true } else if cfg.max_mushrooms_pet_skip > 0 && gs.character.mushrooms > 0 { use_mush = true; true } else { false };
-                                if can_explore {
-                                    let mut pick: Option<(HabitatType, u32, u16, u32)> = None;
-                                    for hab in HabitatType::iter() {
-                                        if let sf_api::gamestate::unlockables::HabitatExploration::Exploring { fights_won, .. } = pets.habitats.get(hab).exploration {
-                                            if let Some(best) = pets.habitats.get(hab).pets.iter().max_by_key(|p| p.level) {
-                                                let entry = (hab, fights_won + 1, best.level, best.id);
-                                                pick = match pick { None => Some(entry), Some((_, _, lvl, _)) if best.level > lvl => Some(entry), x => x };
-                                            }
-                                        }
-                                    }
-                                    if let Some((hab, enemy_pos, _best_lvl, best_id)) = pick {
-                                        log::debug!("Automation {:?}: Pets explore habitat {:?} fight_pos {} pet_id {} (side-action)", ident, hab, enemy_pos, best_id);
-                                        cmd = Some(SFCommand::FightPetDungeon { use_mush, habitat: hab, enemy_pos, player_pet_id: best_id });
-                                    }
-                                }
-                            }
-                        }
-                    }
-                    // Try Guild hydra last
-                    if cmd.is_none() && cfg.auto_guild {
-                        if let Some(guild) = &gs.guild {
-                            if cfg.auto_guild_hydra && guild.hydra.remaining_fights > 0 {
-                                if let Some(next) = guild.hydra.next_battle { if next <= now { cmd = Some(SFCommand::GuildPetBattle { use_mushroom: false }); } }
-                            }
-                        }
-                    }
-                }
-
-                let cmd = cmd.unwrap_or(SFCommand::Update);
-                log::debug!("Automation {:?}: chosen command: {:?}", ident, cmd);
+                // Nothing that changes the character is sent in a dry run. The
+                // mission checker keeps ticking and polling keeps the game
+                // state fresh, so the next tick decides again
+                if self.config.automation_dry_run
+                    && !matches!(cmd, SFCommand::Update)
+                {
+                    info!("[DRY RUN] Automation {ident}: would send {cmd:?}");
+                    return Command::none();
+                }
 
                 // Try to acquire a session. If it's temporarily busy (e.g., AutoPoll), don't try to relog; just retry shortly.
                 let Some(mut session) = status.take_session("Automation") else {
@@ -1049,32 +857,11 @@ impl Helper {
                     if !matches!(cmd, SFCommand::Update) {
                         // Enforce exclusivity: only one primary Tavern/Expedition/CityGuard command
                         // can be queued at a time. Side-actions (dungeons/pets/guild) are not considered primary.
-                        use sf_api::command::Command as SFCommand;
-                        let is_primary = |c: &SFCommand| -> bool {
-                            matches!(
-                                c,
-                                // Tavern / Quests
-                                SFCommand::StartQuest { .. }
-                                    | SFCommand::FinishQuest { .. }
-                                    | SFCommand::BuyBeer
-                                    | SFCommand::SetQuestsInsteadOfExpeditions { .. }
-                                    // Expeditions
-                                    | SFCommand::ExpeditionStart { .. }
-                                    | SFCommand::ExpeditionContinue
-                                    | SFCommand::ExpeditionPickEncounter { .. }
-                                    | SFCommand::ExpeditionPickReward { .. }
-                                    | SFCommand::ExpeditionSkipWait { .. }
-                                    // CityGuard (CityWatch)
-                                    | SFCommand::StartWork { .. }
-                                    | SFCommand::FinishWork
-                            )
-                        };
-
-                        if is_primary(&cmd)
+                        if is_primary_command(&cmd)
                             && account
                                 .automation_queue
                                 .iter()
-                                .any(|q| is_primary(q))
+                                .any(is_primary_command)
                         {
                             log::debug!(
                                 "Automation {:?}: session busy; NOT queueing {:?} because a primary task is already queued (len={})",
@@ -1083,7 +870,14 @@ impl Helper {
                                 account.automation_queue.len()
                             );
                         } else {
-                            account.automation_queue.push(cmd.clone());
+                            let max = self.config.automation_queue_max();
+                            if let Some(dropped) =
+                                account.queue_automation(cmd.clone(), max)
+                            {
+                                warn!(
+                                    "Automation {ident}: queue is full, dropped {dropped:?}"
+                                );
+                            }
                             log::debug!(
                                 "Automation {:?}: session busy; queueing {:?} (queue_len={})",
                                 ident,
@@ -1095,9 +889,10 @@ impl Helper {
                         log::debug!("Automation {:?}: session busy; skipping Update", ident);
                     }
                     drop(status);
+                    let delay = fastrand::u64(self.config.automation_delay_ms());
                     let rerun = Command::perform(
                         async move {
-                            tokio::time::sleep(std::time::Duration::from_millis(fastrand::u64(400..=1200))).await;
+                            tokio::time::sleep(std::time::Duration::from_millis(delay)).await;
                         },
                         move |_| Message::RunAutomationTick { ident }
                     );
@@ -1106,6 +901,11 @@ impl Helper {
                 let player_status = account.status.clone();
                 let chosen_cmd = cmd.clone();
                 drop(status);
+                if let Some(purpose) = mushroom_use(&chosen_cmd) {
+                    account.record_mushrooms_spent(server_day, purpose, 1);
+                }
+                account.set_in_flight(&chosen_cmd);
+                account.pending_action = Some(describe_command(&chosen_cmd));
 
                 return Command::perform(
                     async move {
@@ -1168,12 +968,129 @@ impl Helper {
                 );
             }
             Message::PageCrawled => {}
-            Message::CrawlerDied { server, error } => {
-                log::error!("Crawler died on {server} - {error}");
+            Message::CrawlerDied {
+                server: server_id,
+                error,
+            } => {
+                log::error!("Crawler died on {server_id} - {error}");
+                let Some(server) = self.servers.get_mut(&server_id) else {
+                    return Command::none();
+                };
+                if let Some(pb) = server.headless_progress.clone() {
+                    pb.println(format!(
+                        "Crawler died on {}: {error}",
+                        server.ident.url
+                    ));
+                    pb.finish_and_clear();
+                    if self
+                        .cli_crawling
+                        .as_ref()
+                        .is_some_and(|a| a.exit_on_error)
+                    {
+                        std::process::exit(1);
+                    }
+                    self.servers.0.remove(&server_id);
+                    return Command::perform(async {}, |_| {
+                        Message::NextCLICrawling
+                    });
+                }
+
+                // Keep the progress on disk, so that a retry can continue
+                // from here
+                let mut res = Command::none();
+                if let CrawlingStatus::Crawling {
+                    que, player_info, ..
+                } = &server.crawling
+                    && !player_info.is_empty()
+                {
+                    let backup = que.lock().unwrap().create_backup(player_info);
+                    let ident = server.ident.ident.to_string();
+                    let dir = self.config.backup_dir.clone();
+                    server.backups_in_flight += 1;
+                    res = Command::perform(
+                        async move { backup.write(&ident, dir.as_deref()).await },
+                        move |res| Message::BackupRes {
+                            server: server_id,
+                            error: res.err().map(|a| a.to_string()),
+                        },
+                    );
+                }
+                server.crawling = CrawlingStatus::CrawlingFailed(error);
+                return res;
+            }
+            Message::RetryCrawling(server_id) => {
+                let Some(server) = self.servers.get_mut(&server_id) else {
+                    return Command::none();
+                };
+                if !matches!(server.crawling, CrawlingStatus::CrawlingFailed(_))
+                {
+                    return Command::none();
+                }
+
+                let Some(tp) = server.accounts.values().find_map(|b| {
+                    let status = b.status.lock().unwrap();
+                    match &*status {
+                        AccountStatus::Idle(_, gs)
+                        | AccountStatus::Busy(gs, _) => {
+                            Some(gs.hall_of_fames.players_total)
+                        }
+                        _ => None,
+                    }
+                }) else {
+                    return Command::none();
+                };
+                let total_pages = (tp as usize).div_ceil(PER_PAGE);
+
+                let progress = Arc::new(RestoreProgress::default());
+                server.crawling = CrawlingStatus::Restoring {
+                    progress: progress.clone(),
+                    done: 0,
+                    total: 0,
+                };
+                let server_ident = server.ident.ident.clone();
+                let afn = self.config.auto_fetch_newest;
+                let dir = self.config.backup_dir.clone();
+                let order = self.config.default_crawl_order;
+                return Command::perform(
+                    async move {
+                        let backup =
+                            get_newest_backup(server_ident, afn, dir).await;
+                        Box::new(
+                            restore_backup(
+                                backup, total_pages, progress, order,
+                            )
+                            .await,
+                        )
+                    },
+                    move |backup| Message::ResetCrawling {
+                        server: server_id,
+                        status: backup,
+                    },
+                );
+            }
+            Message::RestoreProgress {
+                server,
+                done: new_done,
+                total: new_total,
+            } => {
                 let Some(server) = self.servers.get_mut(&server) else {
                     return Command::none();
                 };
-                server.crawling = CrawlingStatus::CrawlingFailed(error)
+                if let CrawlingStatus::Restoring { done, total, .. } =
+                    &mut server.crawling
+                {
+                    *done = new_done;
+                    *total = new_total;
+                }
+            }
+            Message::CopyCrawlingError(server_id) => {
+                let Some(server) = self.servers.get(&server_id) else {
+                    return Command::none();
+                };
+                if let CrawlingStatus::CrawlingFailed(reason) = &server.crawling
+                {
+                    return iced::clipboard::write(reason.clone());
+                }
             }
             Message::CharacterCrawled {
                 server,
@@ -1194,6 +1111,8 @@ impl Helper {
                     que,
                     recent_failures,
                     naked,
+                    threads,
+                    finished,
                     ..
                 } = &mut server.crawling
                 else {
@@ -1208,9 +1127,11 @@ impl Helper {
                         let total = remaining + crawled;
                         pb.set_length(total as u64);
                         pb.set_position(crawled as u64);
+                        pb.set_message(format!("Fetched {crawled}/{total}"));
                     };
                     lock.in_flight_accounts.remove(&character.name);
-                    lock.todo_pages.is_empty() && lock.todo_accounts.is_empty()
+                    lock.account_backoff.reset(&character.name);
+                    lock.is_done()
                 };
 
                 if *crawl_que_id != que_id {
@@ -1221,6 +1142,30 @@ impl Helper {
                 *last_update = Local::now();
 
                 handle_new_char_info(character, equipment, player_info, naked);
+                server.crawl_rate.record(player_info.len());
+
+                // The headless crawler still needs its threads to notice,
+                // that it is done
+                if crawler_finished
+                    && finished.is_none()
+                    && server.headless_progress.is_none()
+                {
+                    info!("Finished crawling {}", server.ident.ident);
+                    *finished = Some(Local::now());
+                    *threads = 0;
+                    if self.config.notify_crawl_finished
+                        && let Err(e) = notify_rust::Notification::new()
+                            .appname("SF Assistant")
+                            .summary("Crawling finished")
+                            .body(&format!(
+                                "{} has been fully crawled",
+                                server.ident.ident
+                            ))
+                            .show()
+                    {
+                        warn!("Could not show notification: {e}");
+                    }
+                }
 
                 if crawler_finished {
                     let mut commands = vec![];
@@ -1252,18 +1197,20 @@ impl Helper {
                 };
                 let lock = que.lock().unwrap();
                 if server.headless_progress.is_none()
-                    || !lock.todo_pages.is_empty()
-                    || !lock.todo_accounts.is_empty()
+                    || !lock.is_done()
                     || player_info.is_empty()
                 {
                     return Command::none();
                 }
                 let backup = lock.create_backup(player_info);
+                drop(lock);
                 let ident = server.ident.ident.to_string();
                 let id = server.ident.id;
+                let dir = self.config.backup_dir.clone();
+                server.backups_in_flight += 1;
 
                 return Command::perform(
-                    async move { backup.write(&ident).await },
+                    async move { backup.write(&ident, dir.as_deref()).await },
                     move |res| Message::BackupRes {
                         server: id,
                         error: res.err().map(|a| a.to_string()),
@@ -1301,9 +1248,10 @@ impl Helper {
                         }
                         lock.in_flight_pages.retain(|x| x != a);
                         if error == CrawlerError::RateLimit {
-                            lock.todo_pages.push(*a);
+                            lock.page_backoff.delay(*a);
                             return Command::none();
                         } else {
+                            lock.page_backoff.reset(a);
                             lock.invalid_pages.push(*a);
                         }
                     }
@@ -1313,9 +1261,10 @@ impl Helper {
                         }
                         lock.in_flight_accounts.remove(a);
                         if error == CrawlerError::RateLimit {
-                            lock.todo_accounts.push(a.clone());
+                            lock.account_backoff.delay(a.clone());
                             return Command::none();
                         } else {
+                            lock.account_backoff.reset(a);
                             lock.invalid_accounts.push(a.clone());
                         }
                     }
@@ -1381,7 +1330,10 @@ impl Helper {
                 );
             }
             Message::ViewLogin => self.current_view = View::Login,
-            Message::LoginNameInputChange(a) => self.login_state.name = a,
+            Message::LoginNameInputChange(a) => {
+                self.login_state.name = a;
+                self.login_state.clear_messages();
+            }
             Message::LoginSFSubmit => {
                 return self.login_sf_acc(
                     self.login_state.name.clone(),
@@ -1390,8 +1342,27 @@ impl Helper {
                     false,
                 );
             }
-            Message::LoginPWInputChange(a) => self.login_state.password = a,
-            Message::LoginServerChange(a) => self.login_state.server = a,
+            Message::LoginPWInputChange(a) => {
+                self.login_state.password = a;
+                self.login_state.clear_messages();
+            }
+            Message::LoginServerChange(a) => {
+                self.login_state.server = a;
+                self.login_state.clear_messages();
+            }
+            Message::LoginRegularTest => return self.test_login_regular(),
+            Message::LoginSFTest => return self.test_login_sf(),
+            Message::ImportAccounts => return self.import_accounts(),
+            Message::SetLoginImported(val) => {
+                self.login_state.login_imported = val;
+            }
+            Message::LoginTestResult(res) => match res {
+                Ok(msg) => {
+                    info!("{msg}");
+                    self.login_state.test_result = Some(msg);
+                }
+                Err(err) => self.login_state.error = Some(err),
+            },
             Message::LoginRegularSubmit => {
                 let pw_hash = PWHash::new(&self.login_state.password.clone());
 
@@ -1411,6 +1382,7 @@ impl Helper {
                 session,
                 remember,
                 ident,
+                clock_skew,
             } => {
                 info!("Successfully logged in {ident}");
 
@@ -1422,6 +1394,19 @@ impl Helper {
                 else {
                     return Command::none();
                 };
+                self.login_retries
+                    .remove(&(ident.server_id, player.name.clone()));
+                player.last_successful_poll = Local::now();
+
+                if let Some((char_ident, page)) = &self.restore_view
+                    && ServerIdent::new(&char_ident.server).id
+                        == ident.server_id
+                    && char_ident.name.eq_ignore_ascii_case(&player.name)
+                    && matches!(self.current_view, View::Overview { .. })
+                {
+                    self.current_view = View::Account { ident, page: *page };
+                    self.restore_view = None;
+                }
 
                 if remember {
                     match &player.auth {
@@ -1444,7 +1429,7 @@ impl Helper {
                                     server: server.ident.url.clone(),
                                 },
                             ));
-                            _ = self.config.write();
+                            self.config_dirty = true;
                         }
                         PlayerAuth::SSO => {}
                     }
@@ -1456,8 +1441,24 @@ impl Helper {
                 let char_conf =
                     self.config.get_char_conf(&player.name, ident.server_id);
 
-                player.scrapbook_info = ScrapbookInfo::new(&gs, char_conf);
+                player.scrapbook_info = ScrapbookInfo::new(
+                    &gs, char_conf, self.config.battle_luck_factor,
+                );
                 player.underworld_info = UnderworldInfo::new(&gs, char_conf);
+                // Game timers are compared against the local time shifted by
+                // the skew, see `AccountInfo::server_now`
+                player.clock_offset = Some(
+                    gs.server_time().current() - Local::now().naive_local(),
+                );
+                player.server_skew = clock_skew;
+                if let Some(skew) = player.clock_skew()
+                    && skew.num_seconds().abs() > 30
+                {
+                    warn!(
+                        "The local clock is {}s off from the server of {ident}",
+                        skew.num_seconds()
+                    );
+                }
 
                 *player.status.lock().unwrap() =
                     AccountStatus::Idle(session, gs);
@@ -1465,15 +1466,26 @@ impl Helper {
                 let server_ident = server.ident.ident.clone();
                 let server_id = server.ident.id;
                 let afn = self.config.auto_fetch_newest;
+                let dir = self.config.backup_dir.clone();
+                let order = self.config.default_crawl_order;
                 match &server.crawling {
                     CrawlingStatus::Waiting => {
-                        server.crawling = CrawlingStatus::Restoring;
+                        let progress = Arc::new(RestoreProgress::default());
+                        server.crawling = CrawlingStatus::Restoring {
+                            progress: progress.clone(),
+                            done: 0,
+                            total: 0,
+                        };
                         return Command::perform(
                             async move {
                                 let backup =
-                                    get_newest_backup(server_ident, afn).await;
+                                    get_newest_backup(server_ident, afn, dir)
+                                        .await;
                                 Box::new(
-                                    restore_backup(backup, total_pages).await,
+                                    restore_backup(
+                                        backup, total_pages, progress, order,
+                                    )
+                                    .await,
                                 )
                             },
                             move |backup| Message::ResetCrawling {
@@ -1489,13 +1501,35 @@ impl Helper {
                     _ => (),
                 }
             }
-            Message::LoggininFailure { error, ident } => {
+            Message::LoggininFailure {
+                error,
+                ident,
+                transient,
+            } => {
                 error!("Error loggin in {ident}: {error}");
                 let Some((_, player)) = self.servers.get_ident(&ident) else {
                     return Command::none();
                 };
+                let key = (ident.server_id, player.name.clone());
+                let attempt =
+                    self.login_retries.get(&key).copied().unwrap_or(0);
+                if !transient || attempt >= LOGIN_RETRY_ATTEMPTS {
+                    self.login_retries.remove(&key);
+                    *player.status.lock().unwrap() =
+                        AccountStatus::FatalError(error);
+                    return Command::none();
+                }
+                let delay = (LOGIN_RETRY_BASE_SECS << attempt)
+                    .min(LOGIN_RETRY_MAX_SECS);
+                warn!("Retrying the login of {ident} in {delay}s");
+                self.login_retries.insert(key, attempt + 1);
+                let delay = Duration::from_secs(delay);
                 *player.status.lock().unwrap() =
-                    AccountStatus::FatalError(error)
+                    AccountStatus::WaitingToRetry {
+                        until: Local::now() + delay,
+                        error,
+                    };
+                return self.schedule_relogin(ident, delay);
             }
             Message::ShowPlayer { ident } => {
                 let Some(server) = self.servers.0.get_mut(&ident.server_id)
@@ -1532,11 +1566,15 @@ impl Helper {
                 };
 
                 let mut commands = vec![];
+                // Finished crawls have stopped their threads
+                let mut restart_threads = false;
                 match &mut server.crawling {
-                    CrawlingStatus::Waiting | CrawlingStatus::Restoring => {
+                    CrawlingStatus::Waiting
+                    | CrawlingStatus::Restoring { .. } => {
                         server.crawling = status.into_status();
                         commands.push(server.set_threads(
-                            self.config.start_threads, &self.config.base_name,
+                            self.config.start_threads_for(server_id),
+                            &self.config.base_name,
                         ));
                     }
                     CrawlingStatus::Crawling {
@@ -1547,8 +1585,10 @@ impl Helper {
                         last_update,
                         recent_failures,
                         naked,
+                        finished,
                         threads: _,
                         crawling_session: _,
+                        paused: _,
                     } => {
                         let mut que = que.lock().unwrap();
                         que.que_id = status.que_id;
@@ -1564,6 +1604,7 @@ impl Helper {
                         *player_info = status.player_info;
                         *equipment = status.equipment;
                         *last_update = Local::now();
+                        restart_threads = finished.take().is_some();
                         recent_failures.clear();
                         drop(que);
                     }
@@ -1576,6 +1617,13 @@ impl Helper {
                     return Command::none();
                 };
 
+                if restart_threads {
+                    commands.push(server.set_threads(
+                        self.config.start_threads_for(server_id),
+                        &self.config.base_name,
+                    ));
+                }
+
                 let todo: Vec<_> =
                     server.accounts.values().map(|a| a.ident).collect();
                 for acc in todo {
@@ -1584,6 +1632,7 @@ impl Helper {
                 return Command::batch(commands);
             }
             Message::RemoveAccount { ident } => {
+                self.logout_armed = false;
                 let Some(server) = self.servers.get_mut(&ident.server_id)
                 else {
                     return Command::none();
@@ -1608,23 +1657,65 @@ impl Helper {
                     {
                         self.current_view = View::Login;
                     }
-                    View::Overview { selected, action } => {
+                    View::Overview {
+                        selected, action, ..
+                    } => {
                         _ = selected.remove(&ident);
                         *action = None;
                     }
                     _ => {}
                 }
             }
-            Message::CrawlerSetThreads {
-                server: server_id,
-                new_count,
-            } => {
+            Message::RetryLogin { ident } => return self.retry_login(ident),
+            Message::ScheduledRelogin { ident, timer } => {
+                let Some((_, account)) = self.servers.get_ident(&ident) else {
+                    return Command::none();
+                };
+                if account.relogin_timer != Some(timer) {
+                    return Command::none();
+                }
+                return self.retry_login(ident);
+            }
+            Message::CrawlerSetThreads { ident, new_count } => {
                 let new_count = new_count.clamp(0, self.config.max_threads);
+                let server_id = ident.server_id;
                 let Some(server) = self.servers.get_mut(&server_id) else {
                     return Command::none();
                 };
+                let Some(account) = server.accounts.get(&ident.account) else {
+                    return Command::none();
+                };
+
+                let Some(cfg) =
+                    self.config.get_char_conf_mut(&account.name, server_id)
+                else {
+                    return server
+                        .set_threads(new_count, &self.config.base_name);
+                };
+                cfg.crawl_thread_override = Some(new_count);
+                self.config_dirty = true;
 
-                return server.set_threads(new_count, &self.config.base_name);
+                let threads = self.config.start_threads_for(server_id);
+                return server.set_threads(threads, &self.config.base_name);
+            }
+            Message::CrawlerSetPaused {
+                server: server_id,
+                paused: new_paused,
+            } => {
+                let Some(server) = self.servers.get_mut(&server_id) else {
+                    return Command::none();
+                };
+                let CrawlingStatus::Crawling { que, paused, .. } =
+                    &mut server.crawling
+                else {
+                    return Command::none();
+                };
+                *paused = new_paused;
+                que.lock().unwrap().paused = new_paused;
+                match new_paused {
+                    true => info!("Paused crawling {}", server.ident.ident),
+                    false => info!("Resumed crawling {}", server.ident.ident),
+                }
             }
             Message::ClearHof(server_id) => {
                 let Some(server) = self.servers.get_mut(&server_id) else {
@@ -1634,6 +1725,9 @@ impl Helper {
                 let Some(tp) = server.accounts.iter().find_map(|(_, b)| {
                     match &*b.status.lock().unwrap() {
                         AccountStatus::LoggingInAgain
+                        | AccountStatus::Relogging { .. }
+                        | AccountStatus::WaitingToRetry { .. }
+                        | AccountStatus::LoggedOutIdle { .. }
                         | AccountStatus::LoggingIn
                         | AccountStatus::FatalError(_) => None,
                         AccountStatus::Idle(_, gs)
@@ -1648,9 +1742,15 @@ impl Helper {
                 let tp = (tp as usize).div_ceil(PER_PAGE);
 
                 let id = server.ident.id;
+                let order = self.config.default_crawl_order;
 
                 return Command::perform(
-                    async move { Box::new(restore_backup(None, tp).await) },
+                    async move {
+                        Box::new(
+                            restore_backup(None, tp, Default::default(), order)
+                                .await,
+                        )
+                    },
                     move |res| Message::ResetCrawling {
                         server: id,
                         status: res,
@@ -1688,6 +1788,10 @@ impl Helper {
             }
             Message::AutoBattlePossible { ident } => {
                 let refetch = self.update_best(ident, true);
+                // Attacks the user asked for come first
+                if let Some(attack) = self.fire_manual_attack(ident) {
+                    return Command::batch([refetch, attack]);
+                }
 
                 let Some(server) = self.servers.0.get_mut(&ident.server_id)
                 else {
@@ -1701,15 +1805,28 @@ impl Helper {
                 let CrawlingStatus::Crawling { .. } = &server.crawling else {
                     return Command::none();
                 };
+                if !self.config.char_enabled(&account.name, ident.server_id) {
+                    return refetch;
+                }
 
                 let mut status = account.status.lock().unwrap();
                 let AccountStatus::Idle(_, gs) = &*status else {
                     return refetch;
                 };
                 let next = gs.arena.next_free_fight.unwrap_or_default();
-                if next > Local::now() + Duration::from_millis(200) {
+                let use_mushroom =
+                    next > account.server_now() + Duration::from_millis(200);
+                if use_mushroom
+                    && !self
+                        .config
+                        .get_char_conf(&account.name, ident.server_id)
+                        .is_some_and(|cfg| {
+                            account.can_fight_with_mushroom(gs, cfg)
+                        })
+                {
                     return refetch;
                 }
+                let day = server_day(gs);
 
                 let Some(mut session) = status.take_session("A Fighting")
                 else {
@@ -1721,10 +1838,7 @@ impl Helper {
                     return refetch;
                 };
 
-                let total_len = si.best.len();
-                let new_len = si.best.iter().filter(|a| !a.is_old()).count();
-
-                if total_len == 0 || (new_len as f32 / total_len as f32) < 0.9 {
+                if !si.has_fresh_targets(self.config.battle_freshness()) {
                     status.put_session(session);
                     return refetch;
                 }
@@ -1737,12 +1851,16 @@ impl Helper {
                 };
                 drop(status);
 
+                if use_mushroom {
+                    account.record_mushrooms_spent(day, MushroomUse::Arena, 1);
+                }
+
                 let tn = target.info.name.clone();
                 let fight = Command::perform(
                     async move {
                         let cmd = sf_api::command::Command::Fight {
                             name: tn,
-                            use_mushroom: false,
+                            use_mushroom,
                         };
                         let resp = session.send_command(&cmd).await;
                         (resp, session)
@@ -1778,10 +1896,29 @@ impl Helper {
                     return Command::none();
                 };
 
+                player.pending_action = None;
+                player.souls_collect_pending = false;
+                player.lure_in_flight = None;
+                if let Some(si) = &mut player.scrapbook_info
+                    && si.farming
+                {
+                    info!("Stopped farming for {ident}: a command failed");
+                    si.farming = false;
+                }
+                // A session of an expired SSO login will never work again
+                if matches!(player.auth, PlayerAuth::SSO)
+                    && attempt >= SSO_RELOG_ATTEMPTS
+                {
+                    warn!("SSO session of {ident} expired");
+                    *player.status.lock().unwrap() = AccountStatus::FatalError(
+                        "The SSO session expired".to_string(),
+                    );
+                    return self.reauth_sso(ident, false);
+                }
                 let mut lock = player.status.lock().unwrap();
-                *lock = AccountStatus::LoggingInAgain;
+                *lock = AccountStatus::Relogging { attempt };
                 drop(lock);
-                warn!("Logging in {ident} again");
+                warn!("Logging in {ident} again (attempt {})", attempt + 1);
                 return Command::perform(
                     async move {
                         let Ok(resp) = session.login().await else {
@@ -1840,27 +1977,55 @@ impl Helper {
 
                 let nt = against.info.name.clone();
                 let ut = against.info.uid;
+                let won = last.has_player_won;
 
                 let Some(si) = &mut account.scrapbook_info else {
                     return Command::none();
                 };
 
+                let mut items_gained = 0;
                 if last.has_player_won {
                     for new in &against.info.equipment {
-                        si.scrapbook.items.insert(*new);
+                        if si.scrapbook.items.insert(*new) {
+                            items_gained += 1;
+                        }
                     }
                 }
 
+                account.last_action = Local::now();
+                account.last_action_desc = format!(
+                    "{} against {}",
+                    if last.has_player_won { "won" } else { "lost" },
+                    against.info.name
+                );
+
                 si.attack_log.push((
                     Local::now(),
                     against,
                     last.has_player_won,
+                    items_gained,
                 ));
 
+                if let Some(cfg) = self
+                    .config
+                    .get_char_conf_mut(&account.name, server.ident.id)
+                {
+                    match last.has_player_won {
+                        true => cfg.stats.wins += 1,
+                        false => cfg.stats.losses += 1,
+                    }
+                    cfg.stats.items_gained += items_gained as u64;
+                }
+
                 let mut res = Command::none();
 
                 if !last.has_player_won {
-                    si.blacklist.entry(ut).or_insert((nt, 0)).1 += 1;
+                    // Players attacked by name only have no known uid
+                    // Manual entries are at the max already and stay there
+                    if ut != 0 {
+                        let entry = si.blacklist.entry(ut).or_insert((nt, 0));
+                        entry.1 = entry.1.saturating_add(1);
+                    }
                 } else if let CrawlingStatus::Crawling { .. } = &server.crawling
                 {
                     let ident = account.ident;
@@ -1868,7 +2033,32 @@ impl Helper {
                 }
 
                 lock.put_session(session);
-                return res;
+                self.config_dirty = true;
+
+                let Some(si) = self
+                    .servers
+                    .get_mut(&ident.server_id)
+                    .and_then(|a| a.accounts.get_mut(&ident.account))
+                    .and_then(|a| a.scrapbook_info.as_mut())
+                    .filter(|a| a.farming)
+                else {
+                    return res;
+                };
+                if !won {
+                    info!("Stopped farming for {ident}: lost against {nt}");
+                    si.farming = false;
+                    return res;
+                }
+                if items_gained == 0 {
+                    info!("Stopped farming for {ident}: {nt} had no new items");
+                    si.farming = false;
+                    return res;
+                }
+                let next = Command::perform(
+                    sleep(Duration::from_millis(FARMING_DELAY_MS)),
+                    move |_| Message::FarmNextFight { ident },
+                );
+                return Command::batch([res, next]);
             }
             Message::AutoBattle { ident, state } => {
                 let Some(server) = self.servers.0.get_mut(&ident.server_id)
@@ -1941,39 +2131,87 @@ impl Helper {
                 que.todo_pages.append(&mut ok_pages);
             }
             Message::ViewOverview => {
+                self.logout_armed = false;
                 self.current_view = View::Overview {
                     selected: Default::default(),
                     action: Default::default(),
+                    filter: Default::default(),
+                    sort: Default::default(),
+                    sort_desc: false,
                 };
             }
-            Message::ChangeTheme(theme) => {
-                self.config.theme = theme;
-                _ = self.config.write();
+            Message::WindowResized { width, height } => {
+                if self.config.window_width == Some(width)
+                    && self.config.window_height == Some(height)
+                {
+                    return Command::none();
+                }
+                self.config.window_width = Some(width);
+                self.config.window_height = Some(height);
+                return self.window_changed();
             }
-            Message::ConfigSetUseTavernGlasses { name, server, nv } => {
-                if let Some(cc) = self.config.get_char_conf_mut(&name, server)
+            Message::WindowMoved { x, y } => {
+                if self.config.window_x == Some(x)
+                    && self.config.window_y == Some(y)
                 {
+                    return Command::none();
+                }
+                self.config.window_x = Some(x);
+                self.config.window_y = Some(y);
+                return self.window_changed();
+            }
+            Message::WindowSettled(change) => {
+                if change == self.window_changes {
+                    self.config_dirty = true;
+                }
+            }
+            Message::ChangeTheme(theme) => {
+                self.config.theme = theme;
+                self.config_dirty = true;
+            }
+            Message::ConfigSetUseTavernGlasses { name, server, nv } => {
+                if let Some(cc) = self.config.get_char_conf_mut(&name, server) {
                     cc.use_glasses_for_tavern = nv;
-                    let _ = self.config.write();
+                    self.config_dirty = true;
                 }
             }
             Message::ConfigSetUseExpeditionGlasses { name, server, nv } => {
-                if let Some(cc) = self.config.get_char_conf_mut(&name, server)
-                {
+                if let Some(cc) = self.config.get_char_conf_mut(&name, server) {
                     cc.use_glasses_for_expeditions = nv;
-                    let _ = self.config.write();
+                    self.config_dirty = true;
                 }
             }
             Message::ConfigSetExpeditionRewardPriority { name, server, nv } => {
-                if let Some(cc) = self.config.get_char_conf_mut(&name, server)
-                {
+                if let Some(cc) = self.config.get_char_conf_mut(&name, server) {
                     cc.expedition_reward_priority = nv;
-                    let _ = self.config.write();
+                    self.config_dirty = true;
                 }
             }
             Message::ViewSettings => {
+                self.custom_themes = AvailableTheme::find_custom();
                 self.current_view = View::Settings;
             }
+            Message::ViewLogs => {
+                self.current_view = View::Logs {
+                    level: log::Level::Info,
+                };
+            }
+            Message::SetLogLevel(nv) => {
+                if let View::Logs { level } = &mut self.current_view {
+                    *level = nv;
+                }
+            }
+            Message::CopyLogs => {
+                let level = match &self.current_view {
+                    View::Logs { level } => *level,
+                    _ => log::Level::Debug,
+                };
+                let lines: Vec<_> = logs::recent_lines(level)
+                    .iter()
+                    .map(|a| a.to_string())
+                    .collect();
+                return iced::clipboard::write(lines.join("\n"));
+            }
             Message::SSOLoginSuccess {
                 name,
                 pass,
@@ -2013,7 +2251,7 @@ impl Helper {
                             })
                             .collect(),
                     });
-                    _ = self.config.write();
+                    self.config_dirty = true;
                 }
 
                 if let Some(existing) = self.config.get_sso_accounts_mut(&name)
@@ -2055,13 +2293,14 @@ impl Helper {
                     }
 
                     if modified {
-                        _ = self.config.write();
+                        self.config_dirty = true;
                     }
                 }
 
                 self.login_state.import_que.append(&mut chars);
 
                 res.status = SSOLoginStatus::Success;
+                let reauthed = self.import_reauthed();
                 if auto_login {
                     for acc in &self.config.accounts {
                         let AccountConfig::SF {
@@ -2075,17 +2314,28 @@ impl Helper {
                         if s_name != &name {
                             continue;
                         }
-                        let mut commands = vec![];
-                        for SFAccCharacter { ident, config } in characters {
-                            if !config.login {
-                                continue;
-                            }
-                            let ident = ident.clone();
-                            commands
-                                .push(Command::perform(async {}, move |_| {
-                                    Message::SSOImportAuto { ident }
-                                }))
-                        }
+                        // Spread the logins out, so that we do not hit the
+                        // server with all of them at once
+                        let stagger = self.config.login_stagger_ms;
+                        let mut commands = characters
+                            .iter()
+                            .filter(|a| a.config.login)
+                            .enumerate()
+                            .map(|(pos, SFAccCharacter { ident, .. })| {
+                                let ident = ident.clone();
+                                let delay = stagger * pos as u64;
+                                Command::perform(
+                                    async move {
+                                        if delay > 0 {
+                                            sleep(Duration::from_millis(delay))
+                                                .await;
+                                        }
+                                    },
+                                    move |_| Message::SSOImportAuto { ident },
+                                )
+                            })
+                            .collect::<Vec<_>>();
+                        commands.push(reauthed);
                         return Command::batch(commands);
                     }
                 }
@@ -2095,12 +2345,14 @@ impl Helper {
                 {
                     self.login_state.login_typ = LoginType::SSOChars;
                 };
+                return reauthed;
             }
             Message::SSOImport { pos } => {
                 let account = self.login_state.import_que.remove(pos);
                 return self.login(account, false, PlayerAuth::SSO, false);
             }
             Message::ViewSubPage { player, page } => {
+                self.logout_armed = false;
                 self.current_view = View::Account {
                     ident: player,
                     page,
@@ -2108,7 +2360,7 @@ impl Helper {
             }
             Message::SetAutoFetch(b) => {
                 self.config.auto_fetch_newest = b;
-                _ = self.config.write();
+                self.config_dirty = true;
             }
             Message::SetMaxThreads(nv) => {
                 self.config.max_threads = nv.clamp(0, 50);
@@ -2116,12 +2368,16 @@ impl Helper {
                     .config
                     .start_threads
                     .clamp(0, 50.min(self.config.max_threads));
-                _ = self.config.write();
+                self.config_dirty = true;
             }
             Message::SetStartThreads(nv) => {
                 self.config.start_threads =
                     nv.clamp(0, 50.min(self.config.max_threads));
-                _ = self.config.write();
+                self.config_dirty = true;
+            }
+            Message::SetAutosaveInterval(nv) => {
+                self.config.autosave_interval_min = nv;
+                self.config_dirty = true;
             }
             Message::SSOSuccess {
                 auth_name,
@@ -2151,9 +2407,15 @@ impl Helper {
                 {
                     self.login_state.login_typ = LoginType::SSOChars;
                 };
+                return self.import_reauthed();
             }
             Message::SSORetry => {}
-            Message::SSOAuthError { .. } => {}
+            Message::SSOAuthError { error } => {
+                warn!("SSO authentication failed: {error}");
+            }
+            Message::ReauthSSO { ident } => {
+                return self.reauth_sso(ident, true);
+            }
             Message::OpenLink(url) => {
                 _ = open::that(url);
             }
@@ -2166,18 +2428,64 @@ impl Helper {
                 else {
                     return Command::none();
                 };
-                let CrawlingStatus::Crawling { .. } = &server.crawling else {
-                    return Command::none();
-                };
 
+                let cfg =
+                    self.config.get_char_conf(&account.name, ident.server_id);
                 let mut status = account.status.lock().unwrap();
-                let AccountStatus::Idle(_, gs) = &*status else {
-                    return Command::none();
+                // During the cooldown, the fight is paid for with a mushroom
+                // right away, as long as the arena budget allows it. Only
+                // without mushrooms the attack has to wait
+                let (must_wait, mushroom_day, capped) = match &*status {
+                    AccountStatus::Idle(_, gs) => {
+                        let next = gs.arena.next_free_fight.unwrap_or_default();
+                        let cooldown = next
+                            > account.server_now() + Duration::from_millis(200);
+                        let affordable = cfg.is_some_and(|cfg| {
+                            account.can_spend_mushroom(
+                                gs,
+                                cfg,
+                                MushroomUse::Arena,
+                            )
+                        });
+                        (
+                            cooldown && gs.character.mushrooms == 0,
+                            (cooldown && affordable).then(|| server_day(gs)),
+                            cooldown
+                                && gs.character.mushrooms > 0
+                                && !affordable,
+                        )
+                    }
+                    AccountStatus::Busy(..) => (true, None, false),
+                    _ => return Command::none(),
                 };
-                let next = gs.arena.next_free_fight.unwrap_or_default();
-                if next > Local::now() + Duration::from_millis(200)
-                    && gs.character.mushrooms == 0
-                {
+                if capped {
+                    drop(status);
+                    warn!(
+                        "Not attacking {} for {ident}: the arena mushroom cap \
+                         is reached",
+                        target.info.name
+                    );
+                    account.record_action(format!(
+                        "not attacking {}, the arena mushroom cap is reached",
+                        target.info.name
+                    ));
+                    return Command::none();
+                }
+                if must_wait {
+                    drop(status);
+                    let queue = &mut account.manual_attack_queue;
+                    if queue.len() >= MANUAL_ATTACK_QUEUE_MAX {
+                        warn!("Attack queue of {ident} is full");
+                    } else if !queue
+                        .iter()
+                        .any(|a| a.info.name == target.info.name)
+                    {
+                        info!(
+                            "Queued attack on {} for {ident}",
+                            target.info.name
+                        );
+                        queue.push(target);
+                    }
                     return Command::none();
                 }
 
@@ -2185,13 +2493,17 @@ impl Helper {
                     return Command::none();
                 };
                 drop(status);
+                let use_mushroom = mushroom_day.is_some();
+                if let Some(day) = mushroom_day {
+                    account.record_mushrooms_spent(day, MushroomUse::Arena, 1);
+                }
                 let ident = account.ident;
                 let tn = target.info.name.clone();
                 return Command::perform(
                     async move {
                         let cmd = sf_api::command::Command::Fight {
                             name: tn,
-                            use_mushroom: false,
+                            use_mushroom,
                         };
                         let resp = session.send_command(&cmd).await;
                         (resp, session)
@@ -2211,6 +2523,17 @@ impl Helper {
                     },
                 );
             }
+            Message::ClearManualAttacks { ident } => {
+                let Some(server) = self.servers.get_mut(&ident.server_id)
+                else {
+                    return Command::none();
+                };
+                let Some(account) = server.accounts.get_mut(&ident.account)
+                else {
+                    return Command::none();
+                };
+                account.manual_attack_queue.clear();
+            }
             Message::PlayerSetMaxLvl { ident, max } => {
                 let Some(server) = self.servers.get_mut(&ident.server_id)
                 else {
@@ -2224,6 +2547,13 @@ impl Helper {
                     return Command::none();
                 };
                 si.max_level = max;
+                if let Some(cfg) = self
+                    .config
+                    .get_char_conf_mut(&account.name, server.ident.id)
+                {
+                    cfg.scrapbook_max_level = Some(max);
+                    self.config_dirty = true;
+                }
                 return self.update_best(ident, false);
             }
             Message::PlayerSetMaxAttributes { ident, max } => {
@@ -2239,12 +2569,230 @@ impl Helper {
                     return Command::none();
                 };
                 si.max_attributes = max;
+                if let Some(cfg) = self
+                    .config
+                    .get_char_conf_mut(&account.name, server.ident.id)
+                {
+                    cfg.scrapbook_max_attributes = Some(max);
+                    self.config_dirty = true;
+                }
+                return self.update_best(ident, false);
+            }
+            Message::PlayerSetBattleOrdering { ident, nv } => {
+                let Some(server) = self.servers.get_mut(&ident.server_id)
+                else {
+                    return Command::none();
+                };
+                let Some(account) = server.accounts.get_mut(&ident.account)
+                else {
+                    return Command::none();
+                };
+                let Some(si) = &mut account.scrapbook_info else {
+                    return Command::none();
+                };
+                si.battle_ordering = nv;
+                if let Some(cfg) = self
+                    .config
+                    .get_char_conf_mut(&account.name, server.ident.id)
+                {
+                    cfg.battle_ordering = nv;
+                    self.config_dirty = true;
+                }
                 return self.update_best(ident, false);
             }
+            Message::PlayerSetFarming { ident, nv } => {
+                let Some(server) = self.servers.get_mut(&ident.server_id)
+                else {
+                    return Command::none();
+                };
+                let Some(account) = server.accounts.get_mut(&ident.account)
+                else {
+                    return Command::none();
+                };
+                let Some(si) = &mut account.scrapbook_info else {
+                    return Command::none();
+                };
+                si.farming = nv;
+                si.farming_spent = 0;
+                si.farming_busy_waits = 0;
+                if nv {
+                    info!("Started farming the scrapbook of {ident}");
+                    return self.handle_msg(Message::FarmNextFight { ident });
+                }
+            }
+            Message::FarmNextFight { ident } => {
+                let Some(server) = self.servers.get_mut(&ident.server_id)
+                else {
+                    return Command::none();
+                };
+                let Some(account) = server.accounts.get_mut(&ident.account)
+                else {
+                    return Command::none();
+                };
+                if !account.scrapbook_info.as_ref().is_some_and(|a| a.farming) {
+                    return Command::none();
+                }
+
+                let status = account.status.clone();
+                let mut status = status.lock().unwrap();
+                let gs = match &*status {
+                    AccountStatus::Idle(_, gs) => gs,
+                    AccountStatus::Busy(..) => {
+                        drop(status);
+                        let Some(si) = &mut account.scrapbook_info else {
+                            return Command::none();
+                        };
+                        si.farming_busy_waits += 1;
+                        if si.farming_busy_waits > FARMING_BUSY_WAITS {
+                            info!(
+                                "Stopped farming for {ident}: the session stayed busy"
+                            );
+                            si.farming = false;
+                            return Command::none();
+                        }
+                        // Something else is using the session. Try again
+                        // once it is done
+                        return Command::perform(
+                            sleep(Duration::from_millis(FARMING_DELAY_MS)),
+                            move |_| Message::FarmNextFight { ident },
+                        );
+                    }
+                    _ => return Command::none(),
+                };
+
+                let next = gs.arena.next_free_fight.unwrap_or_default();
+                let use_mushroom =
+                    next > account.server_now() + Duration::from_millis(200);
+                let day = server_day(gs);
+                let budget_left = self
+                    .config
+                    .get_char_conf(&account.name, ident.server_id)
+                    .is_some_and(|cfg| {
+                        let spent = account
+                            .scrapbook_info
+                            .as_ref()
+                            .map_or(0, |a| a.farming_spent);
+                        spent < cfg.max_mushrooms_farming
+                            && gs.character.mushrooms > 0
+                    });
+
+                let crawling =
+                    matches!(server.crawling, CrawlingStatus::Crawling { .. });
+                let Some(si) = &mut account.scrapbook_info else {
+                    return Command::none();
+                };
+                if !crawling {
+                    info!(
+                        "Stopped farming for {ident}: the server is not crawled"
+                    );
+                    si.farming = false;
+                    return Command::none();
+                }
+                if use_mushroom && !budget_left {
+                    info!(
+                        "Stopped farming for {ident}: the mushroom budget is exhausted"
+                    );
+                    si.farming = false;
+                    return Command::none();
+                }
+                let Some(target) =
+                    si.best.iter().find(|a| !a.is_old()).cloned()
+                else {
+                    info!("Stopped farming for {ident}: no targets left");
+                    si.farming = false;
+                    return Command::none();
+                };
+                let Some(mut session) = status.take_session("Farming") else {
+                    return Command::none();
+                };
+                drop(status);
+
+                si.farming_busy_waits = 0;
+                if use_mushroom {
+                    si.farming_spent += 1;
+                    account.record_mushrooms_spent(
+                        day,
+                        MushroomUse::Farming,
+                        1,
+                    );
+                }
+
+                let tn = target.info.name.clone();
+                return Command::perform(
+                    async move {
+                        let cmd = sf_api::command::Command::Fight {
+                            name: tn,
+                            use_mushroom,
+                        };
+                        let resp = session.send_command(&cmd).await;
+                        (resp, session)
+                    },
+                    move |r| match r.0 {
+                        Ok(resp) => Message::PlayerAttackResult {
+                            ident,
+                            session: r.1,
+                            against: target,
+                            resp: Box::new(resp),
+                        },
+                        Err(_) => Message::PlayerCommandFailed {
+                            ident,
+                            session: r.1,
+                            attempt: 0,
+                        },
+                    },
+                );
+            }
+            Message::PlayerSetLureExportFormat { ident, nv } => {
+                let Some(server) = self.servers.get_mut(&ident.server_id)
+                else {
+                    return Command::none();
+                };
+                let Some(account) = server.accounts.get_mut(&ident.account)
+                else {
+                    return Command::none();
+                };
+                let Some(ui) = &mut account.underworld_info else {
+                    return Command::none();
+                };
+                ui.export_format = nv;
+                if let Some(cfg) = self
+                    .config
+                    .get_char_conf_mut(&account.name, server.ident.id)
+                {
+                    cfg.lure_export_format = nv;
+                    self.config_dirty = true;
+                }
+            }
+            Message::PlayerSetLureExportOld { ident, nv } => {
+                let Some(server) = self.servers.get_mut(&ident.server_id)
+                else {
+                    return Command::none();
+                };
+                let Some(account) = server.accounts.get_mut(&ident.account)
+                else {
+                    return Command::none();
+                };
+                let Some(ui) = &mut account.underworld_info else {
+                    return Command::none();
+                };
+                ui.export_old = nv;
+                if let Some(cfg) = self
+                    .config
+                    .get_char_conf_mut(&account.name, server.ident.id)
+                {
+                    cfg.lure_export_old = nv;
+                    self.config_dirty = true;
+                }
+            }
             Message::SaveHoF(server_id) => {
-                let Some(server) = self.servers.get(&server_id) else {
+                let Some(server) = self.servers.get_mut(&server_id) else {
                     return Command::none();
                 };
+                // Two writes to the same file could leave a broken backup
+                if server.backups_in_flight > 0 {
+                    debug!("Skipping HoF save, a backup is already written");
+                    return Command::none();
+                }
 
                 let CrawlingStatus::Crawling {
                     que, player_info, ..
@@ -2258,15 +2806,75 @@ impl Helper {
                 drop(lock);
                 let id = server.ident.id;
                 let ident = server.ident.ident.to_string();
+                let dir = self.config.backup_dir.clone();
+                server.backups_in_flight += 1;
 
                 return Command::perform(
-                    async move { backup.write(&ident).await },
+                    async move { backup.write(&ident, dir.as_deref()).await },
                     move |res| Message::BackupRes {
                         server: id,
                         error: res.err().map(|a| a.to_string()),
                     },
                 );
             }
+            Message::SaveAllAndExit => {
+                if self.saving_on_exit {
+                    return Command::none();
+                }
+                self.saving_on_exit = true;
+                if self.config_dirty {
+                    self.config_dirty = false;
+                    _ = self.config.write();
+                }
+                return Command::perform(async {}, |_| Message::ExitBackups);
+            }
+            Message::ExitBackups => {
+                // Writing the same file twice at once could leave a broken
+                // backup, so we wait for the running writes to finish
+                if self.servers.0.values().any(|a| a.backups_in_flight > 0) {
+                    return Command::perform(
+                        sleep(Duration::from_millis(100)),
+                        |_| Message::ExitBackups,
+                    );
+                }
+
+                let mut backups = vec![];
+                for server in self.servers.0.values() {
+                    let CrawlingStatus::Crawling {
+                        que, player_info, ..
+                    } = &server.crawling
+                    else {
+                        continue;
+                    };
+                    if player_info.is_empty() {
+                        continue;
+                    }
+                    let lock = que.lock().unwrap();
+                    let backup = lock.create_backup(player_info);
+                    drop(lock);
+                    backups.push((server.ident.ident.to_string(), backup));
+                }
+
+                info!("Saving {} backups before exit", backups.len());
+                let dir = self.config.backup_dir.clone();
+                return Command::perform(
+                    async move {
+                        for (ident, backup) in backups {
+                            if let Err(e) =
+                                backup.write(&ident, dir.as_deref()).await
+                            {
+                                error!(
+                                    "Could not write backup for {ident}: {e}"
+                                );
+                            }
+                        }
+                    },
+                    |_| Message::Exit,
+                );
+            }
+            Message::Exit => {
+                return iced::window::close(iced::window::Id::MAIN);
+            }
             Message::BackupRes {
                 server: server_id,
                 error,
@@ -2274,80 +2882,323 @@ impl Helper {
                 let Some(server) = self.servers.get_mut(&server_id) else {
                     return Command::none();
                 };
-                let Some(pb) = server.headless_progress.clone() else {
+                server.backups_in_flight =
+                    server.backups_in_flight.saturating_sub(1);
+                let Some(pb) = server.headless_progress.clone() else {
+                    return Command::none();
+                };
+                if let Some(err) = error {
+                    pb.println(format!(
+                        "Could not write backup for {}: {err}",
+                        server.ident.url
+                    ));
+                    if self
+                        .cli_crawling
+                        .as_ref()
+                        .is_some_and(|a| a.exit_on_error)
+                    {
+                        pb.finish_and_clear();
+                        std::process::exit(1);
+                    }
+                }
+                self.servers.0.remove(&server_id);
+                pb.finish_and_clear();
+                return Command::perform(async {}, |_| {
+                    Message::NextCLICrawling
+                });
+            }
+            Message::CopyBattleOrder { ident } => {
+                let Some(target_list) = self.battle_order(&ident) else {
+                    return Command::none();
+                };
+                return iced::clipboard::write(target_list.join("/"));
+            }
+            Message::ScrapbookBlacklistInput { ident, nv } => {
+                let Some(server) = self.servers.get_mut(&ident.server_id)
+                else {
+                    return Command::none();
+                };
+                let Some(account) = server.accounts.get_mut(&ident.account)
+                else {
+                    return Command::none();
+                };
+                let Some(si) = &mut account.scrapbook_info else {
+                    return Command::none();
+                };
+                si.blacklist_input = nv;
+            }
+            Message::ScrapbookShowMissing { ident, val } => {
+                let Some(server) = self.servers.get_mut(&ident.server_id)
+                else {
+                    return Command::none();
+                };
+                let Some(account) = server.accounts.get_mut(&ident.account)
+                else {
+                    return Command::none();
+                };
+                let Some(si) = &mut account.scrapbook_info else {
+                    return Command::none();
+                };
+                si.show_missing = val;
+                return self.update_best(ident, false);
+            }
+            Message::ScrapbookBlacklistAdd { ident } => {
+                let Some(server) = self.servers.get_mut(&ident.server_id)
+                else {
+                    return Command::none();
+                };
+                let CrawlingStatus::Crawling { player_info, .. } =
+                    &server.crawling
+                else {
+                    return Command::none();
+                };
+                let Some(account) = server.accounts.get_mut(&ident.account)
+                else {
+                    return Command::none();
+                };
+                let Some(si) = &mut account.scrapbook_info else {
+                    return Command::none();
+                };
+                let name = si.blacklist_input.trim().to_lowercase();
+                if name.is_empty() {
+                    return Command::none();
+                }
+                let Some(info) = player_info
+                    .values()
+                    .find(|a| a.name.to_lowercase() == name)
+                else {
+                    warn!("Can not blacklist {name}. The player is not known");
+                    return Command::none();
+                };
+                si.blacklist
+                    .insert(info.uid, (info.name.clone(), MANUAL_BLACKLIST));
+                si.blacklist_input.clear();
+                return self.update_best(ident, false);
+            }
+            Message::ScrapbookFightNameInput { ident, nv } => {
+                let Some(server) = self.servers.get_mut(&ident.server_id)
+                else {
+                    return Command::none();
+                };
+                let Some(account) = server.accounts.get_mut(&ident.account)
+                else {
+                    return Command::none();
+                };
+                let Some(si) = &mut account.scrapbook_info else {
+                    return Command::none();
+                };
+                si.fight_name_input = nv;
+            }
+            Message::PlayerAttackByName { ident } => {
+                let Some(server) = self.servers.get_mut(&ident.server_id)
+                else {
+                    return Command::none();
+                };
+                let Some(account) = server.accounts.get_mut(&ident.account)
+                else {
+                    return Command::none();
+                };
+                let Some(si) = &mut account.scrapbook_info else {
+                    return Command::none();
+                };
+                let name = si.fight_name_input.trim().to_string();
+                if name.is_empty() {
+                    return Command::none();
+                }
+                let lower = name.to_lowercase();
+                let known = match &server.crawling {
+                    CrawlingStatus::Crawling { player_info, .. } => player_info
+                        .values()
+                        .find(|a| a.name.to_lowercase() == lower)
+                        .cloned(),
+                    _ => None,
+                };
+                let target = match known {
+                    Some(info) => AttackTarget {
+                        missing: info
+                            .equipment
+                            .iter()
+                            .filter(|a| !si.scrapbook.items.contains(a))
+                            .count(),
+                        info,
+                    },
+                    None => AttackTarget {
+                        missing: 0,
+                        info: CharacterInfo {
+                            equipment: vec![],
+                            name,
+                            uid: 0,
+                            level: 0,
+                            stats: None,
+                            fetch_date: None,
+                            class: None,
+                        },
+                    },
+                };
+                si.fight_name_input.clear();
+                return Command::perform(async {}, move |_| {
+                    Message::PlayerAttack { ident, target }
+                });
+            }
+            Message::ScrapbookBlacklistRemove { ident, uid } => {
+                let Some(server) = self.servers.get_mut(&ident.server_id)
+                else {
+                    return Command::none();
+                };
+                let Some(account) = server.accounts.get_mut(&ident.account)
+                else {
+                    return Command::none();
+                };
+                let Some(si) = &mut account.scrapbook_info else {
+                    return Command::none();
+                };
+                si.blacklist.remove(&uid);
+                return self.update_best(ident, false);
+            }
+            Message::ExportAttackLog { ident } => {
+                let Some((server, account)) = self.servers.get_ident(&ident)
+                else {
+                    return Command::none();
+                };
+                let Some(si) = &account.scrapbook_info else {
                     return Command::none();
                 };
-                if let Some(err) = error {
-                    pb.println(err)
+
+                let mut csv = String::from(
+                    "timestamp,target name,target level,won,items gained\n",
+                );
+                for (time, target, won, items_gained) in &si.attack_log {
+                    _ = csv.write_fmt(format_args!(
+                        "{},{},{},{},{}\n",
+                        time.format("%Y-%m-%d %H:%M:%S"),
+                        csv_escape(&target.info.name),
+                        target.info.level,
+                        if *won { "won" } else { "lost" },
+                        items_gained,
+                    ));
+                }
+
+                let file_name = format!(
+                    "attack_log_{}_{}.csv",
+                    account.name.to_lowercase(),
+                    server.ident.ident
+                );
+                match std::fs::write(&file_name, csv) {
+                    Ok(()) => info!("Exported attack log to {file_name}"),
+                    Err(e) => error!("Could not export attack log: {e}"),
                 }
-                self.servers.0.remove(&server_id);
-                pb.finish_and_clear();
-                return Command::perform(async {}, |_| {
-                    Message::NextCLICrawling
-                });
             }
-            Message::CopyBattleOrder { ident } => {
+            Message::ExportAccountSummary => {
+                let mut csv = String::from(
+                    "sso account,name,server,auto login,auto battle,\
+                     auto lure,auto tavern,auto expeditions,auto portal,\
+                     auto tower,auto dungeons,auto pets,mission strategy\n",
+                );
+                let mut rows = 0;
+                let chars =
+                    self.config.accounts.iter().flat_map(summary_characters);
+                for (sso, name, server, cfg) in chars {
+                    rows += 1;
+                    _ = writeln!(
+                        csv,
+                        "{},{},{},{},{},{},{},{},{},{},{},{},{}",
+                        csv_escape(sso),
+                        csv_escape(name),
+                        csv_escape(server),
+                        cfg.login,
+                        cfg.auto_battle,
+                        cfg.auto_lure,
+                        cfg.auto_tavern,
+                        cfg.auto_expeditions,
+                        cfg.auto_portal(),
+                        cfg.auto_tower(),
+                        cfg.auto_dungeons_other(),
+                        cfg.auto_pets,
+                        cfg.mission_strategy,
+                    );
+                }
+
+                let file_name = "account_summary.csv";
+                match std::fs::write(file_name, csv) {
+                    Ok(()) => {
+                        info!("Exported {rows} characters to {file_name}")
+                    }
+                    Err(e) => error!("Could not export account summary: {e}"),
+                }
+            }
+            Message::ExportScrapbook { ident } => {
                 let Some((server, account)) = self.servers.get_ident(&ident)
                 else {
                     return Command::none();
                 };
+                let Some(si) = &account.scrapbook_info else {
+                    return Command::none();
+                };
 
-                let CrawlingStatus::Crawling {
-                    player_info,
-                    equipment,
-                    que,
-                    ..
-                } = &server.crawling
+                let file_name =
+                    scrapbook_file_name(&account.name, &server.ident.ident);
+                let items: Vec<_> = si.scrapbook.items.iter().collect();
+                let res = serde_json::to_string(&items)
+                    .map_err(|e| e.to_string())
+                    .and_then(|json| {
+                        std::fs::write(&file_name, json)
+                            .map_err(|e| e.to_string())
+                    });
+                match res {
+                    Ok(()) => info!(
+                        "Exported {} scrapbook items to {file_name}",
+                        items.len()
+                    ),
+                    Err(e) => error!("Could not export scrapbook: {e}"),
+                }
+            }
+            Message::ImportScrapbook { ident } => {
+                let Some(server) = self.servers.get_mut(&ident.server_id)
                 else {
                     return Command::none();
                 };
-
-                let Some(si) = &account.scrapbook_info else {
+                let Some(account) = server.accounts.get_mut(&ident.account)
+                else {
                     return Command::none();
                 };
-
-                let mut best = si.best.first().cloned();
-                let mut scrapbook = si.scrapbook.items.clone();
-
-                let mut per_player_counts = calc_per_player_count(
-                    player_info, equipment, &scrapbook, si,
-                    self.config.blacklist_threshold,
-                );
-
-                let mut target_list = Vec::new();
-                let mut loop_count = 0;
-                let lock = que.lock().unwrap();
-                let invalid =
-                    lock.invalid_accounts.iter().map(|a| a.as_str()).collect();
-
-                while let Some(AttackTarget { missing, info }) = best {
-                    if loop_count > 300 || missing == 0 {
-                        break;
+                let Some(si) = &mut account.scrapbook_info else {
+                    return Command::none();
+                };
+                let file_name =
+                    scrapbook_file_name(&account.name, &server.ident.ident);
+
+                let values = match std::fs::read_to_string(&file_name)
+                    .map_err(|e| e.to_string())
+                    .and_then(|content| {
+                        serde_json::from_str::<Vec<serde_json::Value>>(&content)
+                            .map_err(|e| e.to_string())
+                    }) {
+                    Ok(values) => values,
+                    Err(e) => {
+                        error!("Could not import scrapbook {file_name}: {e}");
+                        return Command::none();
                     }
-                    loop_count += 1;
+                };
 
-                    for eq in &info.equipment {
-                        if scrapbook.contains(eq) {
-                            continue;
-                        }
-                        let Some(players) = equipment.get(eq) else {
-                            continue;
-                        };
-                        for player in players {
-                            let ppc =
-                                per_player_counts.entry(*player).or_insert(1);
-                            *ppc = ppc.saturating_sub(1);
+                let mut added = 0;
+                let mut unknown = 0;
+                for value in values {
+                    // Entries we can not understand are skipped instead of
+                    // failing the whole import
+                    match serde_json::from_value(value) {
+                        Ok(item) => {
+                            if si.scrapbook.items.insert(item) {
+                                added += 1;
+                            }
                         }
+                        Err(_) => unknown += 1,
                     }
-
-                    scrapbook.extend(info.equipment);
-                    target_list.push(info.name);
-                    let best_players =
-                        find_best(&per_player_counts, player_info, 1, &invalid);
-                    best = best_players.into_iter().next();
                 }
-                drop(lock);
-                return iced::clipboard::write(target_list.join("/"));
+                info!(
+                    "Imported {added} new scrapbook items from {file_name} \
+                     ({unknown} unknown entries skipped)"
+                );
+                return self.update_best(ident, false);
             }
             Message::PlayerRelogSuccess { ident, gs, session } => {
                 info!("Relogin success");
@@ -2359,6 +3210,7 @@ impl Helper {
                 else {
                     return Command::none();
                 };
+                player.last_successful_poll = Local::now();
 
                 let mut lock = player.status.lock().unwrap();
                 *lock = AccountStatus::Busy(gs, "Waiting".into());
@@ -2390,7 +3242,7 @@ impl Helper {
                 let Some(ud) = &account.underworld_info else {
                     return Command::none();
                 };
-                if ud.underworld.lured_today >= 5 {
+                if ud.underworld.lured_today >= MAX_LURES_PER_DAY {
                     return Command::none();
                 }
 
@@ -2469,10 +3321,18 @@ impl Helper {
                     last.has_player_won,
                 ));
 
+                if let Some(cfg) = self
+                    .config
+                    .get_char_conf_mut(&account.name, server.ident.id)
+                {
+                    cfg.stats.lures_done += 1;
+                }
+
                 if let Some(underworld) = s.underworld.as_ref() {
                     si.underworld = underworld.clone();
                 }
                 lock.put_session(session);
+                self.config_dirty = true;
             }
             Message::PlayerNotPolled { ident } => {
                 warn!("Unable to update {ident}")
@@ -2486,6 +3346,7 @@ impl Helper {
                 else {
                     return Command::none();
                 };
+                account.last_successful_poll = Local::now();
                 let mut lock = account.status.lock().unwrap();
                 let gs = match &mut *lock {
                     AccountStatus::Busy(gs, _) | AccountStatus::Idle(_, gs) => {
@@ -2496,6 +3357,56 @@ impl Helper {
                     }
                 };
 
+                {
+                    use sf_api::gamestate::tavern::{
+                        CurrentAction, ExpeditionStage,
+                    };
+                    let now = account.server_now();
+                    let arena =
+                        gs.arena.next_free_fight.is_none_or(|a| a <= now);
+                    let dungeon =
+                        gs.dungeons.next_free_fight.is_none_or(|a| a <= now);
+                    let expedition =
+                        matches!(
+                            gs.tavern.current_action,
+                            CurrentAction::Expedition
+                        ) && gs.tavern.expeditions.active().is_some_and(|a| {
+                            matches!(
+                                a.current_stage(),
+                                ExpeditionStage::Finished
+                            )
+                        });
+
+                    account.activity.record(
+                        ActivityKind::of(&gs.tavern.current_action),
+                        now,
+                    );
+
+                    let server_code = get_server_code(&server.ident.url);
+                    for (now_ready, was_ready, what) in [
+                        (arena, &mut account.arena_ready, "free arena fight"),
+                        (
+                            dungeon, &mut account.dungeon_ready,
+                            "free dungeon fight",
+                        ),
+                        (
+                            expedition, &mut account.expedition_ready,
+                            "expedition finished",
+                        ),
+                    ] {
+                        if now_ready
+                            && !*was_ready
+                            && self.config.notifications_enabled
+                        {
+                            self.pending_notifications.push(format!(
+                                "{} ({server_code}): {what}",
+                                account.name
+                            ));
+                        }
+                        *was_ready = now_ready;
+                    }
+                }
+
                 if let Some(cfg) = self
                     .config
                     .get_char_conf(&account.name, server.ident.id)
@@ -2521,13 +3432,37 @@ impl Helper {
                     }
                 }
 
+                if let Some(against) = account.lure_in_flight.take()
+                    && let Some(ui) = &mut account.underworld_info
+                    && let Some(last) = &gs.last_fight
+                {
+                    ui.attack_log.push((
+                        Local::now(),
+                        against,
+                        last.has_player_won,
+                    ));
+                    if let Some(cfg) = self
+                        .config
+                        .get_char_conf_mut(&account.name, server.ident.id)
+                    {
+                        cfg.stats.lures_done += 1;
+                        self.config_dirty = true;
+                    }
+                }
                 if let Some(sbi) = &mut account.underworld_info
                     && let Some(sb) = &gs.underworld
                 {
                     sbi.underworld = sb.clone();
                 }
+                let server_day = server_day(gs);
                 drop(lock);
 
+                let desc = account.pending_action.take().unwrap_or("updated");
+                account.record_action(desc);
+                if std::mem::take(&mut account.souls_collect_pending) {
+                    account.souls_collected = Some(account.server_now());
+                }
+
                 if let Some(cmd) = account.automation_queue.first().cloned() {
                     let mut status = account.status.lock().unwrap();
                     if let Some(mut session) = status.take_session("AutomationQueue") {
@@ -2542,6 +3477,13 @@ impl Helper {
                         let queued_cmd = cmd.clone();
                         let queued_cmd_for_log = queued_cmd.clone();
                         drop(status);
+                        if let Some(purpose) = mushroom_use(&queued_cmd) {
+                            account
+                                .record_mushrooms_spent(server_day, purpose, 1);
+                        }
+                        account.set_in_flight(&queued_cmd);
+                        account.pending_action =
+                            Some(describe_command(&queued_cmd));
 
                         return Command::perform(
                             async move {
@@ -2604,6 +3546,9 @@ impl Helper {
                         );
                     }
                 }
+                if let Some(attack) = self.fire_manual_attack(ident) {
+                    return attack;
+                }
             }
             Message::PlayerSetMaxUndergroundLvl { ident, lvl } => {
                 let Some(server) = self.servers.get_mut(&ident.server_id)
@@ -2618,24 +3563,323 @@ impl Helper {
                     return Command::none();
                 };
                 si.max_level = lvl;
+                if let Some(cfg) = self
+                    .config
+                    .get_char_conf_mut(&account.name, server.ident.id)
+                {
+                    cfg.underworld_max_level = Some(lvl);
+                    self.config_dirty = true;
+                }
                 return self.update_best(ident, false);
             }
-            Message::UpdateResult(should_update) => {
-                self.should_update = should_update;
+            Message::UpdateResult(version) => {
+                self.available_update = version;
+            }
+            Message::IgnoreUpdate => {
+                if let Some(version) = self.available_update.take() {
+                    self.config.ignored_update_version = Some(version);
+                    self.config_dirty = true;
+                }
             }
             Message::SetAutoPoll(new_val) => {
                 self.config.auto_poll = new_val;
-                _ = self.config.write();
+                self.config_dirty = true;
             }
             Message::AdvancedLevelRestrict(val) => {
                 self.config.show_crawling_restrict = val;
-                _ = self.config.write();
+                self.config_dirty = true;
+            }
+            Message::SetAutomationMinDelay(nv) => {
+                use crate::config::{
+                    AUTOMATION_DELAY_MAX_MS, AUTOMATION_DELAY_MIN_MS,
+                };
+                let nv =
+                    nv.clamp(AUTOMATION_DELAY_MIN_MS, AUTOMATION_DELAY_MAX_MS);
+                self.config.automation_min_delay_ms = nv;
+                self.config.automation_max_delay_ms =
+                    self.config.automation_max_delay_ms.max(nv);
+                self.config_dirty = true;
+            }
+            Message::SetAutomationMaxDelay(nv) => {
+                use crate::config::{
+                    AUTOMATION_DELAY_MAX_MS, AUTOMATION_DELAY_MIN_MS,
+                };
+                let nv =
+                    nv.clamp(AUTOMATION_DELAY_MIN_MS, AUTOMATION_DELAY_MAX_MS);
+                self.config.automation_max_delay_ms = nv;
+                self.config.automation_min_delay_ms =
+                    self.config.automation_min_delay_ms.min(nv);
+                self.config_dirty = true;
+            }
+            Message::SetLoginStagger(nv) => {
+                use crate::config::LOGIN_STAGGER_MAX_MS;
+                self.config.login_stagger_ms = nv.min(LOGIN_STAGGER_MAX_MS);
+                self.config_dirty = true;
+            }
+            Message::SetPollInterval(nv) => {
+                use crate::config::{
+                    POLL_INTERVAL_MAX_MS, POLL_INTERVAL_MIN_MS,
+                };
+                self.config.poll_interval_ms =
+                    nv.clamp(POLL_INTERVAL_MIN_MS, POLL_INTERVAL_MAX_MS);
+                self.config_dirty = true;
+            }
+            Message::SetMaxConcurrentLogins(nv) => {
+                let old = self.config.max_concurrent_logins();
+                self.config.max_concurrent_logins = nv;
+                self.config_dirty = true;
+                let new = self.config.max_concurrent_logins();
+                if new > old {
+                    self.login_permits.add_permits(new - old);
+                    return Command::none();
+                }
+                let excess = old - new;
+                let held = excess - self.login_permits.forget_permits(excess);
+                if held == 0 {
+                    return Command::none();
+                }
+                // Permits of running logins can only be taken away, once
+                // these logins are done
+                let permits = self.login_permits.clone();
+                return Command::perform(
+                    async move {
+                        if let Ok(p) =
+                            permits.acquire_many_owned(held as u32).await
+                        {
+                            p.forget();
+                        }
+                    },
+                    |_| Message::LoginPermitsShrunk,
+                );
+            }
+            Message::LoginPermitsShrunk => {
+                debug!("Lowered the amount of concurrent logins");
+            }
+            Message::SetAutomationQueueMax(nv) => {
+                self.config.automation_queue_max = nv;
+                self.config_dirty = true;
+            }
+            Message::SetNotifyCrawlFinished(val) => {
+                self.config.notify_crawl_finished = val;
+                self.config_dirty = true;
+            }
+            Message::SetNotificationsEnabled(val) => {
+                self.config.notifications_enabled = val;
+                if !val {
+                    self.pending_notifications.clear();
+                }
+                self.config_dirty = true;
+            }
+            Message::SetConfirmLogout(val) => {
+                self.config.confirm_logout = val;
+                self.config_dirty = true;
+            }
+            Message::SetProxyUrl(val) => {
+                self.config.proxy_url = (!val.trim().is_empty()).then_some(val);
+                self.config_dirty = true;
+            }
+            Message::SetThousandsSeparator(val) => {
+                self.config.num_format = config::num_format_with(&val);
+                self.config.thousands_separator = val;
+                self.config_dirty = true;
+            }
+            Message::SetAutomationDryRun(val) => {
+                self.config.automation_dry_run = val;
+                self.config_dirty = true;
+            }
+            Message::SetRandomizeActionOrder(val) => {
+                self.config.randomize_action_order = val;
+                self.config_dirty = true;
+            }
+            Message::ArmLogout(val) => self.logout_armed = val,
+            Message::UiTick => {}
+            Message::SetUiRefresh(nv) => {
+                self.config.ui_refresh_ms =
+                    nv.clamp(UI_REFRESH_MIN_MS, UI_REFRESH_MAX_MS);
+                self.config_dirty = true;
+            }
+            Message::SetPollWatchdog(nv) => {
+                self.config.poll_watchdog_min = nv;
+                self.config_dirty = true;
+            }
+            Message::SetAutoLogoutIdle(nv) => {
+                self.config.auto_logout_idle_min = (nv > 0).then_some(nv);
+                self.config_dirty = true;
+            }
+            Message::IdleLogoutCheck => {
+                let Some(idle_min) = self.config.auto_logout_idle_min else {
+                    return Command::none();
+                };
+                let min_idle = chrono::TimeDelta::minutes(idle_min as i64);
+                let lead = chrono::TimeDelta::seconds(IDLE_RELOG_LEAD_SECS);
+                let now = Local::now();
+
+                let mut relogins = vec![];
+                for server in self.servers.0.values() {
+                    for acc in server.accounts.values() {
+                        // SSO characters can only log in again together
+                        // with their whole account
+                        if !matches!(acc.auth, PlayerAuth::Normal(_))
+                            || !acc.automation_queue.is_empty()
+                            || !acc.manual_attack_queue.is_empty()
+                            || acc.pending_action.is_some()
+                            || !self
+                                .config
+                                .char_enabled(&acc.name, server.ident.id)
+                        {
+                            continue;
+                        }
+                        let si = acc.scrapbook_info.as_ref();
+                        if si.is_some_and(|a| a.farming)
+                            || acc
+                                .underworld_info
+                                .as_ref()
+                                .is_some_and(|a| a.auto_lure)
+                        {
+                            continue;
+                        }
+
+                        let mut lock = acc.status.lock().unwrap();
+                        let AccountStatus::Idle(_, gs) = &*lock else {
+                            continue;
+                        };
+                        let (due_now, mut next_due) =
+                            automation_timers(gs, now);
+                        if si.is_some_and(|a| a.auto_battle) {
+                            // No timer means, that the fight is free now
+                            let arena = gs.arena.next_free_fight.unwrap_or(now);
+                            next_due =
+                                Some(next_due.map_or(arena, |a| a.min(arena)));
+                        }
+                        let Some(next_due) = next_due else {
+                            continue;
+                        };
+                        if due_now || next_due - now < min_idle {
+                            continue;
+                        }
+
+                        let until = next_due - lead;
+                        info!(
+                            "Logging out {} until {}, because it has nothing \
+                             to do",
+                            acc.ident,
+                            until.format("%H:%M")
+                        );
+                        *lock = AccountStatus::LoggedOutIdle { until };
+                        let wait = (until - now).to_std().unwrap_or_default();
+                        relogins.push((acc.ident, wait));
+                    }
+                }
+                let commands = relogins
+                    .into_iter()
+                    .map(|(ident, wait)| self.schedule_relogin(ident, wait))
+                    .collect::<Vec<_>>();
+                return Command::batch(commands);
+            }
+            Message::PollWatchdog => {
+                if self.config.poll_watchdog_min == 0 {
+                    return Command::none();
+                }
+                let timeout = chrono::TimeDelta::minutes(
+                    self.config.poll_watchdog_min as i64,
+                );
+                let now = Local::now();
+
+                let mut stuck_idle = vec![];
+                let mut stuck_busy = vec![];
+                for server in self.servers.0.values_mut() {
+                    for acc in server.accounts.values_mut() {
+                        // Parked accounts are not polled at all, so their
+                        // timeout only starts, once they are enabled again
+                        if !self.config.char_enabled(&acc.name, server.ident.id)
+                        {
+                            acc.last_successful_poll = now;
+                            continue;
+                        }
+                        if now - acc.last_successful_poll < timeout {
+                            continue;
+                        }
+                        let mut lock = acc.status.lock().unwrap();
+                        if let Some(session) = lock.take_session("Watchdog") {
+                            stuck_idle.push((acc.ident, session));
+                        } else if matches!(&*lock, AccountStatus::Busy(..)) {
+                            // Whatever holds the session will never give it
+                            // back, so this needs an entirely new login
+                            *lock = AccountStatus::FatalError(
+                                "No successful poll".to_string(),
+                            );
+                            stuck_busy.push(acc.ident);
+                        } else {
+                            continue;
+                        }
+                        drop(lock);
+                        warn!(
+                            "Watchdog: {} has not been polled since {}, \
+                             logging in again",
+                            acc.ident,
+                            acc.last_successful_poll.format("%H:%M:%S")
+                        );
+                        acc.last_successful_poll = now;
+                    }
+                }
+
+                let mut commands = vec![];
+                for (ident, session) in stuck_idle {
+                    commands.push(self.handle_msg(
+                        Message::PlayerCommandFailed {
+                            ident,
+                            session,
+                            attempt: 0,
+                        },
+                    ));
+                }
+                for ident in stuck_busy {
+                    commands.push(self.retry_login(ident));
+                }
+                return Command::batch(commands);
+            }
+            Message::FlushConfig => {
+                if self.config_dirty {
+                    self.config_dirty = false;
+                    _ = self.config.write();
+                }
+            }
+            Message::FlushNotifications => {
+                let pending = std::mem::take(&mut self.pending_notifications);
+                let summary = match pending.len() {
+                    0 => return Command::none(),
+                    1 => "Account ready".to_string(),
+                    n => format!("{n} accounts ready"),
+                };
+                if let Err(e) = notify_rust::Notification::new()
+                    .appname("SF Assistant")
+                    .summary(&summary)
+                    .body(&pending.join("\n"))
+                    .show()
+                {
+                    warn!("Could not show notification: {e}");
+                }
+            }
+            Message::SetCrawlPageShare(val) => {
+                let val = val.min(100);
+                self.config.crawl_page_share = val;
+                self.crawl_page_share
+                    .store(val, std::sync::atomic::Ordering::Relaxed);
+                self.config_dirty = true;
+            }
+            Message::SetAutomationPaused(val) => {
+                self.config.automation_paused = val;
+                self.automation_paused
+                    .store(val, std::sync::atomic::Ordering::Relaxed);
+                self.config_dirty = true;
             }
             Message::CrawlerSetMinMax { server, min, max } => {
                 let Some(server) = self.servers.get_mut(&server) else {
                     return Command::none();
                 };
-                if let CrawlingStatus::Crawling { que, .. } = &server.crawling {
+                if let CrawlingStatus::Crawling { que, finished, .. } =
+                    &server.crawling
+                {
                     let mut que = que.lock().unwrap();
                     que.min_level = min.max(1);
                     que.max_level = max.max(min).min(9999);
@@ -2663,11 +3907,150 @@ impl Helper {
                         };
                         que.todo_accounts.append(&mut todo);
                     }
+
+                    // Accounts, that are back in the level range, restart a
+                    // finished crawler
+                    if finished.is_some() && !que.is_done() {
+                        drop(que);
+                        let threads =
+                            self.config.start_threads_for(server.ident.id);
+                        return server
+                            .set_threads(threads, &self.config.base_name);
+                    }
+                }
+            }
+            Message::CrawlerSetHardMinLevel { server, lvl } => {
+                let Some(server) = self.servers.get_mut(&server) else {
+                    return Command::none();
+                };
+                if let CrawlingStatus::Crawling { que, .. } = &server.crawling {
+                    let lvl = lvl.min(9999);
+                    let mut que = que.lock().unwrap();
+                    que.hard_min_level = lvl;
+                    debug!("Changed hard min level to {lvl}");
+                    // Skipped accounts below the floor would never be
+                    // restored anyways
+                    let keep = que.lvl_skipped_accounts.split_off(&lvl);
+                    que.lvl_skipped_accounts = keep;
+                }
+            }
+            Message::CrawlerRequeueInvalid { server, que_id } => {
+                let Some(server) = self.servers.get_mut(&server) else {
+                    return Command::none();
+                };
+                let CrawlingStatus::Crawling { que, threads, .. } =
+                    &server.crawling
+                else {
+                    return Command::none();
+                };
+                let mut lock = que.lock().unwrap();
+                if lock.que_id != que_id {
+                    return Command::none();
+                }
+                let mut invalid = std::mem::take(&mut lock.invalid_accounts);
+                invalid.retain(|a| !lock.todo_accounts.contains(a));
+                info!("Requeued {} invalid accounts", invalid.len());
+                lock.todo_accounts.append(&mut invalid);
+                drop(lock);
+
+                // An override of 0 pauses the crawling of this server
+                let restart_threads =
+                    self.config.start_threads_for(server.ident.id);
+                if *threads == 0 && restart_threads > 0 {
+                    return server
+                        .set_threads(restart_threads, &self.config.base_name);
+                }
+            }
+            Message::FetchNewestBackup { server, que_id } => {
+                let Some(server) = self.servers.get_mut(&server) else {
+                    return Command::none();
+                };
+                // The fetch replaces the local backup file
+                if server.fetching_backup || server.backups_in_flight > 0 {
+                    return Command::none();
+                }
+                server.fetching_backup = true;
+                let server_id = server.ident.id;
+                let server_ident = server.ident.ident.clone();
+                let dir = self.config.backup_dir.clone();
+                return Command::perform(
+                    async move { get_newest_backup(server_ident, true, dir).await },
+                    move |backup| Message::MergeBackup {
+                        server: server_id,
+                        que_id,
+                        backup,
+                    },
+                );
+            }
+            Message::MergeBackup {
+                server,
+                que_id,
+                backup,
+            } => {
+                let Some(server) = self.servers.get_mut(&server) else {
+                    return Command::none();
+                };
+                server.fetching_backup = false;
+                let Some(backup) = backup else {
+                    warn!("No backup found for {}", server.ident.ident);
+                    return Command::none();
+                };
+                let CrawlingStatus::Crawling {
+                    que_id: crawl_que_id,
+                    player_info,
+                    equipment,
+                    naked,
+                    ..
+                } = &mut server.crawling
+                else {
+                    return Command::none();
+                };
+                if *crawl_que_id != que_id {
+                    return Command::none();
+                }
+                // Locally crawled characters are at least as recent, as the
+                // ones in the backup
+                let mut added = 0;
+                for character in backup.characters {
+                    if player_info.contains_key(&character.uid) {
+                        continue;
+                    }
+                    handle_new_char_info(
+                        character, equipment, player_info, naked,
+                    );
+                    added += 1;
                 }
+                info!("Merged {added} characters into {}", server.ident.ident);
+
+                let server_id = server.ident.id;
+                let todo: Vec<_> =
+                    server.accounts.values().map(|a| a.ident).collect();
+                let mut commands: Vec<_> = todo
+                    .into_iter()
+                    .map(|acc| self.update_best(acc, false))
+                    .collect();
+                // Fetching the backup may have replaced the local file with
+                // the online HoF, which lacks the locally crawled characters
+                commands.push(Command::perform(async {}, move |_| {
+                    Message::SaveHoF(server_id)
+                }));
+                return Command::batch(commands);
             }
             Message::ShowClasses(val) => {
                 self.config.show_class_icons = val;
-                _ = self.config.write();
+                self.config_dirty = true;
+            }
+            Message::ShowResourceColumns(val) => {
+                self.config.show_resource_columns = val;
+                self.config_dirty = true;
+            }
+            Message::SetOverviewCompact(val) => {
+                self.config.overview_compact = val;
+                self.config_dirty = true;
+            }
+            Message::SetShowActivityTimeline(val) => {
+                self.config.show_activity_timeline = val;
+                self.config_dirty = true;
             }
             Message::NextCLICrawling => {
                 let Some(cli) = &mut self.cli_crawling else {
@@ -2686,6 +4069,7 @@ impl Helper {
                     return Command::none();
                 };
                 let threads = cli.threads;
+                let exit_on_error = cli.exit_on_error;
                 return match self.force_init_crawling(&url, threads, pb.clone())
                 {
                     Some(s) => s,
@@ -2694,6 +4078,9 @@ impl Helper {
                             "Could not init crawling on: {url}"
                         ));
                         pb.finish_and_clear();
+                        if exit_on_error {
+                            std::process::exit(1);
+                        }
                         return Command::perform(async {}, |_| {
                             Message::NextCLICrawling
                         });
@@ -2751,8 +4138,9 @@ impl Helper {
                 return self.login(account, false, PlayerAuth::SSO, true);
             }
             Message::SetOverviewSelected { ident, val } => {
-                let View::Overview { selected, action } =
-                    &mut self.current_view
+                let View::Overview {
+                    selected, action, ..
+                } = &mut self.current_view
                 else {
                     return Command::none();
                 };
@@ -2773,7 +4161,16 @@ impl Helper {
                     return Command::none();
                 };
                 config.login = nv;
-                _ = self.config.write();
+                self.config_dirty = true;
+            }
+            Message::ConfigSetEnabled { name, server, nv } => {
+                let Some(config) = self.config.get_char_conf_mut(&name, server)
+                else {
+                    return Command::none();
+                };
+                config.enabled = nv;
+                info!("{} {name}", if nv { "Enabled" } else { "Parked" });
+                self.config_dirty = true;
             }
             Message::ConfigSetAutoBattle { name, server, nv } => {
                 let Some(config) = self.config.get_char_conf_mut(&name, server)
@@ -2781,11 +4178,23 @@ impl Helper {
                     return Command::none();
                 };
                 config.auto_battle = nv;
-                _ = self.config.write();
+                self.config_dirty = true;
             }
             Message::SetBlacklistThr(nv) => {
                 self.config.blacklist_threshold = nv.max(1);
-                _ = self.config.write();
+                self.config_dirty = true;
+            }
+            Message::SetBattleLuckFactor(nv) => {
+                self.config.battle_luck_factor = nv;
+                self.config_dirty = true;
+            }
+            Message::SetDefaultCrawlOrder(nv) => {
+                self.config.default_crawl_order = nv;
+                self.config_dirty = true;
+            }
+            Message::SetAutoBattleFreshness(nv) => {
+                self.config.auto_battle_freshness = nv.clamp(0.0, 1.0);
+                self.config_dirty = true;
             }
             Message::AutoLureIdle => {}
             Message::AutoLurePossible { ident } => {
@@ -2803,16 +4212,22 @@ impl Helper {
                 let CrawlingStatus::Crawling { .. } = &server.crawling else {
                     return Command::none();
                 };
+                if !self.config.char_enabled(&account.name, ident.server_id) {
+                    return refetch;
+                }
 
                 let mut status = account.status.lock().unwrap();
                 let AccountStatus::Idle(_, gs) = &*status else {
                     return refetch;
                 };
 
-                let Some(0..=4) = gs.underworld.as_ref().map(|a| a.lured_today)
-                else {
+                if !gs
+                    .underworld
+                    .as_ref()
+                    .is_some_and(|a| a.lured_today < MAX_LURES_PER_DAY)
+                {
                     return refetch;
-                };
+                }
 
                 let Some(mut session) = status.take_session("Luring") else {
                     return refetch;
@@ -2823,16 +4238,8 @@ impl Helper {
                     return refetch;
                 };
 
-                let total_len = ui.best.len();
-                let new_len = ui.best.iter().filter(|a| !a.is_old()).count();
-
-                if total_len == 0 || (new_len as f32 / total_len as f32) < 0.9 {
-                    status.put_session(session);
-                    return refetch;
-                }
-
                 let Some(target) =
-                    ui.best.iter().find(|a| !a.is_old()).cloned()
+                    ui.lure_target(self.config.battle_freshness()).cloned()
                 else {
                     status.put_session(session);
                     return refetch;
@@ -2867,13 +4274,41 @@ impl Helper {
 
                 return Command::batch([refetch, fight]);
             }
+            Message::NotesEdited { ident, action } => {
+                let Some((editor_ident, content)) = &mut self.notes_editor
+                else {
+                    return Command::none();
+                };
+                if *editor_ident != ident {
+                    return Command::none();
+                }
+                let is_edit = action.is_edit();
+                content.perform(action);
+                if !is_edit {
+                    return Command::none();
+                }
+                let Some((server, account)) = self.servers.get_ident(&ident)
+                else {
+                    return Command::none();
+                };
+                let Some(config) = self
+                    .config
+                    .get_char_conf_mut(&account.name, server.ident.id)
+                else {
+                    return Command::none();
+                };
+                // The editor always ends the text with a line break
+                config.notes =
+                    content.text().trim_end_matches('\n').to_string();
+                self.config_dirty = true;
+            }
             Message::ConfigSetAutoLure { name, server, nv } => {
                 let Some(config) = self.config.get_char_conf_mut(&name, server)
                 else {
                     return Command::none();
                 };
                 config.auto_lure = nv;
-                _ = self.config.write();
+                self.config_dirty = true;
             }
 
             // NEW automation config handlers
@@ -2882,91 +4317,269 @@ impl Helper {
                     return Command::none();
                 };
                 cfg.auto_tavern = nv;
-                _ = self.config.write();
+                self.config_dirty = true;
             }
             Message::ConfigSetAutoExpeditions { name, server, nv } => {
                 let Some(cfg) = self.config.get_char_conf_mut(&name, server) else {
                     return Command::none();
                 };
                 cfg.auto_expeditions = nv;
-                _ = self.config.write();
+                self.config_dirty = true;
             }
             Message::ConfigSetAutoDungeons { name, server, nv } => {
                 let Some(cfg) = self.config.get_char_conf_mut(&name, server) else {
                     return Command::none();
                 };
-                cfg.auto_dungeons = nv;
-                _ = self.config.write();
+                cfg.set_all_dungeons(nv);
+                self.config_dirty = true;
+            }
+            Message::ConfigSetAutoPortal { name, server, nv } => {
+                let Some(cfg) = self.config.get_char_conf_mut(&name, server) else {
+                    return Command::none();
+                };
+                cfg.auto_portal = Some(nv);
+                self.config_dirty = true;
+            }
+            Message::ConfigSetAutoTower { name, server, nv } => {
+                let Some(cfg) = self.config.get_char_conf_mut(&name, server) else {
+                    return Command::none();
+                };
+                cfg.auto_tower = Some(nv);
+                self.config_dirty = true;
+            }
+            Message::ConfigSetAutoDungeonsOther { name, server, nv } => {
+                let Some(cfg) = self.config.get_char_conf_mut(&name, server) else {
+                    return Command::none();
+                };
+                cfg.auto_dungeons_other = Some(nv);
+                self.config_dirty = true;
             }
             Message::ConfigSetAutoPets { name, server, nv } => {
                 let Some(cfg) = self.config.get_char_conf_mut(&name, server) else {
                     return Command::none();
                 };
                 cfg.auto_pets = nv;
-                _ = self.config.write();
+                self.config_dirty = true;
+            }
+            Message::ConfigSetPetsSkipUnwinnable { name, server, nv } => {
+                let Some(cfg) = self.config.get_char_conf_mut(&name, server) else {
+                    return Command::none();
+                };
+                cfg.pets_skip_unwinnable = nv;
+                self.config_dirty = true;
+            }
+            Message::ConfigSetPetsPvpMinLevel { name, server, nv } => {
+                let Some(cfg) = self.config.get_char_conf_mut(&name, server) else {
+                    return Command::none();
+                };
+                cfg.pets_pvp_min_level = nv.clamp(1, PET_MAX_LEVEL);
+                self.config_dirty = true;
             }
             Message::ConfigSetAutoGuild { name, server, nv } => {
                 let Some(cfg) = self.config.get_char_conf_mut(&name, server) else {
                     return Command::none();
                 };
                 cfg.auto_guild = nv;
-                _ = self.config.write();
+                self.config_dirty = true;
+            }
+            Message::ConfigSetAutoWheel { name, server, nv } => {
+                let Some(cfg) = self.config.get_char_conf_mut(&name, server) else {
+                    return Command::none();
+                };
+                cfg.auto_wheel = nv;
+                self.config_dirty = true;
+            }
+            Message::ConfigSetAutoCalendar { name, server, nv } => {
+                let Some(cfg) = self.config.get_char_conf_mut(&name, server) else {
+                    return Command::none();
+                };
+                cfg.auto_calendar = nv;
+                self.config_dirty = true;
+            }
+            Message::ConfigSetAutoCollectTasks { name, server, nv } => {
+                let Some(cfg) = self.config.get_char_conf_mut(&name, server) else {
+                    return Command::none();
+                };
+                cfg.auto_collect_tasks = nv;
+                self.config_dirty = true;
+            }
+            Message::ConfigSetAutoSellJunk { name, server, nv } => {
+                let Some(cfg) = self.config.get_char_conf_mut(&name, server) else {
+                    return Command::none();
+                };
+                cfg.auto_sell_junk = nv;
+                self.config_dirty = true;
+            }
+            Message::ConfigSetSellJunkMaxAttributes { name, server, nv } => {
+                let Some(cfg) = self.config.get_char_conf_mut(&name, server) else {
+                    return Command::none();
+                };
+                cfg.sell_junk_max_attributes = nv.min(SELL_JUNK_ATTRIBUTES_LIMIT);
+                self.config_dirty = true;
+            }
+            Message::ConfigSetAutoUnderworldSouls { name, server, nv } => {
+                let Some(cfg) = self.config.get_char_conf_mut(&name, server) else {
+                    return Command::none();
+                };
+                cfg.auto_underworld_souls = nv;
+                self.config_dirty = true;
+            }
+            Message::ConfigSetAutoUnderworldUpgrade { name, server, nv } => {
+                let Some(cfg) = self.config.get_char_conf_mut(&name, server) else {
+                    return Command::none();
+                };
+                cfg.auto_underworld_upgrade = nv;
+                self.config_dirty = true;
+            }
+            Message::ConfigUnderworldUpgradeUp {
+                name,
+                server,
+                building,
+            } => {
+                let Some(cfg) = self.config.get_char_conf_mut(&name, server) else {
+                    return Command::none();
+                };
+                let mut order = cfg.underworld_upgrade_order();
+                if let Some(pos) = order.iter().position(|a| *a == building)
+                    && pos > 0
+                {
+                    order.swap(pos, pos - 1);
+                }
+                cfg.underworld_upgrade_order = order;
+                self.config_dirty = true;
             }
             Message::ConfigSetAutoGuildAcceptDefense { name, server, nv } => {
                 let Some(cfg) = self.config.get_char_conf_mut(&name, server) else {
                     return Command::none();
                 };
                 cfg.auto_guild_accept_defense = nv;
-                _ = self.config.write();
+                self.config_dirty = true;
             }
             Message::ConfigSetAutoGuildAcceptAttack { name, server, nv } => {
                 let Some(cfg) = self.config.get_char_conf_mut(&name, server) else {
                     return Command::none();
                 };
                 cfg.auto_guild_accept_attack = nv;
-                _ = self.config.write();
+                self.config_dirty = true;
             }
             Message::ConfigSetAutoGuildHydra { name, server, nv } => {
                 let Some(cfg) = self.config.get_char_conf_mut(&name, server) else {
                     return Command::none();
                 };
                 cfg.auto_guild_hydra = nv;
-                _ = self.config.write();
+                self.config_dirty = true;
+            }
+            Message::ConfigSetGuildHydraUseMushrooms { name, server, nv } => {
+                let Some(cfg) = self.config.get_char_conf_mut(&name, server) else {
+                    return Command::none();
+                };
+                cfg.guild_hydra_use_mushrooms = nv;
+                self.config_dirty = true;
             }
             Message::ConfigSetMissionStrategy { name, server, nv } => {
                 let Some(cfg) = self.config.get_char_conf_mut(&name, server) else {
                     return Command::none();
                 };
                 cfg.mission_strategy = nv;
-                _ = self.config.write();
+                self.config_dirty = true;
+            }
+            Message::ConfigSetDungeonTarget { name, server, nv } => {
+                let Some(cfg) = self.config.get_char_conf_mut(&name, server) else {
+                    return Command::none();
+                };
+                cfg.dungeon_target = nv;
+                self.config_dirty = true;
+            }
+            Message::ConfigSetQuestingPreference { name, server, nv } => {
+                let Some(cfg) = self.config.get_char_conf_mut(&name, server) else {
+                    return Command::none();
+                };
+                cfg.questing_preference = nv;
+                self.config_dirty = true;
+            }
+            Message::ConfigSetAutomationWindow { name, server, nv } => {
+                let Some(cfg) = self.config.get_char_conf_mut(&name, server) else {
+                    return Command::none();
+                };
+                cfg.automation_window = nv;
+                self.config_dirty = true;
             }
             Message::ConfigSetAutoBuyBeerMushrooms { name, server, nv } => {
                 let Some(cfg) = self.config.get_char_conf_mut(&name, server) else {
                     return Command::none();
                 };
                 cfg.auto_buy_beer_mushrooms = nv;
-                _ = self.config.write();
+                self.config_dirty = true;
+            }
+            Message::ConfigSetQuestOverwriteInventory { name, server, nv } => {
+                let Some(cfg) = self.config.get_char_conf_mut(&name, server) else {
+                    return Command::none();
+                };
+                cfg.quest_overwrite_inventory = nv;
+                self.config_dirty = true;
             }
             Message::ConfigSetMaxMushroomsBeer { name, server, nv } => {
                 let Some(cfg) = self.config.get_char_conf_mut(&name, server) else {
                     return Command::none();
                 };
                 cfg.max_mushrooms_beer = nv;
-                _ = self.config.write();
+                self.config_dirty = true;
+            }
+            Message::ConfigSetCityGuardHours { name, server, nv } => {
+                let Some(cfg) = self.config.get_char_conf_mut(&name, server) else {
+                    return Command::none();
+                };
+                cfg.city_guard_hours = nv.clamp(1, CITY_GUARD_MAX_HOURS);
+                self.config_dirty = true;
+            }
+            Message::ConfigSetMinGuardThirstGap { name, server, nv } => {
+                let Some(cfg) = self.config.get_char_conf_mut(&name, server) else {
+                    return Command::none();
+                };
+                cfg.min_guard_thirst_gap_min = nv.min(GUARD_THIRST_GAP_MAX_MIN);
+                self.config_dirty = true;
             }
             Message::ConfigSetMaxMushroomsDungeonSkip { name, server, nv } => {
                 let Some(cfg) = self.config.get_char_conf_mut(&name, server) else {
                     return Command::none();
                 };
                 cfg.max_mushrooms_dungeon_skip = nv;
-                _ = self.config.write();
+                self.config_dirty = true;
             }
             Message::ConfigSetMaxMushroomsPetSkip { name, server, nv } => {
                 let Some(cfg) = self.config.get_char_conf_mut(&name, server) else {
                     return Command::none();
                 };
                 cfg.max_mushrooms_pet_skip = nv;
-                _ = self.config.write();
+                self.config_dirty = true;
+            }
+            Message::ConfigSetMaxMushroomsHydra { name, server, nv } => {
+                let Some(cfg) = self.config.get_char_conf_mut(&name, server) else {
+                    return Command::none();
+                };
+                cfg.max_mushrooms_hydra = nv;
+                self.config_dirty = true;
+            }
+            Message::ConfigSetMaxMushroomsArena { name, server, nv } => {
+                let Some(cfg) = self.config.get_char_conf_mut(&name, server) else {
+                    return Command::none();
+                };
+                cfg.max_mushrooms_arena = nv;
+                self.config_dirty = true;
+            }
+            Message::ConfigSetMaxMushroomsFarming { name, server, nv } => {
+                let Some(cfg) = self.config.get_char_conf_mut(&name, server) else {
+                    return Command::none();
+                };
+                cfg.max_mushrooms_farming = nv;
+                self.config_dirty = true;
+            }
+            Message::ConfigSetAutoBattleUseMushrooms { name, server, nv } => {
+                let Some(cfg) = self.config.get_char_conf_mut(&name, server) else {
+                    return Command::none();
+                };
+                cfg.auto_battle_use_mushrooms = nv;
+                self.config_dirty = true;
             }
 
             Message::AutoLure { ident, state } => {
@@ -2985,6 +4598,98 @@ impl Helper {
 
                 si.auto_lure = state;
             }
+            Message::CopyCharacterStatus { ident } => {
+                let Some((_, account)) = self.servers.get_ident(&ident) else {
+                    return Command::none();
+                };
+                let now = account.server_now();
+                let summary = match &*account.status.lock().unwrap() {
+                    AccountStatus::Idle(_, gs) | AccountStatus::Busy(gs, _) => {
+                        status_summary(gs, &self.config.num_format, now)
+                    }
+                    _ => return Command::none(),
+                };
+                return iced::clipboard::write(summary);
+            }
+            Message::SetConfigCopyTarget { ident, target, nv } => {
+                let Some(server) = self.servers.0.get_mut(&ident.server_id)
+                else {
+                    return Command::none();
+                };
+                let Some(account) = server.accounts.get_mut(&ident.account)
+                else {
+                    return Command::none();
+                };
+                if nv {
+                    account.config_copy_targets.insert(target);
+                } else {
+                    account.config_copy_targets.remove(&target);
+                }
+            }
+            Message::CopyCharConfigTo { from, to } => {
+                let from_server = ServerIdent::new(&from.server).id;
+                let Some(source) =
+                    self.config.get_char_conf(&from.name, from_server).cloned()
+                else {
+                    return Command::none();
+                };
+
+                let mut refresh = vec![];
+                for target in &to {
+                    let server_id = ServerIdent::new(&target.server).id;
+                    let Some(cfg) =
+                        self.config.get_char_conf_mut(&target.name, server_id)
+                    else {
+                        continue;
+                    };
+                    cfg.copy_settings_from(&source);
+                    let cfg = cfg.clone();
+
+                    // Logged in characters keep some settings outside of the
+                    // config, so these have to be updated as well
+                    let Some(server) = self.servers.get_mut(&server_id) else {
+                        continue;
+                    };
+                    let lower_name = target.name.to_lowercase();
+                    for acc in server.accounts.values_mut() {
+                        if acc.name.to_lowercase().trim() != lower_name.trim() {
+                            continue;
+                        }
+                        if let Some(si) = &mut acc.scrapbook_info {
+                            si.auto_battle = cfg.auto_battle;
+                            si.battle_ordering = cfg.battle_ordering;
+                        }
+                        if let Some(ui) = &mut acc.underworld_info {
+                            ui.auto_lure = cfg.auto_lure;
+                            ui.export_format = cfg.lure_export_format;
+                            ui.export_old = cfg.lure_export_old;
+                        }
+                        refresh.push(acc.ident);
+                    }
+                }
+
+                info!(
+                    "Copied the settings of {} to {} characters",
+                    from.name,
+                    to.len()
+                );
+                self.config_dirty = true;
+
+                if let Some(server) = self.servers.get_mut(&from_server) {
+                    let lower_name = from.name.to_lowercase();
+                    for acc in server.accounts.values_mut() {
+                        if acc.name.to_lowercase().trim() == lower_name.trim() {
+                            acc.config_copy_targets.clear();
+                        }
+                    }
+                }
+
+                let commands: Vec<_> = refresh
+                    .into_iter()
+                    .map(|a| self.update_best(a, false))
+                    .collect();
+                return Command::batch(commands);
+            }
             Message::CopyBestLures { ident } => {
                 let Some(server) = self.servers.0.get_mut(&ident.server_id)
                 else {
@@ -2999,26 +4704,166 @@ impl Helper {
                     return Command::none();
                 };
 
-                let mut res = format!(
-                    "Best lure targets on {}. Max Lvl = {}\n",
-                    server.ident.url, si.max_level
-                );
+                let mut res = match si.export_format {
+                    LureExportFormat::Human => format!(
+                        "Best lure targets on {}. Max Lvl = {}\n",
+                        server.ident.url, si.max_level
+                    ),
+                    LureExportFormat::Csv => "level,items,name\n".to_string(),
+                    LureExportFormat::Names => String::new(),
+                };
 
                 for a in &si.best {
-                    if a.is_old() {
+                    if a.is_old() && !si.export_old {
                         continue;
                     }
-                    _ = res.write_fmt(format_args!(
-                        "lvl: {:3}, items: {}, name: {}\n",
-                        a.level,
-                        a.equipment.len(),
-                        a.name,
-                    ));
+                    _ = match si.export_format {
+                        LureExportFormat::Human => res.write_fmt(format_args!(
+                            "lvl: {:3}, items: {}, name: {}\n",
+                            a.level,
+                            a.equipment.len(),
+                            a.name,
+                        )),
+                        LureExportFormat::Csv => res.write_fmt(format_args!(
+                            "{},{},{}\n",
+                            a.level,
+                            a.equipment.len(),
+                            csv_escape(&a.name),
+                        )),
+                        LureExportFormat::Names => {
+                            res.write_fmt(format_args!("{}\n", a.name))
+                        }
+                    };
                 }
 
                 return iced::clipboard::write(res);
             }
+            Message::Shortcut(shortcut) => {
+                let msg = match (shortcut, &self.current_view) {
+                    (Shortcut::Back, View::Account { .. }) => {
+                        Message::ViewOverview
+                    }
+                    (Shortcut::Back, View::Overview { .. }) => {
+                        Message::ViewLogin
+                    }
+                    (Shortcut::Back, View::Settings) if self.has_accounts() => {
+                        Message::ViewOverview
+                    }
+                    (Shortcut::Back, View::Settings) => Message::ViewLogin,
+                    (Shortcut::Back, View::Logs { .. }) => {
+                        Message::ViewSettings
+                    }
+                    (Shortcut::Back, View::Characters { .. }) => {
+                        Message::ViewOverview
+                    }
+                    (Shortcut::SubPage(page), View::Account { ident, .. }) => {
+                        Message::ViewSubPage {
+                            player: *ident,
+                            page,
+                        }
+                    }
+                    (
+                        Shortcut::FocusFilter,
+                        View::Overview { .. } | View::Characters { .. },
+                    ) => {
+                        return iced::widget::text_input::focus(
+                            iced::widget::text_input::Id::new(
+                                OVERVIEW_FILTER_ID,
+                            ),
+                        );
+                    }
+                    _ => return Command::none(),
+                };
+                return Command::perform(async {}, move |_| msg);
+            }
+            Message::OverviewFilterChanged(nv) => {
+                let View::Overview { filter, .. } = &mut self.current_view
+                else {
+                    return Command::none();
+                };
+                *filter = nv;
+            }
+            Message::SetOverviewSort(nv) => {
+                let View::Overview {
+                    sort, sort_desc, ..
+                } = &mut self.current_view
+                else {
+                    return Command::none();
+                };
+                if *sort == nv {
+                    *sort_desc = !*sort_desc;
+                } else {
+                    *sort = nv;
+                    *sort_desc = false;
+                }
+            }
+            Message::ViewCharacters => {
+                self.current_view = View::Characters {
+                    filter: Default::default(),
+                    sort: Default::default(),
+                    sort_desc: false,
+                };
+            }
+            Message::CharactersFilterChanged(nv) => {
+                let View::Characters { filter, .. } = &mut self.current_view
+                else {
+                    return Command::none();
+                };
+                *filter = nv;
+            }
+            Message::SetCharactersSort(nv) => {
+                let View::Characters {
+                    sort, sort_desc, ..
+                } = &mut self.current_view
+                else {
+                    return Command::none();
+                };
+                if *sort == nv {
+                    *sort_desc = !*sort_desc;
+                } else {
+                    *sort = nv;
+                    *sort_desc = false;
+                }
+            }
+            Message::ExportCharacters => {
+                let View::Characters {
+                    filter,
+                    sort,
+                    sort_desc,
+                } = &self.current_view
+                else {
+                    return Command::none();
+                };
+                let rows =
+                    character_rows(&self.servers, filter, *sort, *sort_desc);
+                let opt =
+                    |a: Option<u64>| a.map_or(String::new(), |a| a.to_string());
+                let mut csv =
+                    String::from("server,name,level,class,gold,scrapbook\n");
+                for c in &rows {
+                    _ = writeln!(
+                        csv,
+                        "{},{},{},{},{},{}",
+                        csv_escape(&c.server),
+                        csv_escape(&c.name),
+                        opt(c.level.map(u64::from)),
+                        c.class_name(),
+                        opt(c.gold),
+                        opt(c.scrapbook.map(|a| a as u64)),
+                    );
+                }
+
+                let file_name = "characters.csv";
+                match std::fs::write(file_name, csv) {
+                    Ok(()) => info!(
+                        "Exported {} characters to {file_name}",
+                        rows.len()
+                    ),
+                    Err(e) => error!("Could not export characters: {e}"),
+                }
+            }
             Message::SetAction(a) => {
+                self.logout_armed = false;
                 let View::Overview { action, .. } = &mut self.current_view
                 else {
                     return Command::none();
@@ -3029,11 +4874,12 @@ impl Helper {
                 let View::Overview {
                     action: ac,
                     selected,
+                    ..
                 } = &mut self.current_view
                 else {
                     return Command::none();
                 };
-                let targets = match ac {
+                let targets: Vec<_> = match ac {
                     Some(ActionSelection::Multi) => {
                         selected.iter().copied().collect()
                     }
@@ -3043,16 +4889,20 @@ impl Helper {
 
                 *ac = None;
 
+                if let OverviewAction::CopyBattleOrder = action {
+                    return self.copy_battle_orders(&targets);
+                }
+
                 let messages = targets
                     .into_iter()
-                    .map(|a| match action {
-                        OverviewAction::Logout => {
-                            Message::RemoveAccount { ident: a }
-                        }
-                        OverviewAction::AutoBattle(nv) => Message::AutoBattle {
-                            ident: a,
-                            state: nv,
-                        },
+                    .filter_map(|a| {
+                        let (server, account) = self.servers.get_ident(&a)?;
+                        overview_action_message(
+                            action,
+                            a,
+                            account.name.clone(),
+                            server.ident.id,
+                        )
                     })
                     .map(|a| Command::perform(async {}, move |_| a));
 
@@ -3061,4 +4911,72 @@ impl Helper {
         }
         Command::none()
     }
-}
\ No newline at end of file
+}
+
+/// The message, that applies the overview action to a single character.
+/// Actions, that work on all selected characters at once, have none
+fn overview_action_message(
+    action: OverviewAction,
+    ident: AccountIdent,
+    name: String,
+    server: ServerID,
+) -> Option<Message> {
+    Some(match action {
+        OverviewAction::Logout => Message::RemoveAccount { ident },
+        OverviewAction::RetryLogin => Message::RetryLogin { ident },
+        OverviewAction::MissionStrategy(nv) => {
+            Message::ConfigSetMissionStrategy { name, server, nv }
+        }
+        OverviewAction::AutoTavern(nv) => {
+            Message::ConfigSetAutoTavern { name, server, nv }
+        }
+        OverviewAction::AutoExpeditions(nv) => {
+            Message::ConfigSetAutoExpeditions { name, server, nv }
+        }
+        OverviewAction::AutoDungeons(nv) => {
+            Message::ConfigSetAutoDungeons { name, server, nv }
+        }
+        OverviewAction::AutoPets(nv) => {
+            Message::ConfigSetAutoPets { name, server, nv }
+        }
+        OverviewAction::AutoBattle(nv) => {
+            Message::AutoBattle { ident, state: nv }
+        }
+        OverviewAction::CopyBattleOrder => return None,
+    })
+}
+
+/// The (sso account, name, server, config) of every character of the account.
+/// Regular accounts have no sso account name
+fn summary_characters(
+    acc: &AccountConfig,
+) -> Vec<(&str, &str, &str, &CharacterConfig)> {
+    match acc {
+        AccountConfig::Regular {
+            name,
+            server,
+            config,
+            ..
+        } => vec![("", name.as_str(), server.as_str(), config)],
+        AccountConfig::SF {
+            name, characters, ..
+        } => characters
+            .iter()
+            .map(|c| {
+                (name.as_str(), &*c.ident.name, &*c.ident.server, &c.config)
+            })
+            .collect(),
+    }
+}
+
+fn csv_escape(val: &str) -> String {
+    if val.contains([',', '"', '\n']) {
+        format!("\"{}\"", val.replace('"', "\"\""))
+    } else {
+        val.to_string()
+    }
+}
+
+fn scrapbook_file_name(name: &str, server: &str) -> String {
+    format!("scrapbook_{}_{server}.json", name.to_lowercase())
+}
@@ -1,20 +1,34 @@
 use std::{
-    sync::{Arc, Mutex},
+    collections::{HashMap, HashSet},
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicBool, Ordering},
+    },
     time::Duration,
 };
 
-use chrono::{DateTime, Local};
+use chrono::{DateTime, Local, NaiveDate, NaiveTime, TimeDelta};
 use log::trace;
 use nohash_hasher::IntMap;
+use num_format::{CustomFormat, ToFormattedString};
 use sf_api::{
-    gamestate::{GameState, underworld::Underworld, unlockables::ScrapBook},
+    gamestate::{
+        GameState,
+        tavern::CurrentAction,
+        underworld::Underworld,
+        unlockables::{EquipmentIdent, ScrapBook},
+    },
     session::Session,
 };
 use sf_api::command::Command as SFCommand;
 use tokio::time::sleep;
 
 use crate::{
-    AccountIdent, AttackTarget, CharacterInfo, config::CharacterConfig,
+    AccountIdent, AttackTarget, CharacterInfo,
+    config::{
+        BattleOrdering, CharacterConfig, LureExportFormat, SFCharIdent,
+        until_time_window,
+    },
     login::PlayerAuth, message::Message,
 };
 
@@ -27,6 +41,121 @@ pub struct AccountInfo {
     pub scrapbook_info: Option<ScrapbookInfo>,
     pub underworld_info: Option<UnderworldInfo>,
     pub automation_queue: Vec<SFCommand>,
+    /// Manual attacks, that were requested while the arena was on cooldown
+    /// without any mushrooms left, or the session was busy. Fired in order, once a free fight is ready
+    pub manual_attack_queue: Vec<AttackTarget>,
+    /// Mushrooms spent by automation on `mushrooms_spent_day`
+    pub mushrooms_spent_today: HashMap<MushroomUse, u32>,
+    /// The server day the mushroom counter belongs to
+    pub mushrooms_spent_day: Option<NaiveDate>,
+    // The last polled availability of these actions. Used to notify the user,
+    // when they become available
+    pub arena_ready: bool,
+    pub dungeon_ready: bool,
+    pub expedition_ready: bool,
+    /// How far the clock of the server is ahead of the local one, measured
+    /// at login. This includes the time zone of the server
+    pub clock_offset: Option<TimeDelta>,
+    /// How far the clock of the server is ahead of the local one in UTC,
+    /// measured at login. See `login::measure_clock_skew`
+    pub server_skew: Option<TimeDelta>,
+    /// When the account last did something successfully
+    pub last_action: DateTime<Local>,
+    pub last_action_desc: String,
+    /// What the automation command currently in flight does. Becomes the
+    /// last action, once the response has been applied
+    pub pending_action: Option<&'static str>,
+    /// The SSO session of this character expired and is waiting to be
+    /// replaced by a new SSO login
+    pub needs_reauth: bool,
+    /// When automation last collected the underworld souls
+    pub souls_collected: Option<DateTime<Local>>,
+    /// The command in flight collects the underworld souls
+    pub souls_collect_pending: bool,
+    /// The name of the player, that the command in flight lures into the
+    /// underworld
+    pub lure_in_flight: Option<String>,
+    /// The timer, that logs this account in again. Timers started before
+    /// are stale and ignored
+    pub relogin_timer: Option<u64>,
+    /// The quests, expeditions and guard shifts of today
+    pub activity: ActivityLog,
+    /// When the last auto poll (or login) of this account went through
+    pub last_successful_poll: DateTime<Local>,
+    /// The characters selected to receive a copy of this characters settings
+    pub config_copy_targets: HashSet<SFCharIdent>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ActivityKind {
+    Quest,
+    Expedition,
+    CityGuard,
+}
+
+impl ActivityKind {
+    pub fn of(action: &CurrentAction) -> Option<ActivityKind> {
+        match action {
+            CurrentAction::Quest { .. } => Some(ActivityKind::Quest),
+            CurrentAction::Expedition => Some(ActivityKind::Expedition),
+            CurrentAction::CityGuard { .. } => Some(ActivityKind::CityGuard),
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for ActivityKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            ActivityKind::Quest => "Quest",
+            ActivityKind::Expedition => "Expedition",
+            ActivityKind::CityGuard => "City guard",
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ActivitySegment {
+    pub kind: ActivityKind,
+    pub start: DateTime<Local>,
+    pub end: DateTime<Local>,
+}
+
+/// What the character did today. Built from the `CurrentAction` seen in
+/// each poll, so the times are only as exact as the poll interval
+#[derive(Debug, Default)]
+pub struct ActivityLog {
+    pub segments: Vec<ActivitySegment>,
+    /// Whether the last segment is still going on
+    ongoing: bool,
+}
+
+impl ActivityLog {
+    pub fn record(&mut self, kind: Option<ActivityKind>, now: DateTime<Local>) {
+        let today = now.date_naive();
+        self.segments.retain(|a| a.end.date_naive() == today);
+        if self.segments.is_empty() {
+            self.ongoing = false;
+        }
+
+        if self.ongoing
+            && let Some(last) = self.segments.last_mut()
+        {
+            last.end = now;
+            if Some(last.kind) == kind {
+                return;
+            }
+            self.ongoing = false;
+        }
+        if let Some(kind) = kind {
+            self.segments.push(ActivitySegment {
+                kind,
+                start: now,
+                end: now,
+            });
+            self.ongoing = true;
+        }
+    }
 }
 
 pub struct UnderworldInfo {
@@ -35,6 +164,8 @@ pub struct UnderworldInfo {
     pub max_level: u16,
     pub attack_log: Vec<(DateTime<Local>, String, bool)>,
     pub auto_lure: bool,
+    pub export_format: LureExportFormat,
+    pub export_old: bool,
 }
 
 impl UnderworldInfo {
@@ -53,46 +184,123 @@ impl UnderworldInfo {
         Some(Self {
             underworld,
             best: Default::default(),
-            max_level: avg_lvl as u16 + 20,
+            max_level: config
+                .and_then(|a| a.underworld_max_level)
+                .unwrap_or(avg_lvl as u16 + 20),
             attack_log: Vec::new(),
             auto_lure: config.map(|a| a.auto_lure).unwrap_or(false),
+            export_format: config
+                .map(|a| a.lure_export_format)
+                .unwrap_or_default(),
+            export_old: config.is_some_and(|a| a.lure_export_old),
         })
     }
+
+    /// The next player to lure, once enough of the targets are up to date.
+    /// `None`, if all lures of the day are done
+    pub fn lure_target(&self, freshness: f32) -> Option<&CharacterInfo> {
+        if self.underworld.lured_today >= MAX_LURES_PER_DAY {
+            return None;
+        }
+        let new_len = self.best.iter().filter(|a| !a.is_old()).count();
+        if !fresh_enough(new_len, self.best.len(), freshness) {
+            return None;
+        }
+        self.best.iter().find(|a| !a.is_old())
+    }
 }
 
+/// The loss count of players, that were put on the blacklist by hand. This is
+/// always above the blacklist threshold
+pub const MANUAL_BLACKLIST: usize = usize::MAX;
+
+/// The amount of manual attacks, that can wait for the arena at once
+pub const MANUAL_ATTACK_QUEUE_MAX: usize = 5;
+
+/// The amount of players, that can be lured into the underworld per day
+pub const MAX_LURES_PER_DAY: u16 = 5;
+
 pub struct ScrapbookInfo {
     pub scrapbook: ScrapBook,
     pub best: Vec<AttackTarget>,
     pub max_level: u16,
     pub max_attributes: u32,
+    /// uid => (name, lost fights). Manually added entries use
+    /// `MANUAL_BLACKLIST` as their count
     pub blacklist: IntMap<u32, (String, usize)>,
-    pub attack_log: Vec<(DateTime<Local>, AttackTarget, bool)>,
+    /// The contents of the "add to blacklist" input
+    pub blacklist_input: String,
+    /// The contents of the "fight player by name" input
+    pub fight_name_input: String,
+    /// (time, target, won, items added to the scrapbook by this fight)
+    pub attack_log: Vec<(DateTime<Local>, AttackTarget, bool, usize)>,
     pub auto_battle: bool,
+    pub battle_ordering: BattleOrdering,
+    /// Show the missing items instead of the best targets
+    pub show_missing: bool,
+    /// The missing items, that the fewest crawled players own, together with
+    /// the amount of owners. Only kept up to date while `show_missing` is set
+    pub rarest_missing: Vec<(EquipmentIdent, usize)>,
+    /// Keep attacking the best target with mushrooms, until a fight is lost,
+    /// or does not add anything to the scrapbook
+    pub farming: bool,
+    /// The mushrooms spent since farming was started
+    pub farming_spent: u32,
+    /// How often farming has waited for a busy session in a row
+    pub farming_busy_waits: u32,
 }
 
 impl ScrapbookInfo {
     pub fn new(
         gs: &GameState,
         config: Option<&CharacterConfig>,
+        battle_luck_factor: f32,
     ) -> Option<Self> {
-        let max_attributes = {
-            let base = gs.character.attribute_basis.as_array();
-            let bonus = gs.character.attribute_additions.as_array();
-            let total = base.iter().chain(bonus).sum::<u32>();
-            let expected_battle_luck = 1.2f32;
-            (total as f32 * expected_battle_luck) as u32
-        };
+        let max_attributes = config
+            .and_then(|a| a.scrapbook_max_attributes)
+            .unwrap_or_else(|| {
+                let base = gs.character.attribute_basis.as_array();
+                let bonus = gs.character.attribute_additions.as_array();
+                let total = base.iter().chain(bonus).sum::<u32>();
+                (total as f32 * battle_luck_factor) as u32
+            });
+        let max_level = config
+            .and_then(|a| a.scrapbook_max_level)
+            .unwrap_or(gs.character.level);
 
         Some(Self {
             scrapbook: gs.character.scrapbook.as_ref()?.clone(),
             best: Default::default(),
-            max_level: gs.character.level,
+            max_level,
             max_attributes,
             blacklist: Default::default(),
+            blacklist_input: Default::default(),
+            fight_name_input: Default::default(),
             attack_log: Default::default(),
             auto_battle: config.map(|a| a.auto_battle).unwrap_or(false),
+            battle_ordering: config
+                .map(|a| a.battle_ordering)
+                .unwrap_or_default(),
+            show_missing: false,
+            rarest_missing: Default::default(),
+            farming: false,
+            farming_spent: 0,
+            farming_busy_waits: 0,
         })
     }
+
+    /// Auto battle only fights, if enough of the targets have been fetched
+    /// today
+    pub fn has_fresh_targets(&self, freshness: f32) -> bool {
+        let new_len = self.best.iter().filter(|a| !a.is_old()).count();
+        fresh_enough(new_len, self.best.len(), freshness)
+    }
+}
+
+/// Whether at least the `freshness` fraction of the targets is up to date.
+/// Without any targets, there is nothing to attack
+pub fn fresh_enough(new_len: usize, total_len: usize, freshness: f32) -> bool {
+    total_len > 0 && (new_len as f32 / total_len as f32) >= freshness
 }
 
 impl AccountInfo {
@@ -110,16 +318,344 @@ impl AccountInfo {
             status: Arc::new(Mutex::new(AccountStatus::LoggingIn)),
             ident,
             automation_queue: Vec::new(),
+            manual_attack_queue: Vec::new(),
+            mushrooms_spent_today: HashMap::new(),
+            mushrooms_spent_day: None,
+            // Whatever is available right after the login is not news
+            arena_ready: true,
+            dungeon_ready: true,
+            expedition_ready: true,
+            clock_offset: None,
+            server_skew: None,
+            last_action: Local::now(),
+            last_action_desc: "logged in".to_string(),
+            pending_action: None,
+            needs_reauth: false,
+            souls_collected: None,
+            souls_collect_pending: false,
+            lure_in_flight: None,
+            relogin_timer: None,
+            last_successful_poll: Local::now(),
+            activity: ActivityLog::default(),
+            config_copy_targets: HashSet::new(),
+        }
+    }
+
+    /// Remembers what the automation command, that is about to be sent,
+    /// does, so that it can be accounted for once it went through
+    pub fn set_in_flight(&mut self, cmd: &SFCommand) {
+        self.souls_collect_pending =
+            matches!(cmd, SFCommand::UnderworldCollect { .. });
+        self.lure_in_flight = match cmd {
+            SFCommand::UnderworldAttack { player_id } => Some(
+                self.underworld_info
+                    .as_ref()
+                    .and_then(|a| a.best.iter().find(|b| b.uid == *player_id))
+                    .map_or_else(|| player_id.to_string(), |a| a.name.clone()),
+            ),
+            _ => None,
+        };
+    }
+
+    pub fn record_action(&mut self, desc: impl Into<String>) {
+        self.last_action = Local::now();
+        self.last_action_desc = desc.into();
+    }
+
+    /// How far the local clock is off. Unlike the clock offset, this does
+    /// not include the time zone of the server
+    pub fn clock_skew(&self) -> Option<TimeDelta> {
+        self.server_skew
+    }
+
+    /// The current time as the server sees it. Game timers have to be
+    /// compared against this, so that a local clock, that is off, does not
+    /// make automation act too early or too late
+    pub fn server_now(&self) -> DateTime<Local> {
+        Local::now() + self.clock_skew().unwrap_or_default()
+    }
+
+    /// The amount of mushrooms automation has spent on the given server day
+    /// for this purpose
+    pub fn mushrooms_spent_on(
+        &self,
+        day: NaiveDate,
+        purpose: MushroomUse,
+    ) -> u32 {
+        if self.mushrooms_spent_day != Some(day) {
+            return 0;
+        }
+        self.mushrooms_spent_today
+            .get(&purpose)
+            .copied()
+            .unwrap_or(0)
+    }
+
+    /// The amount of mushrooms spent from the shared pool on the given day
+    pub fn shared_mushrooms_spent_on(&self, day: NaiveDate) -> u32 {
+        MushroomUse::SHARED
+            .iter()
+            .map(|a| self.mushrooms_spent_on(day, *a))
+            .sum()
+    }
+
+    /// Whether automation may spend another mushroom on this purpose today.
+    /// Purposes with their own budget can not use the shared pool
+    pub fn can_spend_mushroom(
+        &self,
+        gs: &GameState,
+        cfg: &CharacterConfig,
+        purpose: MushroomUse,
+    ) -> bool {
+        let day = server_day(gs);
+        let within_budget = if purpose.is_shared() {
+            self.shared_mushrooms_spent_on(day) < cfg.mushroom_budget()
+        } else {
+            self.mushrooms_spent_on(day, purpose) < cfg.mushroom_cap(purpose)
+        };
+        within_budget && gs.character.mushrooms > 0
+    }
+
+    /// Queues a command, that could not be sent, because the session was
+    /// busy. Once the queue is longer than `max`, the oldest side-action is
+    /// dropped and returned. Tavern commands are only dropped, if nothing
+    /// else is left
+    pub fn queue_automation(
+        &mut self,
+        cmd: SFCommand,
+        max: usize,
+    ) -> Option<SFCommand> {
+        self.automation_queue.push(cmd);
+        if self.automation_queue.len() <= max {
+            return None;
+        }
+        let pos = self
+            .automation_queue
+            .iter()
+            .position(|a| !is_primary_command(a))
+            .unwrap_or(0);
+        Some(self.automation_queue.remove(pos))
+    }
+
+    pub fn record_mushrooms_spent(
+        &mut self,
+        day: NaiveDate,
+        purpose: MushroomUse,
+        amount: u32,
+    ) {
+        if amount == 0 {
+            return;
+        }
+        if self.mushrooms_spent_day != Some(day) {
+            self.mushrooms_spent_day = Some(day);
+            self.mushrooms_spent_today.clear();
+        }
+        *self.mushrooms_spent_today.entry(purpose).or_default() += amount;
+    }
+
+    /// Whether auto battle may pay for a fight, that is on cooldown, with a
+    /// mushroom. Checked before every fight, so `max_mushrooms_arena` is never
+    /// exceeded
+    pub fn can_fight_with_mushroom(
+        &self,
+        gs: &GameState,
+        cfg: &CharacterConfig,
+    ) -> bool {
+        cfg.auto_battle_use_mushrooms
+            && self.can_spend_mushroom(gs, cfg, MushroomUse::Arena)
+    }
+}
+
+/// The current day on the game server. Daily limits reset at server midnight,
+/// which does not have to match the local midnight
+pub fn server_day(gs: &GameState) -> NaiveDate {
+    gs.server_time().current().date()
+}
+
+/// The time until the next server day starts, which refills the thirst for
+/// adventure
+pub fn until_next_server_day(gs: &GameState) -> TimeDelta {
+    let now = gs.server_time().current();
+    now.date()
+        .succ_opt()
+        .and_then(|a| a.and_hms_opt(0, 0, 0))
+        .map_or(TimeDelta::zero(), |a| a - now)
+}
+
+/// What sending this command spends a mushroom on. `None`, if it is free
+pub fn mushroom_use(cmd: &SFCommand) -> Option<MushroomUse> {
+    Some(match cmd {
+        SFCommand::BuyBeer => MushroomUse::Beer,
+        SFCommand::FightTower { use_mush: true, .. }
+        | SFCommand::FightDungeon {
+            use_mushroom: true, ..
+        } => MushroomUse::DungeonSkip,
+        SFCommand::FightPetDungeon { use_mush: true, .. } => {
+            MushroomUse::PetSkip
+        }
+        SFCommand::Fight {
+            use_mushroom: true, ..
+        } => MushroomUse::Arena,
+        SFCommand::GuildPetBattle { use_mushroom: true } => MushroomUse::Hydra,
+        _ => return None,
+    })
+}
+
+/// What automation spends mushrooms on. Every purpose has a daily budget
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MushroomUse {
+    Beer,
+    DungeonSkip,
+    PetSkip,
+    Hydra,
+    Arena,
+    Farming,
+}
+
+impl MushroomUse {
+    /// The purposes, that spend from one shared pool, see `mushroom_budget`
+    pub const SHARED: [MushroomUse; 3] = [
+        MushroomUse::Beer,
+        MushroomUse::DungeonSkip,
+        MushroomUse::PetSkip,
+    ];
+
+    pub fn is_shared(self) -> bool {
+        Self::SHARED.contains(&self)
+    }
+}
+
+/// Whether the command belongs to the tavern, expeditions or the city guard.
+/// Only one of these can be queued at a time, side-actions are not primary
+pub fn is_primary_command(cmd: &SFCommand) -> bool {
+    matches!(
+        cmd,
+        // Tavern / Quests
+        SFCommand::StartQuest { .. }
+            | SFCommand::FinishQuest { .. }
+            | SFCommand::BuyBeer
+            | SFCommand::SetQuestsInsteadOfExpeditions { .. }
+            // Expeditions
+            | SFCommand::ExpeditionStart { .. }
+            | SFCommand::ExpeditionContinue
+            | SFCommand::ExpeditionPickEncounter { .. }
+            | SFCommand::ExpeditionPickReward { .. }
+            | SFCommand::ExpeditionSkipWait { .. }
+            // CityGuard (CityWatch)
+            | SFCommand::StartWork { .. }
+            | SFCommand::FinishWork
+    )
+}
+
+/// A short description of what the command does, as shown to the user
+pub fn describe_command(cmd: &SFCommand) -> &'static str {
+    match cmd {
+        SFCommand::Update => "updated",
+        SFCommand::BuyBeer => "bought beer",
+        SFCommand::StartQuest { .. } => "started quest",
+        SFCommand::FinishQuest { .. } => "finished quest",
+        SFCommand::StartWork { .. } => "started work",
+        SFCommand::FinishWork => "finished work",
+        SFCommand::SetQuestsInsteadOfExpeditions { .. } => "changed quest mode",
+        SFCommand::ExpeditionStart { .. } => "started expedition",
+        SFCommand::ExpeditionContinue => "continued expedition",
+        SFCommand::ExpeditionPickEncounter { .. } => "picked encounter",
+        SFCommand::ExpeditionPickReward { .. } => "picked reward",
+        SFCommand::ExpeditionSkipWait { .. } => "skipped expedition wait",
+        SFCommand::Fight { .. } => "fought in the arena",
+        SFCommand::FightDungeon { .. } => "fought dungeon",
+        SFCommand::FightTower { .. } => "fought tower",
+        SFCommand::FightPortal => "fought portal",
+        SFCommand::FightPetDungeon { .. } => "fought pet dungeon",
+        SFCommand::FightPetOpponent { .. } => "fought pet opponent",
+        SFCommand::GuildPetBattle { .. } => "fought hydra",
+        SFCommand::GuildJoinAttack => "joined guild attack",
+        SFCommand::GuildJoinDefense => "joined guild defense",
+        SFCommand::SpinWheelOfFortune { .. } => "spun the wheel",
+        SFCommand::CollectCalendar => "collected calendar",
+        SFCommand::CollectDailyQuestReward { .. }
+        | SFCommand::CollectEventTaskReward { .. } => "collected task reward",
+        SFCommand::SellShop { .. } => "sold junk item",
+        SFCommand::UnderworldCollect { .. } => "collected souls",
+        SFCommand::UnderworldUpgradeBuilding { .. } => {
+            "upgraded underworld building"
         }
+        _ => "sent a command",
     }
 }
 
+/// A plain text summary of the character, that can be shared without
+/// revealing anything about the login. `now` is `AccountInfo::server_now`
+pub fn status_summary(
+    gs: &GameState,
+    num_format: &CustomFormat,
+    now: DateTime<Local>,
+) -> String {
+    use sf_api::gamestate::tavern::CurrentAction;
+
+    use crate::automation::GameStateLike;
+
+    let timer = |time: Option<DateTime<Local>>| match time {
+        Some(t) if t > now => {
+            let secs = (t - now).num_seconds();
+            format!("in {}m {}s", secs / 60, secs % 60)
+        }
+        _ => "ready".to_string(),
+    };
+    let c = &gs.character;
+    let action = match &gs.tavern.current_action {
+        CurrentAction::Quest { busy_until, .. } => {
+            format!("Quest (done {})", timer(Some(*busy_until)))
+        }
+        CurrentAction::CityGuard { busy_until, .. } => {
+            format!("City guard (done {})", timer(Some(*busy_until)))
+        }
+        CurrentAction::Expedition => match gs.expedition_end_time() {
+            Some(end) => {
+                format!("Expedition (next stage {})", timer(Some(end)))
+            }
+            None => "Expedition".to_string(),
+        },
+        _ => "Idle".to_string(),
+    };
+    let scrapbook = c.scrapbook.as_ref().map_or("—".to_string(), |a| {
+        a.items.len().to_formatted_string(num_format)
+    });
+
+    [
+        format!("{} (lvl {})", c.name, c.level),
+        format!("Gold: {}", (c.silver / 100).to_formatted_string(num_format)),
+        format!("Mushrooms: {}", c.mushrooms),
+        format!("Scrapbook items: {scrapbook}"),
+        format!("Current action: {action}"),
+        format!("Next arena fight: {}", timer(gs.arena.next_free_fight)),
+        format!("Next dungeon fight: {}", timer(gs.dungeons.next_free_fight)),
+    ]
+    .join("\n")
+}
+
 pub enum AccountStatus {
     LoggingIn,
     Idle(Box<Session>, Box<GameState>),
     Busy(Box<GameState>, Box<str>),
     FatalError(String),
     LoggingInAgain,
+    /// A command failed and we are trying to log in again. `attempt` counts
+    /// the failed relogins so far
+    Relogging {
+        attempt: u64,
+    },
+    /// The login failed for a reason, that should go away by itself, like
+    /// maintenance. It is retried at `until`
+    WaitingToRetry {
+        until: DateTime<Local>,
+        error: String,
+    },
+    /// Logged out, because there was nothing to do for a while. Logs in
+    /// again at `until`, shortly before the next timer runs out
+    LoggedOutIdle {
+        until: DateTime<Local>,
+    },
 }
 
 impl AccountStatus {
@@ -149,6 +685,10 @@ impl AccountStatus {
 pub struct AutoAttackChecker {
     pub player_status: Arc<Mutex<AccountStatus>>,
     pub ident: AccountIdent,
+    /// Fights do not have to wait for the free fight
+    pub use_mushrooms: bool,
+    /// See `AccountInfo::clock_skew`
+    pub clock_skew: TimeDelta,
 }
 
 impl AutoAttackChecker {
@@ -161,8 +701,10 @@ impl AutoAttackChecker {
                 _ => None,
             }
         };
-        if let Some(next) = next_fight {
-            let remaining = next - Local::now();
+        if let Some(next) = next_fight
+            && !self.use_mushrooms
+        {
+            let remaining = next - (Local::now() + self.clock_skew);
             if let Ok(remaining) = remaining.to_std() {
                 tokio::time::sleep(remaining).await;
             }
@@ -189,14 +731,14 @@ impl AutoLureChecker {
                 _ => None,
             }
         };
-        let Some(0..=4) = lured else {
+        if !lured.is_some_and(|a| a < MAX_LURES_PER_DAY) {
             // Either no underworld, or already lured the max
             tokio::time::sleep(Duration::from_millis(fastrand::u64(
                 5000..=10_000,
             )))
             .await;
             return Message::AutoLureIdle;
-        };
+        }
 
         tokio::time::sleep(Duration::from_millis(fastrand::u64(3000..=5000)))
             .await;
@@ -208,11 +750,16 @@ impl AutoLureChecker {
 pub struct AutoPoll {
     pub player_status: Arc<Mutex<AccountStatus>>,
     pub ident: AccountIdent,
+    pub interval_ms: u64,
 }
 
 impl AutoPoll {
     pub async fn check(&self) -> Message {
-        sleep(Duration::from_millis(fastrand::u64(5000..=10000))).await;
+        let interval = self.interval_ms;
+        sleep(Duration::from_millis(fastrand::u64(
+            interval..=interval * 2,
+        )))
+        .await;
         let mut session = {
             let mut lock = self.player_status.lock().unwrap();
             let res = lock.take_session("Auto Poll");
@@ -254,18 +801,105 @@ impl AutoPoll {
     }
 }
 
+/// Whether an automated action is due right now and when the next one
+/// becomes due otherwise. Only covers the timers automation waits for
+pub fn automation_timers(
+    gs: &GameState,
+    now: DateTime<Local>,
+) -> (bool, Option<DateTime<Local>>) {
+    let mut next_due: Option<DateTime<Local>> = None;
+    let mut due_now = false;
+
+    use sf_api::gamestate::tavern::CurrentAction;
+    // Tavern: quest end or expedition waiting stage
+    match &gs.tavern.current_action {
+        CurrentAction::Quest { busy_until, .. } => {
+            if *busy_until > now { next_due = Some(*busy_until); } else { due_now = true; }
+        }
+        CurrentAction::Expedition => {
+            if let Some(active) = gs.tavern.expeditions.active() {
+                use sf_api::gamestate::tavern::ExpeditionStage;
+                if let ExpeditionStage::Waiting(until) = active.current_stage() {
+                    if until > now { next_due = Some(until); } else { due_now = true; }
+                }
+            }
+        }
+        _ => {}
+    }
+
+    // Pets: PvP and exploration cooldowns
+    if let Some(pets) = &gs.pets {
+        match pets.opponent.next_free_battle {
+            Some(t) => { if t > now { next_due = next_due.map_or(Some(t), |a| Some(a.min(t))); } else { due_now = true; } },
+            None => { due_now = true; }
+        }
+        match pets.next_free_exploration {
+            Some(t) => { if t > now { next_due = next_due.map_or(Some(t), |a| Some(a.min(t))); } else { due_now = true; } },
+            None => { due_now = true; }
+        }
+    }
+
+    // Dungeons: next free fight timer
+    match gs.dungeons.next_free_fight {
+        Some(t) => { if t > now { next_due = next_due.map_or(Some(t), |a| Some(a.min(t))); } else { due_now = true; } },
+        None => { due_now = true; }
+    }
+
+    // Guild: hydra next battle
+    if let Some(guild) = &gs.guild {
+        if let Some(t) = guild.hydra.next_battle { if t > now { next_due = next_due.map_or(Some(t), |a| Some(a.min(t))); } else { due_now = true; } }
+    }
+
+    (due_now, next_due)
+}
+
 pub struct AutoMissionsChecker {
     pub player_status: Arc<Mutex<AccountStatus>>,
     pub ident: AccountIdent,
     pub first_tick: bool,
+    pub paused: Arc<AtomicBool>,
+    pub delay_ms: std::ops::RangeInclusive<u64>,
+    pub first_tick_ms: std::ops::RangeInclusive<u64>,
+    pub pause_poll_ms: std::ops::RangeInclusive<u64>,
+    /// The delay before checking again, when no timer is known
+    pub retry_ms: std::ops::RangeInclusive<u64>,
+    /// The longest wait for a timer, before checking again
+    pub max_wait: Duration,
+    /// See `AccountInfo::clock_skew`
+    pub clock_skew: TimeDelta,
+    pub window: Option<(NaiveTime, NaiveTime)>,
 }
 
 impl AutoMissionsChecker {
+    fn jitter(&self) -> u64 {
+        fastrand::u64(self.delay_ms.clone())
+    }
+
     pub async fn check(&mut self) -> Message {
+        if self.paused.load(Ordering::Relaxed) {
+            log::debug!("AutoMissions {:?}: automation paused", self.ident);
+            // Check back every few seconds, so that unpausing resumes quickly
+            while self.paused.load(Ordering::Relaxed) {
+                let poll = fastrand::u64(self.pause_poll_ms.clone());
+                sleep(Duration::from_millis(poll)).await;
+            }
+            self.first_tick = true;
+        }
+        let until_window = until_time_window(self.window, Local::now().time());
+        if !until_window.is_zero() {
+            log::debug!(
+                "AutoMissions {:?}: outside of the automation window, waiting {:?}",
+                self.ident,
+                until_window
+            );
+            sleep(until_window).await;
+            self.first_tick = true;
+        }
         if self.first_tick {
             self.first_tick = false;
             log::debug!("AutoMissions {:?}: first tick, triggering soon", self.ident);
-            sleep(Duration::from_millis(fastrand::u64(200..=600))).await;
+            let delay = fastrand::u64(self.first_tick_ms.clone());
+            sleep(Duration::from_millis(delay)).await;
             return Message::RunAutomationTick { ident: self.ident };
         }
         let is_idle = {
@@ -273,68 +907,30 @@ impl AutoMissionsChecker {
             matches!(&*s, AccountStatus::Idle(_, _))
         };
         if !is_idle {
-            let backoff = fastrand::u64(1500..=3500);
+            // Whatever keeps the account busy is likely to take a few
+            // requests, so we back off a bit longer than usual
+            let backoff = fastrand::u64(
+                self.delay_ms.start() * 3..=self.delay_ms.end() * 3,
+            );
             log::debug!("AutoMissions {:?}: not idle, retry in {}ms", self.ident, backoff);
             sleep(Duration::from_millis(backoff)).await;
             return Message::RunAutomationTick { ident: self.ident };
         }
 
-        use chrono::Local;
-        let now = Local::now();
-        let mut next_due: Option<chrono::DateTime<Local>> = None;
-        let mut due_now = false;
-
-        if let AccountStatus::Idle(_, gs) = &*self.player_status.lock().unwrap() {
-            use sf_api::gamestate::tavern::CurrentAction;
-            // Tavern: quest end or expedition waiting stage
-            match &gs.tavern.current_action {
-                CurrentAction::Quest { busy_until, .. } => {
-                    if *busy_until > now { next_due = Some(*busy_until); } else { due_now = true; }
-                }
-                CurrentAction::Expedition => {
-                    if let Some(active) = gs.tavern.expeditions.active() {
-                        use sf_api::gamestate::tavern::ExpeditionStage;
-                        if let ExpeditionStage::Waiting(until) = active.current_stage() {
-                            if until > now { next_due = Some(until); } else { due_now = true; }
-                        }
-                    }
-                }
-                _ => {}
-            }
-
-            // Pets: PvP and exploration cooldowns
-            if let Some(pets) = &gs.pets {
-                match pets.opponent.next_free_battle {
-                    Some(t) => { if t > now { next_due = next_due.map_or(Some(t), |a| Some(a.min(t))); } else { due_now = true; } },
-                    None => { due_now = true; }
-                }
-                match pets.next_free_exploration {
-                    Some(t) => { if t > now { next_due = next_due.map_or(Some(t), |a| Some(a.min(t))); } else { due_now = true; } },
-                    None => { due_now = true; }
-                }
-            }
-
-            // Dungeons: next free fight timer
-            match gs.dungeons.next_free_fight {
-                Some(t) => { if t > now { next_due = next_due.map_or(Some(t), |a| Some(a.min(t))); } else { due_now = true; } },
-                None => { due_now = true; }
-            }
-
-            // Guild: hydra next battle
-            if let Some(guild) = &gs.guild {
-                if let Some(t) = guild.hydra.next_battle { if t > now { next_due = next_due.map_or(Some(t), |a| Some(a.min(t))); } else { due_now = true; } }
-            }
-        }
+        let now = Local::now() + self.clock_skew;
+        let (due_now, next_due) = match &*self.player_status.lock().unwrap() {
+            AccountStatus::Idle(_, gs) => automation_timers(gs, now),
+            _ => (false, None),
+        };
 
         if due_now {
-            let jitter = fastrand::u64(400..=1200);
+            let jitter = self.jitter();
             log::debug!("AutoMissions {:?}: one or more actions due now, jitter {}ms", self.ident, jitter);
             sleep(Duration::from_millis(jitter)).await;
         } else if let Some(t) = next_due {
             if t > now {
-                let max_interval = std::time::Duration::from_secs(120);
                 let wait_full = (t - now).to_std().unwrap_or_default();
-                let wait = if wait_full > max_interval { max_interval } else { wait_full };
+                let wait = wait_full.min(self.max_wait);
                 log::debug!(
                     "AutoMissions {:?}: next due at {}, waiting {:?}",
                     self.ident,
@@ -343,19 +939,43 @@ impl AutoMissionsChecker {
                 );
                 tokio::time::sleep(wait).await;
             } else {
-                let jitter = fastrand::u64(400..=1200);
+                let jitter = self.jitter();
                 log::debug!("AutoMissions {:?}: due now, jitter {}ms", self.ident, jitter);
                 sleep(Duration::from_millis(jitter)).await;
             }
         } else {
-            let backoff = fastrand::u64(30_000..=60_000);
+            let backoff = fastrand::u64(self.retry_ms.clone());
             log::debug!("AutoMissions {:?}: no timers found, retry in {}ms", self.ident, backoff);
             sleep(Duration::from_millis(backoff)).await;
         }
 
-        let jitter = fastrand::u64(300..=1200);
+        let jitter = self.jitter();
         log::trace!("AutoMissions {:?}: post-wait jitter {}ms", self.ident, jitter);
         sleep(Duration::from_millis(jitter)).await;
         Message::RunAutomationTick { ident: self.ident }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn no_targets_are_never_fresh() {
+        assert!(!fresh_enough(0, 0, 0.0));
+        assert!(!fresh_enough(0, 0, 0.5));
+    }
+
+    #[test]
+    fn old_targets_are_fresh_enough_without_a_requirement() {
+        assert!(fresh_enough(0, 10, 0.0));
+        assert!(!fresh_enough(0, 10, 0.1));
+    }
+
+    #[test]
+    fn freshness_is_a_fraction_of_all_targets() {
+        assert!(fresh_enough(5, 10, 0.5));
+        assert!(!fresh_enough(4, 10, 0.5));
+        assert!(fresh_enough(10, 10, 1.0));
+    }
+}
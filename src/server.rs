@@ -1,7 +1,8 @@
 use std::{
-    collections::{BTreeMap, HashMap, HashSet},
+    collections::{BTreeMap, HashMap, HashSet, VecDeque},
     hash::Hasher,
     sync::{Arc, Mutex},
+    time::{Duration, Instant},
 };
 
 use chrono::{DateTime, Local};
@@ -12,6 +13,7 @@ use sf_api::{
 
 use crate::{
     AccountID, AccountIdent, CharacterInfo, QueID, ServerID,
+    backup::RestoreProgress,
     crawler::{CrawlAction, CrawlerState, WorkerQue},
     player::AccountInfo,
 };
@@ -19,7 +21,12 @@ use crate::{
 #[derive(Debug, Clone)]
 pub enum CrawlingStatus {
     Waiting,
-    Restoring,
+    Restoring {
+        progress: Arc<RestoreProgress>,
+        /// The last progress reported to the UI
+        done: usize,
+        total: usize,
+    },
     CrawlingFailed(String),
     Crawling {
         que_id: QueID,
@@ -35,6 +42,11 @@ pub enum CrawlingStatus {
         last_update: DateTime<Local>,
         crawling_session: Option<Arc<CrawlerState>>,
         recent_failures: Vec<CrawlAction>,
+        /// The crawler threads keep running, but take nothing from the que
+        paused: bool,
+        /// When the que ran empty and the threads were stopped. Cleared, once
+        /// threads are started again
+        finished: Option<DateTime<Local>>,
     },
 }
 
@@ -44,6 +56,54 @@ pub struct ServerInfo {
     pub crawling: CrawlingStatus,
     pub connection: ServerConnection,
     pub headless_progress: Option<indicatif::ProgressBar>,
+    pub crawl_rate: CrawlRate,
+    /// HoF backups of this server, that are currently being written
+    pub backups_in_flight: usize,
+    /// A manually requested backup is being fetched
+    pub fetching_backup: bool,
+}
+
+/// Rolling window of (time, crawled characters) samples, used to estimate
+/// how fast a server is being crawled
+#[derive(Debug, Default)]
+pub struct CrawlRate {
+    samples: VecDeque<(Instant, usize)>,
+}
+
+impl CrawlRate {
+    const MAX_SAMPLES: usize = 30;
+    const MIN_SAMPLES: usize = 5;
+    const SAMPLE_INTERVAL: Duration = Duration::from_secs(1);
+
+    pub fn record(&mut self, crawled: usize) {
+        let now = Instant::now();
+        if let Some((last_time, last_crawled)) = self.samples.back() {
+            if crawled < *last_crawled {
+                // The crawler was restarted, so old samples are meaningless
+                self.samples.clear();
+            } else if now.duration_since(*last_time) < Self::SAMPLE_INTERVAL {
+                return;
+            }
+        }
+        self.samples.push_back((now, crawled));
+        if self.samples.len() > Self::MAX_SAMPLES {
+            self.samples.pop_front();
+        }
+    }
+
+    /// Characters crawled per second, if enough samples have been collected
+    pub fn per_sec(&self) -> Option<f64> {
+        if self.samples.len() < Self::MIN_SAMPLES {
+            return None;
+        }
+        let (start, start_count) = self.samples.front()?;
+        let (end, end_count) = self.samples.back()?;
+        let secs = end.duration_since(*start).as_secs_f64();
+        if secs <= 0.0 {
+            return None;
+        }
+        Some(end_count.saturating_sub(*start_count) as f64 / secs)
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
@@ -72,6 +132,14 @@ impl ServerIdent {
             ident,
         }
     }
+
+    /// The page the game of this server can be played on. Regular and SSO
+    /// characters use the same server URLs, which only lost their scheme
+    /// and slashes in `new()`
+    pub fn browser_url(&self) -> String {
+        let host = self.url.trim_start_matches("http:");
+        format!("https://{host}/")
+    }
 }
 
 #[derive(Default)]
@@ -90,6 +158,9 @@ impl Servers {
             crawling: CrawlingStatus::Waiting,
             connection,
             headless_progress: pb,
+            crawl_rate: CrawlRate::default(),
+            backups_in_flight: 0,
+            fetching_backup: false,
         })
     }
 
@@ -1,16 +1,20 @@
+use chrono::{Local, NaiveTime};
 use iced::{
     Alignment, Element, Length,
-    widget::{checkbox, column, text, row, pick_list, container, button, horizontal_space, slider},
+    widget::{checkbox, column, text, row, pick_list, container, button, horizontal_space, slider, progress_bar},
     theme,
 };
 
 use crate::{
-    config::{Config, MissionStrategy, ExpeditionRewardPriority},
+    config::{Config, DungeonTarget, MissionStrategy, ExpeditionRewardPriority, QuestingPreference, CITY_GUARD_MAX_HOURS, GUARD_THIRST_GAP_MAX_MIN, PET_MAX_LEVEL, SELL_JUNK_ATTRIBUTES_LIMIT},
     message::Message,
-    player::{AccountInfo, AccountStatus},
+    player::{AccountInfo, AccountStatus, MushroomUse, server_day},
     server::ServerInfo,
 };
 
+/// The amount of opponents, that have to be beaten to fully explore a habitat
+const HABITAT_OPPONENTS: u32 = 20;
+
 pub fn view_automation<'a>(
     player: &'a AccountInfo,
     og_server: &'a ServerInfo,
@@ -30,6 +34,27 @@ pub fn view_automation<'a>(
         AccountStatus::LoggingInAgain => {
             return text("Logging in again").size(20).into();
         }
+        AccountStatus::Relogging { attempt } => {
+            return text(format!("Logging in again (attempt {})", attempt + 1))
+                .size(20)
+                .into();
+        }
+        AccountStatus::WaitingToRetry { until, error } => {
+            return text(format!(
+                "Retrying login in {}: {error}",
+                super::remaining_minutes(*until, Local::now())
+            ))
+            .size(20)
+            .into();
+        }
+        AccountStatus::LoggedOutIdle { until } => {
+            return text(format!(
+                "Logged out while idle, logging in again in {}",
+                super::remaining_minutes(*until, Local::now())
+            ))
+            .size(20)
+            .into();
+        }
     };
 
     let config = config.get_char_conf(&player.name, og_server.ident.id);
@@ -40,13 +65,7 @@ pub fn view_automation<'a>(
             .into();
     };
 
-    let strategies = &[
-        MissionStrategy::Shortest,
-        MissionStrategy::MostGold,
-        MissionStrategy::BestGoldPerMinute,
-        MissionStrategy::BestXpPerMinute,
-        MissionStrategy::Smartest,
-    ];
+    let strategies = MissionStrategy::all();
 
     let header = row![
         text("Automation").size(24),
@@ -58,8 +77,7 @@ pub fn view_automation<'a>(
     ];
 
     // Live status
-    use chrono::Local;
-    let now = Local::now();
+    let now = player.server_now();
     let quest_status = match &gs.tavern.current_action {
         sf_api::gamestate::tavern::CurrentAction::Quest { busy_until, .. } => {
             let secs = (*busy_until - now).num_seconds().max(0);
@@ -97,6 +115,13 @@ pub fn view_automation<'a>(
         ]
         .spacing(24)
     );
+    left = left.push(
+        checkbox("Use mushrooms while the free fight is on cooldown", config.auto_battle_use_mushrooms).on_toggle(|nv| Message::ConfigSetAutoBattleUseMushrooms {
+            name: player.name.clone(),
+            server: og_server.ident.id,
+            nv,
+        }),
+    );
     left = left.push(
         row![
             checkbox("Tavern", config.auto_tavern).on_toggle(|nv| Message::ConfigSetAutoTavern {
@@ -116,11 +141,25 @@ pub fn view_automation<'a>(
     );
     left = left.push(
         row![
-            checkbox("Dungeons", config.auto_dungeons).on_toggle(|nv| Message::ConfigSetAutoDungeons {
+            checkbox("Portal", config.auto_portal()).on_toggle(|nv| Message::ConfigSetAutoPortal {
+                name: player.name.clone(),
+                server: og_server.ident.id,
+                nv,
+            }),
+            checkbox("Tower", config.auto_tower()).on_toggle(|nv| Message::ConfigSetAutoTower {
                 name: player.name.clone(),
                 server: og_server.ident.id,
                 nv,
             }),
+            checkbox("Dungeons", config.auto_dungeons_other()).on_toggle(|nv| Message::ConfigSetAutoDungeonsOther {
+                name: player.name.clone(),
+                server: og_server.ident.id,
+                nv,
+            }),
+        ].spacing(24)
+    );
+    left = left.push(
+        row![
             checkbox("Pets", config.auto_pets).on_toggle(|nv| Message::ConfigSetAutoPets {
                 name: player.name.clone(),
                 server: og_server.ident.id,
@@ -135,9 +174,49 @@ pub fn view_automation<'a>(
                 server: og_server.ident.id,
                 nv,
             }),
+            checkbox("Free wheel spin", config.auto_wheel).on_toggle(|nv| Message::ConfigSetAutoWheel {
+                name: player.name.clone(),
+                server: og_server.ident.id,
+                nv,
+            }),
+            checkbox("Calendar", config.auto_calendar).on_toggle(|nv| Message::ConfigSetAutoCalendar {
+                name: player.name.clone(),
+                server: og_server.ident.id,
+                nv,
+            }),
+            checkbox("Tasks", config.auto_collect_tasks).on_toggle(|nv| Message::ConfigSetAutoCollectTasks {
+                name: player.name.clone(),
+                server: og_server.ident.id,
+                nv,
+            }),
+            checkbox("Sell junk", config.auto_sell_junk).on_toggle(|nv| Message::ConfigSetAutoSellJunk {
+                name: player.name.clone(),
+                server: og_server.ident.id,
+                nv,
+            }),
         ]
         .spacing(24)
     );
+    if config.auto_sell_junk {
+        left = left.push(
+            container(
+                row![
+                    text("Only sell scrapbook duplicates with attributes up to"),
+                    slider(0..=SELL_JUNK_ATTRIBUTES_LIMIT, config.sell_junk_max_attributes, {
+                        let name = player.name.clone();
+                        let server = og_server.ident.id;
+                        move |nv| Message::ConfigSetSellJunkMaxAttributes { name: name.clone(), server, nv }
+                    })
+                    .step(10u32)
+                    .width(Length::Fixed(160.0)),
+                    text(config.sell_junk_max_attributes.to_string()),
+                ]
+                .spacing(12)
+                .align_items(Alignment::Center),
+            )
+            .padding(6),
+        );
+    }
     if config.auto_guild {
         left = left.push(
             column![
@@ -159,12 +238,40 @@ pub fn view_automation<'a>(
                         server: og_server.ident.id,
                         nv,
                     }),
-                ],
+                    checkbox("Use mushrooms for extra hydra fights", config.guild_hydra_use_mushrooms).on_toggle(|nv| Message::ConfigSetGuildHydraUseMushrooms {
+                        name: player.name.clone(),
+                        server: og_server.ident.id,
+                        nv,
+                    }),
+                ].spacing(24),
             ]
             .spacing(12)
             .padding(6)
         );
     }
+    if config.auto_pets {
+        let mut pet_options = row![
+            checkbox("Skip pet fights below level", config.pets_skip_unwinnable).on_toggle(|nv| Message::ConfigSetPetsSkipUnwinnable {
+                name: player.name.clone(),
+                server: og_server.ident.id,
+                nv,
+            }),
+        ]
+        .spacing(12)
+        .align_items(Alignment::Center);
+        if config.pets_skip_unwinnable {
+            pet_options = pet_options.push(
+                slider(1..=PET_MAX_LEVEL, config.pets_pvp_min_level, {
+                    let name = player.name.clone();
+                    let server = og_server.ident.id;
+                    move |nv| Message::ConfigSetPetsPvpMinLevel { name: name.clone(), server, nv }
+                })
+                .width(Length::Fixed(160.0)),
+            );
+            pet_options = pet_options.push(text(config.pets_pvp_min_level.to_string()));
+        }
+        left = left.push(container(pet_options).padding(6));
+    }
     left = left.push(text("Strategy").size(18));
     left = left.push(
         row![
@@ -183,6 +290,59 @@ pub fn view_automation<'a>(
         .spacing(12)
         .align_items(Alignment::Center),
     );
+    left = left.push(
+        row![
+            text("Dungeon target").width(Length::Fixed(160.0)),
+            pick_list(
+                DungeonTarget::all(),
+                Some(config.dungeon_target),
+                {
+                    let name = player.name.clone();
+                    let server = og_server.ident.id;
+                    move |nv| Message::ConfigSetDungeonTarget { name: name.clone(), server, nv }
+                }
+            )
+            .width(Length::Fixed(220.0))
+        ]
+        .spacing(12)
+        .align_items(Alignment::Center),
+    );
+    left = left.push(
+        row![
+            text("Quests / expeditions").width(Length::Fixed(160.0)),
+            pick_list(
+                QuestingPreference::all(),
+                Some(config.questing_preference),
+                {
+                    let name = player.name.clone();
+                    let server = og_server.ident.id;
+                    move |nv| Message::ConfigSetQuestingPreference { name: name.clone(), server, nv }
+                }
+            )
+            .width(Length::Fixed(220.0))
+        ]
+        .spacing(12)
+        .align_items(Alignment::Center),
+    );
+    // Time window, outside of which no automation runs
+    let window_set = move |nv| Message::ConfigSetAutomationWindow {
+        name: player.name.clone(),
+        server: og_server.ident.id,
+        nv,
+    };
+    let mut window_row = row![
+        checkbox("Only automate between", config.automation_window.is_some())
+            .on_toggle(move |nv| window_set(nv.then(default_window))),
+    ]
+    .spacing(12)
+    .align_items(Alignment::Center);
+    if let Some((start, end)) = config.automation_window {
+        window_row = window_row
+            .push(pick_list(day_times(), Some(DayTime(start)), move |nv: DayTime| window_set(Some((nv.0, end)))))
+            .push(text("and"))
+            .push(pick_list(day_times(), Some(DayTime(end)), move |nv: DayTime| window_set(Some((start, nv.0)))));
+    }
+    left = left.push(window_row);
     // Expedition options
     let exp_toggle_icon = iced_aw::core::icons::bootstrap::icon_to_text(
         if config.auto_expeditions { iced_aw::Bootstrap::LightningFill } else { iced_aw::Bootstrap::Lightning }
@@ -218,6 +378,7 @@ pub fn view_automation<'a>(
         ExpeditionRewardPriority::MushroomsGoldEggs,
         ExpeditionRewardPriority::GoldMushroomsEggs,
         ExpeditionRewardPriority::EggsMushroomsGold,
+        ExpeditionRewardPriority::ItemsMushroomsGold,
     ];
     left = left.push(
         row![
@@ -253,6 +414,45 @@ pub fn view_automation<'a>(
         ]
         .spacing(12)
     );
+    left = left.push(
+        checkbox("Overwrite items when the inventory is full", config.quest_overwrite_inventory).on_toggle(|nv| Message::ConfigSetQuestOverwriteInventory {
+            name: player.name.clone(),
+            server: og_server.ident.id,
+            nv,
+        }),
+    );
+    left = left.push(
+        row![
+            text("City guard hours").width(Length::Fixed(160.0)),
+            slider(1..=CITY_GUARD_MAX_HOURS, config.city_guard_hours(), {
+                let name = player.name.clone();
+                let server = og_server.ident.id;
+                move |nv| Message::ConfigSetCityGuardHours { name: name.clone(), server, nv }
+            })
+            .width(Length::Fixed(220.0)),
+            text(format!("{}h", config.city_guard_hours())),
+        ]
+        .spacing(12)
+        .align_items(Alignment::Center),
+    );
+    left = left.push(
+        row![
+            text("Guard if no thirst for").width(Length::Fixed(160.0)),
+            slider(0..=GUARD_THIRST_GAP_MAX_MIN, config.min_guard_thirst_gap_min, {
+                let name = player.name.clone();
+                let server = og_server.ident.id;
+                move |nv| Message::ConfigSetMinGuardThirstGap { name: name.clone(), server, nv }
+            })
+            .step(15u16)
+            .width(Length::Fixed(220.0)),
+            text(match config.min_guard_thirst_gap_min {
+                0 => "any time".to_string(),
+                min => format!("{}h {:02}m", min / 60, min % 60),
+            }),
+        ]
+        .spacing(12)
+        .align_items(Alignment::Center),
+    );
 
     // Mushroom budgets
     left = left.push(text("Mushroom budgets").size(18));
@@ -298,15 +498,56 @@ pub fn view_automation<'a>(
         .spacing(12)
         .align_items(Alignment::Center),
     );
+    left = left.push(
+        row![
+            text("Hydra fights").width(Length::Fixed(160.0)),
+            slider(0..=50, config.max_mushrooms_hydra, {
+                let name = player.name.clone();
+                let server = og_server.ident.id;
+                move |nv| Message::ConfigSetMaxMushroomsHydra { name: name.clone(), server, nv }
+            })
+            .width(Length::Fixed(220.0)),
+            text(config.max_mushrooms_hydra.to_string()),
+        ]
+        .spacing(12)
+        .align_items(Alignment::Center),
+    );
+    left = left.push(
+        row![
+            text("Arena fights").width(Length::Fixed(160.0)),
+            slider(0..=50, config.max_mushrooms_arena, {
+                let name = player.name.clone();
+                let server = og_server.ident.id;
+                move |nv| Message::ConfigSetMaxMushroomsArena { name: name.clone(), server, nv }
+            })
+            .width(Length::Fixed(220.0)),
+            text(config.max_mushrooms_arena.to_string()),
+        ]
+        .spacing(12)
+        .align_items(Alignment::Center),
+        row![
+            text("Arena farming").width(Length::Fixed(160.0)),
+            slider(0..=50, config.max_mushrooms_farming, {
+                let name = player.name.clone();
+                let server = og_server.ident.id;
+                move |nv| Message::ConfigSetMaxMushroomsFarming { name: name.clone(), server, nv }
+            })
+            .width(Length::Fixed(220.0)),
+            text(config.max_mushrooms_farming.to_string()),
+        ]
+        .spacing(12)
+        .align_items(Alignment::Center),
+    );
 
     // Right column: live info and timers
     let mut right = column![].spacing(12).width(Length::Fixed(520.0));
 
     let next_free = match gs.arena.next_free_fight {
-        Some(t) if t > chrono::Local::now() => {
-            let mins = (t - chrono::Local::now()).num_minutes();
-            text(format!("Next free fight in {}m", mins.max(0))).size(16)
-        }
+        Some(t) if t > now => text(format!(
+            "Next free fight in {}",
+            super::remaining_seconds(t, now)
+        ))
+        .size(16),
         _ => text("Free fight possible").size(16),
     };
     right = right.push(next_free);
@@ -316,11 +557,21 @@ pub fn view_automation<'a>(
         row![text("Tavern status:"), quest_status].spacing(12)
     );
 
+    let mush_budget = config.mushroom_budget();
+    let mush_spent = player.shared_mushrooms_spent_on(server_day(gs));
+    let hydra_budget = config.max_mushrooms_hydra;
+    let hydra_spent = player.mushrooms_spent_on(server_day(gs), MushroomUse::Hydra);
+    let arena_budget = config.max_mushrooms_arena;
+    let arena_spent = player.mushrooms_spent_on(server_day(gs), MushroomUse::Arena);
+
     right = right.push(text("Live timers").size(18));
     right = right.push(column![
         row![text("Thirst left:"), text(format!("{}s", thirst))].spacing(8),
         row![text("Beer today:"), text(format!("{}/{}", gs.tavern.beer_drunk, beer_cap))].spacing(8),
         row![text("Mushroom skip allowed:"), text(if gs.tavern.mushroom_skip_allowed { "Yes" } else { "No" })].spacing(8),
+        row![text("Mushroom budget left today:"), text(format!("{}/{}", mush_budget.saturating_sub(mush_spent), mush_budget))].spacing(8),
+        row![text("Hydra mushrooms left today:"), text(format!("{}/{}", hydra_budget.saturating_sub(hydra_spent), hydra_budget))].spacing(8),
+        row![text("Arena mushrooms left today:"), text(format!("{}/{}", arena_budget.saturating_sub(arena_spent), arena_budget))].spacing(8),
     ].spacing(6));
 
     // Pets timers
@@ -352,17 +603,35 @@ pub fn view_automation<'a>(
         for hab in HabitatType::iter() {
             let h = pets.habitats.get(hab);
             let best = h.pets.iter().max_by_key(|p| p.level).map(|p| p.level).unwrap_or(0);
-            let (state, enemy_pos) = match &h.exploration {
-                HabitatExploration::Exploring { fights_won, .. } => ("Exploring", *fights_won + 1),
-                _ => ("Idle/Unknown", 0),
+            let (state, fights_won) = match &h.exploration {
+                HabitatExploration::Exploring { fights_won, .. } => ("Exploring", Some(*fights_won)),
+                HabitatExploration::Finished => ("Explored", Some(HABITAT_OPPONENTS)),
+                _ => ("Idle/Unknown", None),
+            };
+            let progress: Element<Message> = match fights_won {
+                Some(won) if won >= HABITAT_OPPONENTS => {
+                    iced_aw::core::icons::bootstrap::icon_to_text(iced_aw::Bootstrap::CheckCircleFill).size(14.0).into()
+                }
+                Some(won) => row![
+                    progress_bar(0.0..=HABITAT_OPPONENTS as f32, won as f32)
+                        .width(Length::Fixed(80.0))
+                        .height(Length::Fixed(8.0)),
+                    text(format!("{won}/{HABITAT_OPPONENTS}")).size(12),
+                ]
+                .spacing(4)
+                .align_items(Alignment::Center)
+                .into(),
+                None => text("").into(),
             };
             pet_detail = pet_detail.push(
                 row![
                     text(format!("{:?}", hab)).width(Length::Fixed(100.0)),
                     text(format!("best lvl {}", best)).width(Length::Fixed(90.0)),
-                    text(format!("{}{}", state, if enemy_pos>0 { format!(" (enemy {})", enemy_pos) } else { String::new() })).width(Length::Fill),
+                    text(state).width(Length::Fixed(90.0)),
+                    progress,
                 ]
                 .spacing(8)
+                .align_items(Alignment::Center)
             );
         }
 
@@ -395,7 +664,6 @@ pub fn view_automation<'a>(
             lines = lines.push(text("No shadow dungeon open"));
         }
         if let Some(t) = gs.dungeons.next_free_fight {
-            let now = chrono::Local::now();
             let s = if t > now { let secs = (t - now).num_seconds().max(0); format!("Next dungeon fight in {}m {}s", secs/60, secs%60) } else { "Next dungeon fight: ready".into() };
             lines = lines.push(text(s));
         }
@@ -442,4 +710,30 @@ pub fn view_automation<'a>(
         .align_y(iced::alignment::Vertical::Top)
         .padding(20)
         .into()
-}
\ No newline at end of file
+}
+
+/// A time of day for the automation window, shown without seconds
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct DayTime(NaiveTime);
+
+impl std::fmt::Display for DayTime {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0.format("%H:%M"))
+    }
+}
+
+/// Every half hour of the day
+fn day_times() -> Vec<DayTime> {
+    (0..48)
+        .filter_map(|a| NaiveTime::from_hms_opt(a / 2, (a % 2) * 30, 0))
+        .map(DayTime)
+        .collect()
+}
+
+/// The window used when the time window is first enabled: overnight
+fn default_window() -> (NaiveTime, NaiveTime) {
+    (
+        NaiveTime::from_hms_opt(22, 0, 0).unwrap_or_default(),
+        NaiveTime::from_hms_opt(8, 0, 0).unwrap_or_default(),
+    )
+}
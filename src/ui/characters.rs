@@ -0,0 +1,227 @@
+use iced::{
+    Alignment, Element, Length,
+    alignment::Horizontal,
+    theme,
+    widget::{self, button, column, horizontal_space, row, text, text_input},
+};
+use num_format::ToFormattedString;
+use sf_api::gamestate::character::Class;
+
+use crate::{
+    AccountIdent, OVERVIEW_FILTER_ID, config::Config, get_server_code,
+    message::Message, player::AccountStatus, server::Servers, top_bar,
+};
+
+/// The column the character table is sorted by. Ties are broken by server
+/// and name
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CharacterSort {
+    #[default]
+    Server,
+    Name,
+    Level,
+    Class,
+    Gold,
+    Scrapbook,
+}
+
+/// One character of the flat table. Values are `None`, while the character
+/// is not logged in
+#[derive(Debug, Clone)]
+pub struct CharacterRow {
+    pub ident: AccountIdent,
+    pub server: String,
+    pub name: String,
+    pub level: Option<u16>,
+    pub class: Option<Class>,
+    /// Whole gold. Silver is dropped
+    pub gold: Option<u64>,
+    pub scrapbook: Option<usize>,
+}
+
+impl CharacterRow {
+    pub fn class_name(&self) -> String {
+        self.class.map(|a| format!("{a:?}")).unwrap_or_default()
+    }
+}
+
+/// Every character of every server, that matches the filter, sorted by the
+/// given column. The filter matches the name, server code and class
+pub fn character_rows(
+    servers: &Servers,
+    filter: &str,
+    sort: CharacterSort,
+    sort_desc: bool,
+) -> Vec<CharacterRow> {
+    let filter = filter.to_lowercase();
+    let mut rows: Vec<CharacterRow> = servers
+        .0
+        .values()
+        .flat_map(|server| {
+            server.accounts.values().map(|acc| {
+                let mut row = CharacterRow {
+                    ident: acc.ident,
+                    server: get_server_code(&server.ident.url),
+                    name: acc.name.clone(),
+                    level: None,
+                    class: None,
+                    gold: None,
+                    scrapbook: acc
+                        .scrapbook_info
+                        .as_ref()
+                        .map(|a| a.scrapbook.items.len()),
+                };
+                match &*acc.status.lock().unwrap() {
+                    AccountStatus::Idle(_, gs) | AccountStatus::Busy(gs, _) => {
+                        let c = &gs.character;
+                        row.level = Some(c.level);
+                        row.class = Some(c.class);
+                        // Silver is the smallest unit, 100 of which make up
+                        // one gold
+                        row.gold = Some(c.silver / 100);
+                    }
+                    _ => {}
+                }
+                row
+            })
+        })
+        .filter(|a| {
+            filter.is_empty()
+                || a.name.to_lowercase().contains(&filter)
+                || a.server.to_lowercase().contains(&filter)
+                || a.class_name().to_lowercase().contains(&filter)
+        })
+        .collect();
+
+    rows.sort_by(|a, b| {
+        let by_server = || a.server.cmp(&b.server);
+        let by_name = || a.name.cmp(&b.name);
+        let ord = match sort {
+            CharacterSort::Server => by_server(),
+            CharacterSort::Name => by_name(),
+            CharacterSort::Level => a.level.cmp(&b.level),
+            CharacterSort::Class => a.class_name().cmp(&b.class_name()),
+            CharacterSort::Gold => a.gold.cmp(&b.gold),
+            CharacterSort::Scrapbook => a.scrapbook.cmp(&b.scrapbook),
+        };
+        let ord = if sort_desc { ord.reverse() } else { ord };
+        ord.then_with(by_server).then_with(by_name)
+    });
+    rows
+}
+
+const SERVER_WIDTH: f32 = 60.0;
+const NAME_WIDTH: f32 = 200.0;
+const LEVEL_WIDTH: f32 = 60.0;
+const CLASS_WIDTH: f32 = 120.0;
+const GOLD_WIDTH: f32 = 120.0;
+const SCRAPBOOK_WIDTH: f32 = 80.0;
+
+pub fn view_characters<'a>(
+    servers: &'a Servers,
+    config: &'a Config,
+    filter: &str,
+    sort: CharacterSort,
+    sort_desc: bool,
+) -> Element<'a, Message> {
+    let top_bar = top_bar(
+        text("All characters").size(20).into(),
+        Some(Message::ViewOverview),
+    );
+
+    let filter_input = text_input("Filter by name, server or class", filter)
+        .id(text_input::Id::new(OVERVIEW_FILTER_ID))
+        .on_input(Message::CharactersFilterChanged)
+        .width(Length::Fixed(300.0));
+
+    let export = button("Export to CSV")
+        .on_press(Message::ExportCharacters)
+        .style(theme::Button::Secondary);
+
+    let controls = row!(filter_input, export)
+        .spacing(10)
+        .align_items(Alignment::Center);
+
+    let header = |label, width, this: CharacterSort| {
+        let mut content = row!(text(label)).align_items(Alignment::Center);
+        if this == sort {
+            content = content.push(
+                iced_aw::core::icons::bootstrap::icon_to_text(if sort_desc {
+                    iced_aw::Bootstrap::CaretDownFill
+                } else {
+                    iced_aw::Bootstrap::CaretUpFill
+                })
+                .size(12.0),
+            );
+        }
+        button(content)
+            .on_press(Message::SetCharactersSort(this))
+            .style(theme::Button::Text)
+            .padding(0.0)
+            .width(width)
+    };
+
+    let info_row = row!(
+        header("Server", SERVER_WIDTH, CharacterSort::Server),
+        header("Name", NAME_WIDTH, CharacterSort::Name),
+        horizontal_space(),
+        header("Level", LEVEL_WIDTH, CharacterSort::Level),
+        header("Class", CLASS_WIDTH, CharacterSort::Class),
+        header("Gold", GOLD_WIDTH, CharacterSort::Gold),
+        header("Scrapbook", SCRAPBOOK_WIDTH, CharacterSort::Scrapbook),
+    )
+    .spacing(10.0)
+    .width(Length::Fill)
+    .padding(5.0);
+
+    let mut table = column!(info_row)
+        .padding(20)
+        .spacing(5)
+        .width(Length::Fill)
+        .align_items(Alignment::Center);
+
+    let num = |val: Option<u64>, width| {
+        let val = val.map_or("—".to_string(), |a| {
+            a.to_formatted_string(&config.num_format)
+        });
+        text(val)
+            .width(width)
+            .horizontal_alignment(Horizontal::Center)
+    };
+
+    let rows = character_rows(servers, filter, sort, sort_desc);
+    if rows.is_empty() {
+        table = table.push(text("No character matches the filter"));
+    }
+    for c in rows {
+        let class = text(c.class_name())
+            .width(CLASS_WIDTH)
+            .horizontal_alignment(Horizontal::Center);
+        let info_row = row!(
+            text(&c.server).width(SERVER_WIDTH),
+            text(&c.name).width(NAME_WIDTH),
+            horizontal_space(),
+            num(c.level.map(u64::from), LEVEL_WIDTH),
+            class,
+            num(c.gold, GOLD_WIDTH),
+            num(c.scrapbook.map(|a| a as u64), SCRAPBOOK_WIDTH),
+        )
+        .spacing(10.0)
+        .align_items(Alignment::Center);
+
+        table = table.push(
+            button(info_row)
+                .on_press(Message::ShowPlayer { ident: c.ident })
+                .width(Length::Fill)
+                .padding(4.0)
+                .style(theme::Button::Secondary),
+        );
+    }
+
+    column!(top_bar, controls, widget::scrollable(table))
+        .spacing(5)
+        .height(Length::Fill)
+        .width(Length::Fill)
+        .align_items(Alignment::Center)
+        .into()
+}
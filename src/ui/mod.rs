@@ -7,21 +7,30 @@ use iced::{
     theme,
     widget::{
         self, Button, button, checkbox, column, container, horizontal_space,
-        pick_list, progress_bar, row, text,
+        pick_list, progress_bar, row, slider, text, text_input, tooltip,
     },
 };
 use iced_aw::{number_input, widgets::DropDown};
 use num_format::ToFormattedString;
 use options::view_options;
+use sf_api::gamestate::GameState;
 
 use self::{scrapbook::view_scrapbook, underworld::view_underworld, automation::view_automation};
 use crate::{
-    AccountIdent, AccountPage, ActionSelection, Helper, View,
-    config::{AvailableTheme, Config},
+    AccountIdent, AccountPage, ActionSelection, Helper, OVERVIEW_FILTER_ID,
+    View,
+    config::{
+        AUTOMATION_DELAY_MAX_MS, AUTOMATION_QUEUE_LIMIT, AvailableTheme,
+        Config, LOGIN_STAGGER_MAX_MS, MAX_CONCURRENT_LOGINS, MissionStrategy,
+        POLL_INTERVAL_MAX_MS, UI_REFRESH_MAX_MS,
+    },
     crawler::CrawlingOrder,
-    get_server_code,
+    get_server_code, logs,
     message::Message,
-    player::{AccountInfo, AccountStatus},
+    player::{
+        AccountInfo, AccountStatus, ActivityKind, ActivityLog,
+        MAX_LURES_PER_DAY,
+    },
     server::{CrawlingStatus, ServerInfo},
     top_bar,
 };
@@ -29,6 +38,7 @@ use crate::{
 mod options;
 mod scrapbook;
 mod automation;
+pub mod characters;
 pub mod underworld;
 
 impl Helper {
@@ -38,10 +48,24 @@ impl Helper {
             View::Login => self
                 .login_state
                 .view(&self.config.accounts, self.has_accounts()),
-            View::Overview { selected, action } => {
-                self.view_overview(selected, action)
+            View::Overview {
+                selected,
+                action,
+                filter,
+                sort,
+                sort_desc,
+            } => {
+                self.view_overview(selected, action, filter, *sort, *sort_desc)
             }
+            View::Characters {
+                filter,
+                sort,
+                sort_desc,
+            } => characters::view_characters(
+                &self.servers, &self.config, filter, *sort, *sort_desc,
+            ),
             View::Settings => self.view_settings(),
+            View::Logs { level } => self.view_logs(*level),
         };
         // Wrap entire content in a themed container so the palette background is visible
         let main_part = container(view)
@@ -58,18 +82,19 @@ impl Helper {
             });
         let mut res = column!();
 
-        if self.should_update {
+        if let Some(version) = &self.available_update {
             let dl_button =  button("Download").on_press(
                 Message::OpenLink("https://github.com/the-marenga/sf-scrapbook-helper/releases/latest".to_string())
             );
 
             let ignore_button = button("Ignore")
-                .on_press(Message::UpdateResult(false))
+                .on_press(Message::IgnoreUpdate)
                 .style(theme::Button::Destructive);
 
             let update_msg = row!(
                 horizontal_space(),
-                text("A new Version is available!").size(20),
+                text(format!("A new Version is available! (v{version})"))
+                    .size(20),
                 dl_button,
                 horizontal_space(),
                 ignore_button,
@@ -110,25 +135,97 @@ impl Helper {
                 .into()
         };
 
-        let top = row!(
+        let mut top = row!(
             text(&player.name).size(20),
             text(get_server_code(&server.ident.url))
                 .horizontal_alignment(iced::alignment::Horizontal::Right)
                 .size(20),
-            selection(AccountPage::Scrapbook),
-            selection(AccountPage::Underworld),
-            selection(AccountPage::Automation),
-            selection(AccountPage::Options),
-            button(text("Logout"))
-                .on_press(Message::RemoveAccount {
-                    ident: player.ident,
-                })
-                .padding(4)
-                .style(theme::Button::Destructive)
         )
         .spacing(15)
         .align_items(Alignment::Center);
 
+        if let Some(cc) =
+            self.config.get_char_conf(&player.name, server.ident.id)
+            && !cc.notes.is_empty()
+        {
+            // Only the first line fits into the top bar
+            let first_line = cc.notes.lines().next().unwrap_or_default();
+            top = top.push(text(first_line).size(13).style(
+                theme::Text::Color(iced::Color::from_rgb8(130, 130, 130)),
+            ));
+        }
+
+        let top = top.push(
+            text(format!(
+                "{}: {}",
+                time_ago(player.last_action),
+                player.last_action_desc
+            ))
+            .size(13),
+        );
+
+        let mut top = top
+            .push(selection(AccountPage::Scrapbook))
+            .push(selection(AccountPage::Underworld))
+            .push(selection(AccountPage::Automation))
+            .push(selection(AccountPage::Options));
+
+        if let AccountStatus::FatalError(err)
+        | AccountStatus::WaitingToRetry { error: err, .. } =
+            &*player.status.lock().unwrap()
+        {
+            let retry = if player.needs_reauth {
+                button(text("Re-authenticate")).on_press(Message::ReauthSSO {
+                    ident: player.ident,
+                })
+            } else {
+                button(text("Retry login")).on_press(Message::RetryLogin {
+                    ident: player.ident,
+                })
+            }
+            .padding(4);
+            top = top.push(
+                tooltip(retry, text(err), tooltip::Position::Bottom)
+                    .style(theme::Container::Box),
+            );
+        }
+
+        if let AccountStatus::LoggedOutIdle { .. } =
+            &*player.status.lock().unwrap()
+        {
+            top = top.push(
+                button(text("Log in now"))
+                    .on_press(Message::RetryLogin {
+                        ident: player.ident,
+                    })
+                    .padding(4),
+            );
+        }
+
+        let top = top.push(
+            button(text("Copy status"))
+                .on_press(Message::CopyCharacterStatus {
+                    ident: player.ident,
+                })
+                .padding(4),
+        );
+
+        let top = top.push(
+            button(text("Open game"))
+                .on_press(Message::OpenLink(server.ident.browser_url()))
+                .padding(4)
+                .style(theme::Button::Secondary),
+        );
+
+        let top = top.push(
+            button(text(self.logout_label()))
+                .on_press(self.logout_press(Message::RemoveAccount {
+                    ident: player.ident,
+                }))
+                .padding(4)
+                .style(theme::Button::Destructive),
+        );
+
         let top_bar = top_bar(top.into(), Some(Message::ViewOverview));
 
         let middle = match page {
@@ -143,12 +240,22 @@ impl Helper {
                 server,
                 &self.config,
             ),
-            AccountPage::Options => view_options(player, server, &self.config),
+            AccountPage::Options => view_options(
+                player,
+                server,
+                &self.config,
+                self.notes_editor.as_ref().map(|a| &a.1),
+            ),
         };
 
         let col_container = container(middle).center_y();
 
-        column!(top_bar, col_container)
+        let mut all = column!(top_bar);
+        if self.config.show_activity_timeline {
+            all = all.push(view_activity_timeline(&player.activity));
+        }
+
+        all.push(col_container)
             .spacing(5)
             .height(Length::Fill)
             .align_items(Alignment::Center)
@@ -165,17 +272,21 @@ impl Helper {
             },
         );
         use AvailableTheme::*;
-        let all_themes = [
+        let mut all_themes = vec![
             Light, Dark, Dracula, Nord, SolarizedLight, SolarizedDark,
             GruvboxLight, GruvboxDark, CatppuccinLatte, CatppuccinFrappe,
             CatppuccinMacchiato, CatppuccinMocha, TokyoNight, TokyoNightStorm,
             TokyoNightLight, KanagawaWave, KanagawaDragon, KanagawaLotus,
-            Moonfly, Nightfly, Oxocarbon, CharcoalOrange,
+            Moonfly, Nightfly, Oxocarbon, CharcoalOrange, TokyoNightMoon,
         ];
+        all_themes.extend(self.custom_themes.iter().cloned());
+        if !all_themes.contains(&self.config.theme) {
+            all_themes.push(self.config.theme.clone());
+        }
 
         let theme_picker = pick_list(
             all_themes,
-            Some(self.config.theme),
+            Some(self.config.theme.clone()),
             Message::ChangeTheme,
         )
         .width(Length::Fixed(200.0));
@@ -205,6 +316,53 @@ impl Helper {
             checkbox("Show class icons", self.config.show_class_icons)
                 .on_toggle(Message::ShowClasses);
 
+        let show_resource_columns = checkbox(
+            "Show resources in overview", self.config.show_resource_columns,
+        )
+        .on_toggle(Message::ShowResourceColumns);
+
+        let overview_compact =
+            checkbox("Compact overview", self.config.overview_compact)
+                .on_toggle(Message::SetOverviewCompact);
+
+        let activity_timeline = checkbox(
+            "Show today's activity on the account page",
+            self.config.show_activity_timeline,
+        )
+        .on_toggle(Message::SetShowActivityTimeline);
+
+        let dry_run = checkbox(
+            "Dry run: only log automation commands",
+            self.config.automation_dry_run,
+        )
+        .on_toggle(Message::SetAutomationDryRun);
+
+        let randomize_order = checkbox(
+            "Randomize the order of automation actions",
+            self.config.randomize_action_order,
+        )
+        .on_toggle(Message::SetRandomizeActionOrder);
+
+        let automation_paused =
+            checkbox("Pause all automation", self.config.automation_paused)
+                .on_toggle(Message::SetAutomationPaused);
+
+        let notifications = checkbox(
+            "Notify when fights are available",
+            self.config.notifications_enabled,
+        )
+        .on_toggle(Message::SetNotificationsEnabled);
+
+        let notify_crawl = checkbox(
+            "Notify when a server is fully crawled",
+            self.config.notify_crawl_finished,
+        )
+        .on_toggle(Message::SetNotifyCrawlFinished);
+
+        let confirm_logout =
+            checkbox("Confirm before logging out", self.config.confirm_logout)
+                .on_toggle(Message::SetConfirmLogout);
+
         let max_threads =
             number_input(self.config.max_threads, 50, Message::SetMaxThreads);
 
@@ -237,10 +395,219 @@ impl Helper {
         .width(Length::Fill)
         .align_items(Alignment::Center);
 
+        let luck = self.config.battle_luck_factor;
+        let battle_luck = row!(
+            text(format!("Battle luck factor: {luck:.2}")),
+            horizontal_space(),
+            slider(0.5..=2.0, luck, Message::SetBattleLuckFactor)
+                .step(0.05)
+                .width(Length::Fixed(100.0)),
+        )
+        .width(Length::Fill)
+        .align_items(Alignment::Center);
+
+        let thousands_separator = row!(
+            "Thousands separator:",
+            horizontal_space(),
+            text_input(",", &self.config.thousands_separator)
+                .on_input(Message::SetThousandsSeparator)
+                .width(Length::Fixed(60.0)),
+        )
+        .width(Length::Fill)
+        .align_items(Alignment::Center);
+
+        let proxy = column!(
+            row!(
+                "Proxy:",
+                horizontal_space(),
+                text_input(
+                    "socks5://host:port",
+                    self.config.proxy_url.as_deref().unwrap_or_default()
+                )
+                .on_input(Message::SetProxyUrl)
+                .width(Length::Fixed(180.0)),
+            )
+            .align_items(Alignment::Center),
+            text("Used for every connection after a restart").size(12)
+        )
+        .width(Length::Fill);
+
+        let crawl_order = row!(
+            "Default crawl order:",
+            horizontal_space(),
+            pick_list(
+                [
+                    CrawlingOrder::Random,
+                    CrawlingOrder::TopDown,
+                    CrawlingOrder::BottomUp,
+                ],
+                Some(self.config.default_crawl_order),
+                Message::SetDefaultCrawlOrder,
+            ),
+        )
+        .width(Length::Fill)
+        .align_items(Alignment::Center);
+
+        let freshness = self.config.battle_freshness();
+        let battle_freshness = row!(
+            text(format!("Up to date targets: {:.0}%", freshness * 100.0)),
+            horizontal_space(),
+            slider(0.0..=1.0, freshness, Message::SetAutoBattleFreshness)
+                .step(0.05)
+                .width(Length::Fixed(100.0)),
+        )
+        .width(Length::Fill)
+        .align_items(Alignment::Center);
+
+        let min_delay = number_input(
+            self.config.automation_min_delay_ms,
+            AUTOMATION_DELAY_MAX_MS,
+            Message::SetAutomationMinDelay,
+        )
+        .step(100);
+
+        let min_delay =
+            row!("Min automation delay (ms):", horizontal_space(), min_delay)
+                .width(Length::Fill)
+                .align_items(Alignment::Center);
+
+        let max_delay = number_input(
+            self.config.automation_max_delay_ms,
+            AUTOMATION_DELAY_MAX_MS,
+            Message::SetAutomationMaxDelay,
+        )
+        .step(100);
+
+        let max_delay =
+            row!("Max automation delay (ms):", horizontal_space(), max_delay)
+                .width(Length::Fill)
+                .align_items(Alignment::Center);
+
+        let login_stagger = number_input(
+            self.config.login_stagger_ms,
+            LOGIN_STAGGER_MAX_MS,
+            Message::SetLoginStagger,
+        )
+        .step(250);
+
+        let login_stagger =
+            row!("SSO login stagger (ms):", horizontal_space(), login_stagger)
+                .width(Length::Fill)
+                .align_items(Alignment::Center);
+
+        let concurrent_logins = number_input(
+            self.config.max_concurrent_logins(),
+            MAX_CONCURRENT_LOGINS,
+            Message::SetMaxConcurrentLogins,
+        );
+
+        let concurrent_logins = row!(
+            "Max concurrent logins:",
+            horizontal_space(),
+            concurrent_logins
+        )
+        .width(Length::Fill)
+        .align_items(Alignment::Center);
+
+        let queue_max = number_input(
+            self.config.automation_queue_max(),
+            AUTOMATION_QUEUE_LIMIT,
+            Message::SetAutomationQueueMax,
+        );
+
+        let queue_max = row!(
+            "Max queued automation commands:",
+            horizontal_space(),
+            queue_max
+        )
+        .width(Length::Fill)
+        .align_items(Alignment::Center);
+
+        let poll_interval = number_input(
+            self.config.poll_interval_ms(),
+            POLL_INTERVAL_MAX_MS,
+            Message::SetPollInterval,
+        )
+        .step(1000);
+
+        let poll_interval = row!(
+            "Auto poll interval (ms):",
+            horizontal_space(),
+            poll_interval
+        )
+        .width(Length::Fill)
+        .align_items(Alignment::Center);
+
+        let ui_refresh = number_input(
+            self.config.ui_refresh_ms(),
+            UI_REFRESH_MAX_MS,
+            Message::SetUiRefresh,
+        )
+        .step(100);
+
+        let ui_refresh =
+            row!("UI refresh (ms):", horizontal_space(), ui_refresh)
+                .width(Length::Fill)
+                .align_items(Alignment::Center);
+
+        let poll_watchdog = number_input(
+            self.config.poll_watchdog_min,
+            24 * 60,
+            Message::SetPollWatchdog,
+        );
+
+        let poll_watchdog = row!(
+            "Relog without poll (min, 0 = off):",
+            horizontal_space(),
+            poll_watchdog
+        )
+        .width(Length::Fill)
+        .align_items(Alignment::Center);
+
+        let idle_logout = number_input(
+            self.config.auto_logout_idle_min.unwrap_or(0),
+            24 * 60,
+            Message::SetAutoLogoutIdle,
+        )
+        .step(5);
+
+        let idle_logout = row!(
+            "Log out when idle for (min, 0 = off):",
+            horizontal_space(),
+            idle_logout
+        )
+        .width(Length::Fill)
+        .align_items(Alignment::Center);
+
+        let autosave = number_input(
+            self.config.autosave_interval_min,
+            24 * 60,
+            Message::SetAutosaveInterval,
+        )
+        .step(5);
+
+        let autosave =
+            row!("HoF autosave (min, 0 = off):", horizontal_space(), autosave)
+                .width(Length::Fill)
+                .align_items(Alignment::Center);
+
+        let export_accounts = button("Export account summary")
+            .on_press(Message::ExportAccountSummary)
+            .style(theme::Button::Secondary);
+
+        let view_logs = button("View logs")
+            .on_press(Message::ViewLogs)
+            .style(theme::Button::Secondary);
+
         let settings_column = column!(
-            theme_row, auto_fetch_hof, auto_poll, max_threads, start_threads,
-            blacklist_threshold,
-            crawling_restrict, show_class_icons
+            theme_row, auto_fetch_hof, auto_poll, automation_paused, dry_run,
+            randomize_order, notifications, notify_crawl, confirm_logout,
+            min_delay, max_delay, login_stagger, concurrent_logins, queue_max,
+            poll_interval, poll_watchdog, idle_logout, ui_refresh, max_threads,
+            start_threads, crawl_order, blacklist_threshold, battle_luck,
+            battle_freshness, crawling_restrict, show_class_icons,
+            show_resource_columns, overview_compact, activity_timeline,
+            thousands_separator, proxy, autosave, export_accounts, view_logs
         )
         .width(Length::Fixed(300.0))
         .spacing(20);
@@ -253,44 +620,149 @@ impl Helper {
             .into()
     }
 
+    fn view_logs(&self, level: log::Level) -> Element<'_, Message> {
+        let top_row =
+            top_bar(text("Logs").size(20).into(), Some(Message::ViewSettings));
+
+        let levels = vec![
+            log::Level::Error,
+            log::Level::Warn,
+            log::Level::Info,
+            log::Level::Debug,
+        ];
+        let level_picker = pick_list(levels, Some(level), Message::SetLogLevel);
+
+        let copy = button("Copy logs")
+            .on_press(Message::CopyLogs)
+            .style(theme::Button::Secondary);
+
+        let controls = row!(text("Level: "), level_picker, copy)
+            .spacing(10)
+            .align_items(Alignment::Center);
+
+        let lines = logs::recent_lines(level);
+        let mut log_col = column!().spacing(2).padding(10);
+        for line in &lines {
+            log_col = log_col.push(text(line.to_string()).size(12));
+        }
+        if lines.is_empty() {
+            log_col = log_col.push(text("Nothing has been logged yet"));
+        }
+
+        let log_view = widget::scrollable(log_col)
+            .direction(widget::scrollable::Direction::Both {
+                vertical: Default::default(),
+                horizontal: Default::default(),
+            })
+            .width(Length::Fill)
+            .height(Length::Fill);
+
+        column!(top_row, controls, log_view)
+            .spacing(20)
+            .height(Length::Fill)
+            .width(Length::Fill)
+            .align_items(Alignment::Center)
+            .into()
+    }
+
     fn view_overview(
         &self,
         selected: &HashSet<AccountIdent>,
         currrent_action: &Option<ActionSelection>,
+        filter: &str,
+        sort: OverviewSort,
+        sort_desc: bool,
     ) -> Element<'_, Message> {
         let top_bar =
             top_bar(text("Overview").size(20).into(), Some(Message::ViewLogin));
 
+        let filter_input = text_input("Filter by name or server", filter)
+            .id(text_input::Id::new(OVERVIEW_FILTER_ID))
+            .on_input(Message::OverviewFilterChanged)
+            .width(Length::Fixed(300.0));
+        let view_characters = button("All characters")
+            .on_press(Message::ViewCharacters)
+            .style(theme::Button::Secondary);
+        let filter_input = row!(filter_input, view_characters)
+            .spacing(10)
+            .align_items(Alignment::Center);
+
+        let filter = filter.to_lowercase();
+        let is_visible = |acc: &AccountInfo, server: &ServerInfo| {
+            filter.is_empty()
+                || acc.name.to_lowercase().contains(&filter)
+                || get_server_code(&server.ident.url)
+                    .to_lowercase()
+                    .contains(&filter)
+        };
+
         let mut accounts = column!()
             .padding(20)
             .spacing(5)
             .width(Length::Fill)
             .align_items(Alignment::Center);
 
-        let info_row = row!(
+        let header = |label, width, this: OverviewSort| {
+            let mut content = row!(text(label)).align_items(Alignment::Center);
+            if this == sort {
+                content = content.push(
+                    iced_aw::core::icons::bootstrap::icon_to_text(
+                        if sort_desc {
+                            iced_aw::Bootstrap::CaretDownFill
+                        } else {
+                            iced_aw::Bootstrap::CaretUpFill
+                        },
+                    )
+                    .size(12.0),
+                );
+            }
+            button(content)
+                .on_press(Message::SetOverviewSort(this))
+                .style(theme::Button::Text)
+                .padding(0.0)
+                .width(width)
+        };
+
+        let mut info_row = row!(
             center(text("Status").width(ACC_STATUS_WIDTH)),
-            center(text("Server").width(SERVER_CODE_WIDTH)),
-            text("Name").width(ACC_NAME_WIDTH),
+            header("Server", SERVER_CODE_WIDTH, OverviewSort::Server),
+            header("Name", ACC_NAME_WIDTH, OverviewSort::Name),
             horizontal_space(),
-            center(text("Underworld").width(UNDERWORLD_WIDTH)),
-            center(text("Arena").width(NEXT_FIGHT_WIDTH)),
-            center(text("Tav").width(TAVERN_WIDTH)),
-            center(text("Exp").width(EXPEDITION_WIDTH)),
-            center(text("Dng").width(DUNGEON_WIDTH)),
-            center(text("Pets").width(PET_WIDTH)),
-            center(text("Guild").width(GUILD_WIDTH)),
-            center(text("Scrapbook").width(SCRAPBOOK_COUNT_WIDTH)),
-            text("Crawling").width(CRAWLING_STATUS_WIDTH),
-        )
-        .spacing(10.0)
-        .width(Length::Fill)
-        .padding(5.0);
+        );
+        let compact = self.config.overview_compact;
+        if compact {
+            info_row =
+                info_row.push(center(text("Next").width(NEXT_ACTION_WIDTH)));
+        } else if self.config.show_resource_columns {
+            info_row = info_row
+                .push(center(text("Lvl").width(LEVEL_WIDTH)))
+                .push(center(text("Gold").width(GOLD_WIDTH)))
+                .push(center(text("Mush").width(MUSHROOM_WIDTH)));
+        }
+        if !compact {
+            info_row = info_row
+                .push(center(text("Underworld").width(UNDERWORLD_WIDTH)))
+                .push(header("Arena", NEXT_FIGHT_WIDTH, OverviewSort::Arena))
+                .push(center(text("Tav").width(TAVERN_WIDTH)))
+                .push(center(text("Exp").width(EXPEDITION_WIDTH)))
+                .push(center(text("Dng").width(DUNGEON_WIDTH)))
+                .push(center(text("Pets").width(PET_WIDTH)))
+                .push(center(text("Guild").width(GUILD_WIDTH)))
+                .push(center(text("Wheel").width(WHEEL_WIDTH)))
+                .push(header(
+                    "Scrapbook",
+                    SCRAPBOOK_COUNT_WIDTH,
+                    OverviewSort::Scrapbook,
+                ))
+                .push(text("Crawling").width(CRAWLING_STATUS_WIDTH));
+        }
+        let info_row = info_row.spacing(10.0).width(Length::Fill).padding(5.0);
 
         let all_active: Vec<_> = self
             .servers
             .0
             .values()
-            .flat_map(|a| a.accounts.values())
+            .flat_map(|s| s.accounts.values().filter(|a| is_visible(a, s)))
             .map(|a| a.ident)
             .collect();
 
@@ -330,12 +802,11 @@ impl Helper {
 
         accounts = accounts.push(full_row);
 
-        let mut servers: Vec<_> = self.servers.0.values().collect();
-        servers.sort_by_key(|a| &a.ident.ident);
-        for server in servers {
+        let mut rows = vec![];
+        for server in self.servers.0.values() {
             let server_status: Box<str> = match &server.crawling {
                 CrawlingStatus::Waiting => "Waiting".into(),
-                CrawlingStatus::Restoring => "Restoring".into(),
+                CrawlingStatus::Restoring { .. } => "Restoring".into(),
                 CrawlingStatus::CrawlingFailed(_) => "Error".into(),
                 CrawlingStatus::Crawling { que, .. } => {
                     let lock = que.lock().unwrap();
@@ -351,62 +822,212 @@ impl Helper {
                 }
             };
 
-            let mut accs: Vec<_> = server.accounts.values().collect();
-            accs.sort_by_key(|a| &a.name);
-            for acc in accs {
-                let info_row =
-                    overview_row(acc, server, &server_status, &self.config);
-                let selected = selected.contains(&acc.ident);
+            for acc in
+                server.accounts.values().filter(|a| is_visible(a, server))
+            {
+                // Fights that are ready (or unknown) sort as the earliest
+                let next_fight = match &*acc.status.lock().unwrap() {
+                    AccountStatus::Idle(_, gs) | AccountStatus::Busy(gs, _) => {
+                        let now = acc.server_now();
+                        gs.arena.next_free_fight.filter(|a| *a > now)
+                    }
+                    _ => None,
+                };
+                rows.push((server, acc, server_status.clone(), next_fight));
+            }
+        }
 
-                let ident = acc.ident;
+        rows.sort_by(|(s_a, a, _, fight_a), (s_b, b, _, fight_b)| {
+            let by_server = || s_a.ident.ident.cmp(&s_b.ident.ident);
+            let by_name = || a.name.cmp(&b.name);
+            let scrapbook_count = |acc: &AccountInfo| {
+                acc.scrapbook_info
+                    .as_ref()
+                    .map_or(0, |a| a.scrapbook.items.len())
+            };
+            let ord = match sort {
+                OverviewSort::Server => by_server(),
+                OverviewSort::Name => by_name(),
+                OverviewSort::Scrapbook => {
+                    scrapbook_count(a).cmp(&scrapbook_count(b))
+                }
+                OverviewSort::Arena => fight_a.cmp(fight_b),
+            };
+            let ord = if sort_desc { ord.reverse() } else { ord };
+            ord.then_with(by_server).then_with(by_name)
+        });
 
-                let cb = checkbox("", selected)
-                    .on_toggle(move |nv| Message::SetOverviewSelected {
-                        ident: vec![ident],
-                        val: nv,
-                    })
-                    .size(13.0);
+        for (server, acc, server_status, _) in rows {
+            let info_row =
+                overview_row(acc, server, &server_status, &self.config);
+            let selected = selected.contains(&acc.ident);
 
-                let this_action = Some(ActionSelection::Character(ident));
-                let is_acting = currrent_action == &this_action;
+            let ident = acc.ident;
 
-                let action_button = button(
-                    iced_aw::core::icons::bootstrap::icon_to_text(
-                        iced_aw::Bootstrap::ThreeDotsVertical,
-                    )
-                    .size(18.0),
-                )
-                .on_press(if is_acting {
-                    Message::SetAction(None)
-                } else {
-                    Message::SetAction(this_action)
+            let cb = checkbox("", selected)
+                .on_toggle(move |nv| Message::SetOverviewSelected {
+                    ident: vec![ident],
+                    val: nv,
                 })
-                .padding(4.0);
+                .size(13.0);
 
-                let action_dd = DropDown::new(
-                    action_button,
-                    self.overview_actions(),
-                    is_acting,
+            let this_action = Some(ActionSelection::Character(ident));
+            let is_acting = currrent_action == &this_action;
+
+            let action_button = button(
+                iced_aw::core::icons::bootstrap::icon_to_text(
+                    iced_aw::Bootstrap::ThreeDotsVertical,
                 )
-                .width(Length::Fill)
-                .on_dismiss(Message::SetAction(None))
-                .alignment(iced_aw::drop_down::Alignment::BottomStart);
+                .size(18.0),
+            )
+            .on_press(if is_acting {
+                Message::SetAction(None)
+            } else {
+                Message::SetAction(this_action)
+            })
+            .padding(4.0);
 
-                let full_row = row!(cb, info_row, action_dd)
-                    .spacing(5.0)
-                    .align_items(Alignment::Center);
+            let action_dd = DropDown::new(
+                action_button,
+                self.overview_actions(),
+                is_acting,
+            )
+            .width(Length::Fill)
+            .on_dismiss(Message::SetAction(None))
+            .alignment(iced_aw::drop_down::Alignment::BottomStart);
+
+            let full_row = row!(cb, info_row, action_dd)
+                .spacing(5.0)
+                .align_items(Alignment::Center);
 
-                accounts = accounts.push(full_row);
+            accounts = accounts.push(full_row);
+        }
+
+        let crawl_total = self.view_crawl_total();
+
+        // The resource columns do not fit into small windows, so the table
+        // keeps a minimum width and can be scrolled sideways
+        let accounts = if self.config.show_resource_columns && !compact {
+            // Leaves room for the vertical scrollbar
+            let width =
+                self.config.window_width.map_or(0.0, |a| a as f32 - 20.0);
+            widget::scrollable(
+                accounts.width(Length::Fixed(width.max(OVERVIEW_MIN_WIDTH))),
+            )
+            .direction(widget::scrollable::Direction::Both {
+                vertical: Default::default(),
+                horizontal: Default::default(),
+            })
+        } else {
+            widget::scrollable(accounts)
+        };
+
+        column!(
+            top_bar,
+            self.view_account_summary(),
+            crawl_total,
+            filter_input,
+            accounts
+        )
+        .spacing(5)
+        .height(Length::Fill)
+        .width(Length::Fill)
+        .align_items(Alignment::Center)
+        .into()
+    }
+
+    /// How many accounts are logged in, which ones have problems and so on
+    fn view_account_summary(&self) -> Element<'_, Message> {
+        let mut accounts = 0;
+        let mut servers = 0;
+        let (mut active, mut errored, mut logging_in) = (0, 0, 0);
+        for server in self.servers.0.values() {
+            if server.accounts.is_empty() {
+                continue;
+            }
+            servers += 1;
+            for acc in server.accounts.values() {
+                accounts += 1;
+                match &*acc.status.lock().unwrap() {
+                    AccountStatus::Idle(..) | AccountStatus::Busy(..) => {
+                        active += 1
+                    }
+                    AccountStatus::FatalError(_)
+                    | AccountStatus::WaitingToRetry { .. } => errored += 1,
+                    AccountStatus::LoggingIn
+                    | AccountStatus::LoggingInAgain
+                    | AccountStatus::Relogging { .. } => logging_in += 1,
+                    AccountStatus::LoggedOutIdle { .. } => {}
+                }
             }
         }
+        text(format!(
+            "{accounts} accounts across {servers} servers, {active} active, \
+             {errored} errored, {logging_in} logging in"
+        ))
+        .into()
+    }
 
-        column!(top_bar, widget::scrollable(accounts))
-            .spacing(5)
-            .height(Length::Fill)
-            .width(Length::Fill)
-            .align_items(Alignment::Center)
-            .into()
+    fn view_crawl_total(&self) -> Element<'_, Message> {
+        let mut crawled = 0;
+        let mut remaining = 0;
+        let mut rate = None;
+        for server in self.servers.0.values() {
+            let CrawlingStatus::Crawling {
+                que, player_info, ..
+            } = &server.crawling
+            else {
+                continue;
+            };
+            let server_remaining = que.lock().unwrap().count_remaining();
+            crawled += player_info.len();
+            remaining += server_remaining;
+            if server_remaining > 0
+                && let Some(per_sec) = server.crawl_rate.per_sec()
+            {
+                rate = Some(rate.unwrap_or(0.0) + per_sec);
+            }
+        }
+
+        let total = (crawled + remaining).max(1);
+        let eta = match rate {
+            _ if remaining == 0 => "Finished".to_string(),
+            Some(per_sec) if per_sec > 0.0 => {
+                let secs = (remaining as f64 / per_sec).ceil() as u64;
+                // Rounding up the whole duration first keeps this from
+                // showing "1h 60m"
+                let mins = secs.div_ceil(60);
+                let (h, m) = (mins / 60, mins % 60);
+                if h > 0 {
+                    format!("{h}h {m}m")
+                } else {
+                    format!("{m}m")
+                }
+            }
+            _ => "—".to_string(),
+        };
+        let rate_text = match rate {
+            Some(per_sec) => format!("{per_sec:.1}/s"),
+            None => "—".to_string(),
+        };
+
+        row!(
+            text(format!(
+                "Crawled {}/{}",
+                crawled.to_formatted_string(&self.config.num_format),
+                total.to_formatted_string(&self.config.num_format)
+            )),
+            progress_bar(0.0..=total as f32, crawled.min(total) as f32)
+                .height(Length::Fixed(10.0))
+                .width(Length::Fixed(200.0)),
+            text(rate_text),
+            text(format!("ETA: {eta}")),
+        )
+        .spacing(15)
+        .align_items(Alignment::Center)
+        .into()
     }
+
     fn overview_actions(&self) -> Element<'_, Message> {
         let mut all_actions = column!().spacing(4.0);
 
@@ -441,21 +1062,106 @@ impl Helper {
         ));
 
         all_actions = all_actions.push(action(
-            button("Logout")
-                .on_press(Message::MultiAction {
+            button("Copy Order").on_press(Message::MultiAction {
+                action: OverviewAction::CopyBattleOrder,
+            }),
+        ));
+
+        all_actions = all_actions.push(
+            pick_list(MissionStrategy::all(), None, |nv| {
+                Message::MultiAction {
+                    action: OverviewAction::MissionStrategy(nv),
+                }
+            })
+            .placeholder("Strategy")
+            .width(100.0),
+        );
+
+        fn toggle(
+            label: &str,
+            action: fn(bool) -> OverviewAction,
+        ) -> Element<'_, Message> {
+            let icon_button = |icon, nv| {
+                button(iced_aw::core::icons::bootstrap::icon_to_text(icon))
+                    .on_press(Message::MultiAction { action: action(nv) })
+                    .padding(4.0)
+            };
+            row!(
+                text(label),
+                horizontal_space(),
+                icon_button(iced_aw::Bootstrap::Check, true),
+                icon_button(iced_aw::Bootstrap::X, false),
+            )
+            .spacing(2.0)
+            .width(100.0)
+            .align_items(Alignment::Center)
+            .into()
+        }
+
+        all_actions = all_actions
+            .push(toggle("Tavern", OverviewAction::AutoTavern))
+            .push(toggle("Exp", OverviewAction::AutoExpeditions))
+            .push(toggle("Dng", OverviewAction::AutoDungeons))
+            .push(toggle("Pets", OverviewAction::AutoPets));
+
+        all_actions = all_actions.push(action(button("Reconnect").on_press(
+            Message::MultiAction {
+                action: OverviewAction::RetryLogin,
+            },
+        )));
+
+        all_actions = all_actions.push(action(
+            button(self.logout_label())
+                .on_press(self.logout_press(Message::MultiAction {
                     action: OverviewAction::Logout,
-                })
+                }))
                 .style(theme::Button::Destructive),
         ));
 
         all_actions.into()
     }
+
+    fn logout_label(&self) -> &'static str {
+        if self.logout_armed {
+            "Confirm"
+        } else {
+            "Logout"
+        }
+    }
+
+    /// The message of a logout button, that does `logout` only on the second
+    /// click, if confirmations are enabled
+    fn logout_press(&self, logout: Message) -> Message {
+        if self.config.confirm_logout && !self.logout_armed {
+            Message::ArmLogout(true)
+        } else {
+            logout
+        }
+    }
 }
 
 #[derive(Debug, Clone, Copy)]
 pub enum OverviewAction {
     Logout,
+    /// Logs in all selected accounts, that ran into a fatal error, again
+    RetryLogin,
     AutoBattle(bool),
+    CopyBattleOrder,
+    MissionStrategy(MissionStrategy),
+    AutoTavern(bool),
+    AutoExpeditions(bool),
+    AutoDungeons(bool),
+    AutoPets(bool),
+}
+
+/// The column the overview is sorted by. Ties are broken by server and name
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OverviewSort {
+    #[default]
+    Server,
+    Name,
+    Scrapbook,
+    Arena,
 }
 
 const ACC_STATUS_WIDTH: f32 = 80.0;
@@ -463,13 +1169,23 @@ const ACC_NAME_WIDTH: f32 = 200.0;
 const SERVER_CODE_WIDTH: f32 = 50.0;
 const SCRAPBOOK_COUNT_WIDTH: f32 = 60.0;
 const NEXT_FIGHT_WIDTH: f32 = 60.0;
-const UNDERWORLD_WIDTH: f32 = 60.0;
+const UNDERWORLD_WIDTH: f32 = 80.0;
 const DUNGEON_WIDTH: f32 = 60.0;
 const PET_WIDTH: f32 = 60.0;
 const GUILD_WIDTH: f32 = 60.0;
+const WHEEL_WIDTH: f32 = 60.0;
 const TAVERN_WIDTH: f32 = 60.0;
 const EXPEDITION_WIDTH: f32 = 60.0;
 const CRAWLING_STATUS_WIDTH: f32 = 80.0;
+const NEXT_ACTION_WIDTH: f32 = 60.0;
+const LEVEL_WIDTH: f32 = 40.0;
+const GOLD_WIDTH: f32 = 80.0;
+const MUSHROOM_WIDTH: f32 = 50.0;
+/// The automation queue length, from which on the overview badge warns
+const AUTOMATION_QUEUE_WARN: usize = 5;
+/// The width the overview needs to show all columns including the resource
+/// columns. Narrower windows scroll horizontally instead
+const OVERVIEW_MIN_WIDTH: f32 = 1470.0;
 
 fn overview_row<'a>(
     acc: &'a AccountInfo,
@@ -479,29 +1195,120 @@ fn overview_row<'a>(
 ) -> Element<'a, Message> {
     let status_text = |t: &str| center(text(t).width(ACC_STATUS_WIDTH));
 
+    let now = acc.server_now();
     let mut next_free_fight = None;
+    let mut next_action = None;
+    let mut error = None;
+    let mut resources = None;
 
+    let parked = !config.char_enabled(&acc.name, server.ident.id);
     let acc_status = match &*acc.status.lock().unwrap() {
         AccountStatus::LoggingIn => status_text("Logging in"),
         AccountStatus::Idle(_, gs) => {
             next_free_fight = Some(gs.arena.next_free_fight);
-            status_text("Active")
+            next_action = Some(next_action_time(gs));
+            let c = &gs.character;
+            resources = Some((c.level, c.silver, c.mushrooms));
+            status_text(if parked { "Paused" } else { "Active" })
         }
         AccountStatus::Busy(gs, reason) => {
             next_free_fight = Some(gs.arena.next_free_fight);
+            next_action = Some(next_action_time(gs));
+            let c = &gs.character;
+            resources = Some((c.level, c.silver, c.mushrooms));
             // Treat internal automation busy states as Active to avoid flicker
             let r = reason.as_ref();
-            if r.starts_with("Auto") { status_text("Active") } else { status_text(reason) }
+            if parked {
+                status_text("Paused")
+            } else if r.starts_with("Auto") {
+                status_text("Active")
+            } else {
+                status_text(reason)
+            }
+        }
+        AccountStatus::FatalError(err) => {
+            error = Some(err.clone());
+            status_text("Error!")
         }
-        AccountStatus::FatalError(_) => status_text("Error!"),
         AccountStatus::LoggingInAgain => status_text("Logging in"),
+        AccountStatus::Relogging { attempt } => {
+            status_text(&format!("Relogin (attempt {})", attempt + 1))
+        }
+        AccountStatus::WaitingToRetry { until, error: err } => {
+            error = Some(err.clone());
+            let left = remaining_minutes(*until, Local::now());
+            status_text(&format!("Retry in {left}"))
+        }
+        AccountStatus::LoggedOutIdle { until } => {
+            let left = remaining_minutes(*until, Local::now());
+            status_text(&format!("Sleeping {left}"))
+        }
+    };
+
+    // The cell is too narrow for the error itself, so it is shown on hover
+    let acc_status: Element<Message> = match error {
+        Some(err) => tooltip(acc_status, text(err), tooltip::Position::Bottom)
+            .style(theme::Container::Box)
+            .into(),
+        None => acc_status.into(),
     };
 
     let server_code = center(
         text(get_server_code(&server.ident.url)).width(SERVER_CODE_WIDTH),
     );
 
-    let acc_name = text(&acc.name).width(ACC_NAME_WIDTH);
+    // Commands pile up here, while the session stays busy for too long
+    let queued = acc.automation_queue.len();
+    let mut acc_name = row!(text(&acc.name))
+        .spacing(6)
+        .align_items(Alignment::Center)
+        .width(ACC_NAME_WIDTH);
+    if queued > 0 {
+        let color = if queued >= AUTOMATION_QUEUE_WARN {
+            iced::Color::from_rgb8(230, 160, 40)
+        } else {
+            iced::Color::from_rgb8(130, 130, 130)
+        };
+        let badge = text(format!("+{queued}"))
+            .size(13)
+            .style(theme::Text::Color(color));
+        acc_name = acc_name.push(
+            tooltip(
+                badge,
+                text(format!("{queued} automation commands queued")),
+                tooltip::Position::Bottom,
+            )
+            .style(theme::Container::Box),
+        );
+    }
+
+    let icon_to_text =
+        |icon| iced_aw::core::icons::bootstrap::icon_to_text(icon).size(18.0);
+
+    if config.overview_compact {
+        let next = match next_action {
+            None => icon_to_text(iced_aw::Bootstrap::Question),
+            Some(Some(x)) if x >= now => text(remaining_minutes(x, now)),
+            Some(Some(_)) => icon_to_text(iced_aw::Bootstrap::Check),
+            Some(None) => text("—"),
+        };
+        let info_row = row!(
+            acc_status,
+            server_code,
+            acc_name,
+            horizontal_space(),
+            center(next.width(NEXT_ACTION_WIDTH))
+        )
+        .spacing(10.0)
+        .align_items(Alignment::Center);
+        return button(info_row)
+            .on_press(Message::ShowPlayer { ident: acc.ident })
+            .width(Length::Fill)
+            .height(Length::Shrink)
+            .padding(4.0)
+            .style(theme::Button::Secondary)
+            .into();
+    }
 
     let scrapbook_count: String = match &acc.scrapbook_info {
         Some(si) => si
@@ -515,13 +1322,10 @@ fn overview_row<'a>(
         .width(SCRAPBOOK_COUNT_WIDTH)
         .horizontal_alignment(Horizontal::Center);
 
-    let icon_to_text =
-        |icon| iced_aw::core::icons::bootstrap::icon_to_text(icon).size(18.0);
-
     let arena_cell: Element<Message> = {
         let timer_text = match next_free_fight {
             None => icon_to_text(iced_aw::Bootstrap::Question),
-            Some(Some(x)) if x >= Local::now() => text(remaining_minutes(x)),
+            Some(Some(x)) if x >= now => text(remaining_minutes(x, now)),
             Some(_) => icon_to_text(iced_aw::Bootstrap::Check),
         };
 
@@ -552,11 +1356,10 @@ fn overview_row<'a>(
     let tavern_cell: Element<Message> = {
     let (timer_text, auto_on) = match &*acc.status.lock().unwrap() {
             AccountStatus::Idle(_, gs) | AccountStatus::Busy(gs, _) => {
-                let now = Local::now();
                 use sf_api::gamestate::tavern::CurrentAction;
                 let timer_text = match &gs.tavern.current_action {
                     CurrentAction::Quest { busy_until, .. } if *busy_until > now => {
-                        text(remaining_minutes(*busy_until))
+                        text(remaining_minutes(*busy_until, now))
                     }
                     CurrentAction::Quest { .. } => {
                         // ended
@@ -616,12 +1419,11 @@ fn overview_row<'a>(
         let (display_text, auto_on) = match &*acc.status.lock().unwrap() {
             AccountStatus::Idle(_, gs) | AccountStatus::Busy(gs, _) => {
                 use sf_api::gamestate::tavern::{CurrentAction, ExpeditionStage, AvailableTasks};
-                let now = Local::now();
                 let label = match &gs.tavern.current_action {
                     CurrentAction::Expedition => {
                         if let Some(active) = gs.tavern.expeditions.active() {
                             match active.current_stage() {
-                                ExpeditionStage::Waiting(until) if until > now => text(remaining_minutes(until)),
+                                ExpeditionStage::Waiting(until) if until > now => text(remaining_minutes(until, now)),
                                 ExpeditionStage::Waiting(_) => text("0:00"),
                                 _ => icon_to_text(iced_aw::Bootstrap::Check),
                             }
@@ -668,15 +1470,14 @@ fn overview_row<'a>(
     let dungeons_cell: Element<Message> = {
     let (timer_text, auto_on) = match &*acc.status.lock().unwrap() {
             AccountStatus::Idle(_, gs) | AccountStatus::Busy(gs, _) => {
-                let now = Local::now();
                 let t = gs.dungeons.next_free_fight;
                 let timer_text = match t {
-                    Some(x) if x > now => text(remaining_minutes(x)),
+                    Some(x) if x > now => text(remaining_minutes(x, now)),
                     _ => icon_to_text(iced_aw::Bootstrap::Check),
                 };
                 let auto_on = config
                     .get_char_conf(&acc.name, server.ident.id)
-                    .map(|c| c.auto_dungeons)
+                    .map(|c| c.any_dungeon_fights())
                     .unwrap_or(false);
                 (timer_text, auto_on)
             }
@@ -684,7 +1485,7 @@ fn overview_row<'a>(
                 icon_to_text(iced_aw::Bootstrap::Question),
                 config
                     .get_char_conf(&acc.name, server.ident.id)
-                    .map(|c| c.auto_dungeons)
+                    .map(|c| c.any_dungeon_fights())
                     .unwrap_or(false),
             ),
         };
@@ -716,7 +1517,6 @@ fn overview_row<'a>(
     let pets_cell: Element<Message> = {
     let (timer_text, auto_on) = match &*acc.status.lock().unwrap() {
             AccountStatus::Idle(_, gs) | AccountStatus::Busy(gs, _) => {
-                let now = Local::now();
                 let timer_text = if let Some(pets) = &gs.pets {
                     // If all habitats have battled opponent, prefer exploration timer
                     use sf_api::gamestate::unlockables::HabitatType;
@@ -726,12 +1526,12 @@ fn overview_row<'a>(
                     for h in HabitatType::iter() { if !pets.habitats.get(h).battled_opponent { any_pvp_left = true; break; } }
                     if any_pvp_left {
                         match pets.opponent.next_free_battle {
-                            Some(t) if t > now => text(remaining_minutes(t)),
+                            Some(t) if t > now => text(remaining_minutes(t, now)),
                             Some(_) | None => icon_to_text(iced_aw::Bootstrap::Check),
                         }
                     } else {
                         match pets.next_free_exploration {
-                            Some(t) if t > now => text(remaining_minutes(t)),
+                            Some(t) if t > now => text(remaining_minutes(t, now)),
                             Some(_) | None => icon_to_text(iced_aw::Bootstrap::Check),
                         }
                     }
@@ -780,11 +1580,10 @@ fn overview_row<'a>(
     let guild_cell: Element<Message> = {
     let (timer_text, auto_on) = match &*acc.status.lock().unwrap() {
             AccountStatus::Idle(_, gs) | AccountStatus::Busy(gs, _) => {
-                let now = Local::now();
                 let (next, remaining) = gs.guild.as_ref().map(|g| (g.hydra.next_battle, g.hydra.remaining_fights)).unwrap_or((None, 0));
                 let timer_text = match (remaining, next) {
                     (0, _) => icon_to_text(iced_aw::Bootstrap::X),
-                    (_, Some(t)) if t > now => text(remaining_minutes(t)),
+                    (_, Some(t)) if t > now => text(remaining_minutes(t, now)),
                     (_, _) => icon_to_text(iced_aw::Bootstrap::Check),
                 };
                 let auto_on = config
@@ -826,6 +1625,44 @@ fn overview_row<'a>(
         .align_items(Alignment::Center)
         .width(GUILD_WIDTH);
 
+    let wheel_cell: Element<Message> = {
+        let auto_on = config
+            .get_char_conf(&acc.name, server.ident.id)
+            .map(|c| c.auto_wheel)
+            .unwrap_or(false);
+        let timer_text = match &*acc.status.lock().unwrap() {
+            AccountStatus::Idle(_, gs) | AccountStatus::Busy(gs, _) => {
+                match gs.specials.wheel.next_free_spin {
+                    Some(t) if t > now => text(remaining_minutes(t, now)),
+                    Some(_) => icon_to_text(iced_aw::Bootstrap::Check),
+                    None => icon_to_text(iced_aw::Bootstrap::X),
+                }
+            }
+            _ => icon_to_text(iced_aw::Bootstrap::Question),
+        };
+
+        let toggle = button(icon_to_text(if auto_on {
+            iced_aw::Bootstrap::LightningFill
+        } else {
+            iced_aw::Bootstrap::Lightning
+        }))
+        .on_press(Message::ConfigSetAutoWheel {
+            name: acc.name.clone(),
+            server: server.ident.id,
+            nv: !auto_on,
+        })
+        .padding(0.0);
+
+        row!(center(timer_text.width(25.0)), toggle)
+            .align_items(Alignment::Center)
+            .spacing(4.0)
+            .into()
+    };
+
+    let wheel_cell = column!(wheel_cell)
+        .align_items(Alignment::Center)
+        .width(WHEEL_WIDTH);
+
     let underworld_info: Element<Message> = acc
         .underworld_info
         .as_ref()
@@ -836,16 +1673,27 @@ fn overview_row<'a>(
                 iced_aw::Bootstrap::Lightning
             };
 
-            let remaining = 5u16.saturating_sub(a.underworld.lured_today);
+            let remaining =
+                MAX_LURES_PER_DAY.saturating_sub(a.underworld.lured_today);
             let remaining = if remaining == 0 {
                 icon_to_text(iced_aw::Bootstrap::Check)
             } else {
                 text(remaining.to_string())
             };
 
+            let souls = config
+                .get_char_conf(&acc.name, server.ident.id)
+                .is_some_and(|c| c.auto_underworld_souls);
+            let souls_status = if souls {
+                iced_aw::Bootstrap::DropletFill
+            } else {
+                iced_aw::Bootstrap::Droplet
+            };
+
             let row = row!(
                 center(remaining.width(25.0)),
-                center(icon_to_text(auto_status))
+                center(icon_to_text(auto_status)),
+                center(icon_to_text(souls_status))
             )
             .align_items(Alignment::Center)
             .spacing(4.0);
@@ -863,23 +1711,36 @@ fn overview_row<'a>(
 
     let crawling_status = text(crawling_status).width(CRAWLING_STATUS_WIDTH);
 
-    let info_row = row!(
-        acc_status,
-        server_code,
-        acc_name,
-        horizontal_space(),
-    underworld_info,
-    arena_cell,
-    tavern_cell,
-    expedition_cell,
-        dungeons_cell,
-        pets_cell,
-        guild_cell,
-        scrapbook_count,
-        crawling_status
-    )
-    .spacing(10.0)
-    .align_items(Alignment::Center);
+    let mut info_row =
+        row!(acc_status, server_code, acc_name, horizontal_space());
+
+    if config.show_resource_columns {
+        let num = |val: Option<u64>, width| {
+            let val = val.map_or("—".to_string(), |a| {
+                a.to_formatted_string(&config.num_format)
+            });
+            center(text(val).width(width))
+        };
+        info_row = info_row
+            .push(num(resources.map(|a| a.0.into()), LEVEL_WIDTH))
+            // Silver is the smallest unit, 100 of which make up one gold
+            .push(num(resources.map(|a| a.1 / 100), GOLD_WIDTH))
+            .push(num(resources.map(|a| a.2.into()), MUSHROOM_WIDTH));
+    }
+
+    let info_row = info_row
+        .push(underworld_info)
+        .push(arena_cell)
+        .push(tavern_cell)
+        .push(expedition_cell)
+        .push(dungeons_cell)
+        .push(pets_cell)
+        .push(guild_cell)
+        .push(wheel_cell)
+        .push(scrapbook_count)
+        .push(crawling_status)
+        .spacing(10.0)
+        .align_items(Alignment::Center);
 
     button(info_row)
         .on_press(Message::ShowPlayer { ident: acc.ident })
@@ -890,20 +1751,134 @@ fn overview_row<'a>(
         .into()
 }
 
-fn remaining_minutes(time: DateTime<Local>) -> String {
+/// The soonest of the timers shown in the overview. Timers in the past mean,
+/// that something can be done right now
+fn next_action_time(gs: &GameState) -> Option<DateTime<Local>> {
+    use crate::automation::GameStateLike;
+
+    let pets = gs.pets.as_ref();
+    [
+        gs.arena.next_free_fight,
+        gs.tavern_end_time(),
+        gs.expedition_end_time(),
+        gs.dungeons.next_free_fight,
+        pets.and_then(|a| a.opponent.next_free_battle),
+        pets.and_then(|a| a.next_free_exploration),
+        gs.specials.wheel.next_free_spin,
+    ]
+    .into_iter()
+    .flatten()
+    .min()
+}
+
+/// The time left until `time`. Game timers have to be passed
+/// `AccountInfo::server_now` as `now`, local timers `Local::now`
+fn remaining_minutes(time: DateTime<Local>, now: DateTime<Local>) -> String {
     // Round to whole minutes to reduce UI churn and keep the interface smooth
-    let now = Local::now();
     let total_secs = (time - now).num_seconds();
     let mins = if total_secs <= 0 { 0 } else { (total_secs + 59) / 60 };
     format!("{mins}m")
 }
 
+/// A second accurate countdown for the account page, which is redrawn more
+/// often than the overview. See `remaining_minutes` for `now`
+fn remaining_seconds(time: DateTime<Local>, now: DateTime<Local>) -> String {
+    let secs = (time - now).num_seconds().max(0);
+    let (hours, mins, secs) = (secs / 3600, secs / 60 % 60, secs % 60);
+    if hours > 0 {
+        format!("{hours}:{mins:02}:{secs:02}")
+    } else {
+        format!("{mins}:{secs:02}")
+    }
+}
+
+/// The width of the activity timeline, which covers a whole day
+const TIMELINE_WIDTH: f32 = 720.0;
+
+/// Today's quests, expeditions and guard shifts as colored blocks on a bar,
+/// that spans the whole day
+fn view_activity_timeline(log: &ActivityLog) -> Element<'_, Message> {
+    use chrono::Timelike;
+
+    let today = Local::now().date_naive();
+    let pos = |time: DateTime<Local>| {
+        let secs = if time.date_naive() < today {
+            0
+        } else {
+            time.num_seconds_from_midnight()
+        };
+        secs as f32 * TIMELINE_WIDTH / 86_400.0
+    };
+
+    let mut bar = row!().height(Length::Fixed(12.0));
+    let mut cursor = 0.0;
+    for segment in &log.segments {
+        let start = pos(segment.start).max(cursor);
+        let end = pos(segment.end).max(start + 1.0);
+        let gap = Length::Fixed(start - cursor);
+        bar = bar.push(widget::Space::with_width(gap));
+
+        let kind = segment.kind;
+        let block = container(text(""))
+            .width(Length::Fixed(end - start))
+            .height(Length::Fill)
+            .style(move |theme: &iced::Theme| {
+                let p = theme.palette();
+                let color = match kind {
+                    ActivityKind::Quest => p.primary,
+                    ActivityKind::Expedition => p.success,
+                    ActivityKind::CityGuard => {
+                        iced::Color::from_rgb8(130, 130, 130)
+                    }
+                };
+                iced::widget::container::Appearance {
+                    background: Some(iced::Background::Color(color)),
+                    ..Default::default()
+                }
+            });
+        let label = format!(
+            "{kind}: {} - {}",
+            segment.start.format("%H:%M"),
+            segment.end.format("%H:%M")
+        );
+        bar = bar.push(
+            tooltip(block, text(label), tooltip::Position::Bottom)
+                .style(theme::Container::Box),
+        );
+        cursor = end;
+    }
+
+    let bar = container(bar)
+        .width(Length::Fixed(TIMELINE_WIDTH))
+        .style(theme::Container::Box);
+    let scale = row!(
+        text("0:00").size(11),
+        horizontal_space(),
+        text("12:00").size(11),
+        horizontal_space(),
+        text("24:00").size(11)
+    )
+    .width(Length::Fixed(TIMELINE_WIDTH));
+
+    column!(bar, scale).spacing(2).into()
+}
+
+fn time_ago(time: DateTime<Local>) -> String {
+    let secs = (Local::now() - time).num_seconds().max(0);
+    match secs {
+        ..60 => format!("{secs}s ago"),
+        ..3600 => format!("{}m ago", secs / 60),
+        _ => format!("{}h ago", secs / 3600),
+    }
+}
+
 fn center(t: text::Text) -> text::Text {
     t.horizontal_alignment(Horizontal::Center)
 }
 
 pub fn view_crawling<'a>(
     server: &'a ServerInfo,
+    player: &'a AccountInfo,
     config: &'a Config,
 ) -> Element<'a, Message> {
     let mut left_col = column!().align_items(Alignment::Center).spacing(10);
@@ -915,6 +1890,8 @@ pub fn view_crawling<'a>(
             threads,
             que,
             player_info,
+            paused,
+            finished,
             ..
         } => {
             let lock = que.lock().unwrap();
@@ -935,10 +1912,17 @@ pub fn view_crawling<'a>(
                 .height(Length::Fixed(10.0));
             left_col = left_col.push(progress);
 
+            // Shows the override of this character. The crawler runs with
+            // the lowest one of all characters on the server
+            let shown_threads = config
+                .get_char_conf(&player.name, sid)
+                .and_then(|a| a.crawl_thread_override)
+                .unwrap_or(*threads);
+            let ident = player.ident;
             let thread_num =
-                number_input(*threads, config.max_threads, move |nv| {
+                number_input(shown_threads, config.max_threads, move |nv| {
                     Message::CrawlerSetThreads {
-                        server: sid,
+                        ident,
                         new_count: nv,
                     }
                 });
@@ -946,6 +1930,20 @@ pub fn view_crawling<'a>(
                 row!(text("Threads: "), horizontal_space(), thread_num)
                     .align_items(Alignment::Center);
             left_col = left_col.push(thread_num);
+            let pause = button(if *paused { "Resume" } else { "Pause" })
+                .on_press(Message::CrawlerSetPaused {
+                    server: sid,
+                    paused: !*paused,
+                });
+            let pause_text = match finished {
+                Some(time) => format!("Finished at {}", time.format("%H:%M")),
+                None if *paused => "Crawling paused".to_string(),
+                None => String::new(),
+            };
+            left_col = left_col.push(
+                row!(text(pause_text), horizontal_space(), pause)
+                    .align_items(Alignment::Center),
+            );
             let order_picker = pick_list(
                 [
                     CrawlingOrder::Random,
@@ -997,12 +1995,86 @@ pub fn view_crawling<'a>(
                     row!(text("Max Lvl: "), horizontal_space(), set_min_lvl)
                         .align_items(Alignment::Center);
                 left_col = left_col.push(thread_num);
+                left_col = left_col.push(
+                    text(
+                        "Characters outside of min/max are skipped and \
+                         restored, once the range includes them again",
+                    )
+                    .size(12),
+                );
+
+                let set_hard_min =
+                    number_input(lock.hard_min_level, 9999u32, move |nv| {
+                        Message::CrawlerSetHardMinLevel {
+                            server: sid,
+                            lvl: nv,
+                        }
+                    });
+                let hard_min = row!(
+                    text("Hard min Lvl: "),
+                    horizontal_space(),
+                    set_hard_min
+                )
+                .align_items(Alignment::Center);
+                left_col = left_col.push(hard_min);
+                left_col = left_col.push(
+                    text(
+                        "Characters below the hard min are never fetched. \
+                         Getting them back requires clearing the HoF",
+                    )
+                    .size(12),
+                );
+
+                let set_page_share = number_input(
+                    config.crawl_page_share,
+                    100u8,
+                    Message::SetCrawlPageShare,
+                );
+                let page_share = row!(
+                    text("Page share (%): "),
+                    horizontal_space(),
+                    set_page_share
+                )
+                .align_items(Alignment::Center);
+                left_col = left_col.push(page_share);
+            }
+
+            let diagnostics = text(format!(
+                "Invalid: {} pages, {} accounts. In flight: {}",
+                lock.invalid_pages.len(),
+                lock.invalid_accounts.len(),
+                lock.in_flight_pages.len() + lock.in_flight_accounts.len()
+            ))
+            .size(12);
+            left_col = left_col.push(diagnostics);
+
+            if !lock.invalid_accounts.is_empty() {
+                left_col = left_col.push(
+                    button("Requeue invalid accounts")
+                        .on_press(Message::CrawlerRequeueInvalid {
+                            server: sid,
+                            que_id: lock.que_id,
+                        })
+                        .style(theme::Button::Secondary),
+                );
             }
 
             let clear = button("Clear HoF").on_press(Message::ClearHof(sid));
             let save = button("Save HoF").on_press(Message::SaveHoF(sid));
+            let fetch = button(if server.fetching_backup {
+                "Fetching..."
+            } else {
+                "Fetch newest"
+            })
+            .on_press_maybe(
+                (!server.fetching_backup && server.backups_in_flight == 0)
+                    .then_some(Message::FetchNewestBackup {
+                        server: sid,
+                        que_id: lock.que_id,
+                    }),
+            );
             left_col = left_col.push(
-                column!(row!(clear, save).spacing(10))
+                column!(row!(clear, save, fetch).spacing(10))
                     .align_items(Alignment::Center),
             );
 
@@ -1011,13 +2083,36 @@ pub fn view_crawling<'a>(
         CrawlingStatus::Waiting => {
             left_col = left_col.push(text("Waiting for Player..."));
         }
-        CrawlingStatus::Restoring => {
+        CrawlingStatus::Restoring { done, total, .. } if *total > 0 => {
+            left_col = left_col.push(text(format!(
+                "Restoring {}/{}",
+                done.to_formatted_string(&config.num_format),
+                total.to_formatted_string(&config.num_format)
+            )));
+            let progress =
+                progress_bar(0.0..=*total as f32, (*done).min(*total) as f32)
+                    .height(Length::Fixed(10.0));
+            left_col = left_col.push(progress);
+        }
+        CrawlingStatus::Restoring { .. } => {
             left_col = left_col.push(text("Loading Server Data..."));
         }
         CrawlingStatus::CrawlingFailed(reason) => {
-            // TODO: Maybe display this?
-            _ = reason;
             left_col = left_col.push(text("Crawling Failed"));
+            left_col = left_col.push(
+                widget::scrollable(text(reason).size(12))
+                    .height(Length::Fixed(80.0)),
+            );
+            left_col = left_col.push(
+                row!(
+                    button("Retry crawling")
+                        .on_press(Message::RetryCrawling(server.ident.id)),
+                    button("Copy error")
+                        .on_press(Message::CopyCrawlingError(server.ident.id))
+                        .style(theme::Button::Secondary),
+                )
+                .spacing(10),
+            );
         }
     }
 
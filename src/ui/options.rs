@@ -1,20 +1,31 @@
 use iced::{
     Alignment, Element, Length,
-    widget::{checkbox, column, text, row, pick_list},
+    widget::{button, checkbox, column, pick_list, row, text, text_editor},
 };
 
 use crate::{
-    config::{Config, MissionStrategy},
+    config::{Config, MissionStrategy, SFCharIdent},
     message::Message,
     player::AccountInfo,
-    server::ServerInfo,
+    server::{ServerIdent, ServerInfo},
 };
 
 pub fn view_options<'a>(
     player: &'a AccountInfo,
     og_server: &'a ServerInfo,
     config: &'a Config,
+    notes: Option<&'a text_editor::Content>,
 ) -> Element<'a, Message> {
+    let lower_name = player.name.to_lowercase();
+    let others: Vec<_> = config
+        .char_idents()
+        .into_iter()
+        .filter(|a| {
+            ServerIdent::new(&a.server).id != og_server.ident.id
+                || a.name.to_lowercase().trim() != lower_name.trim()
+        })
+        .collect();
+
     let config = config.get_char_conf(&player.name, og_server.ident.id);
 
     let Some(config) = config else {
@@ -27,6 +38,33 @@ pub fn view_options<'a>(
 
     let mut all = column!().spacing(20).width(Length::Fixed(360.0));
 
+    if let (Some(offset), Some(skew)) =
+        (player.clock_offset, player.clock_skew())
+    {
+        // Both are measured in whole seconds, at slightly different times
+        let zone = ((offset - skew).num_seconds() as f64 / 60.0).round() as i64;
+        all = all.push(
+            text(format!(
+                "Server clock: {}{}:{:02} from local time, {:+}s skew",
+                if zone < 0 { '-' } else { '+' },
+                zone.abs() / 60,
+                zone.abs() % 60,
+                skew.num_seconds()
+            ))
+            .size(13),
+        );
+    }
+
+    all = all.push(
+        checkbox("Poll and automate this character", config.enabled).on_toggle(
+            |nv| Message::ConfigSetEnabled {
+                name: player.name.clone(),
+                server: og_server.ident.id,
+                nv,
+            },
+        ),
+    );
+
     all = all.push(
         checkbox("Automatically login on startup", config.login).on_toggle(
             |nv| Message::ConfigSetAutoLogin {
@@ -77,7 +115,7 @@ pub fn view_options<'a>(
 
     all = all.push(
         row![
-            checkbox("Dungeons", config.auto_dungeons).on_toggle(|nv| Message::ConfigSetAutoDungeons {
+            checkbox("Dungeons", config.any_dungeon_fights()).on_toggle(|nv| Message::ConfigSetAutoDungeons {
                 name: player.name.clone(),
                 server: og_server.ident.id,
                 nv,
@@ -130,6 +168,49 @@ pub fn view_options<'a>(
 
     // Reserve mushrooms removed: we save all mushrooms by default and only spend if a specific budget is enabled
 
+    if let Some(notes) = notes {
+        let ident = player.ident;
+        all = all.push(text("Notes").size(18));
+        all = all.push(
+            text_editor(notes)
+                .on_action(move |action| Message::NotesEdited { ident, action })
+                .height(Length::Fixed(100.0)),
+        );
+    }
+
+    if !others.is_empty() {
+        all = all.push(text("Copy settings to").size(18));
+        for other in others {
+            let selected = player.config_copy_targets.contains(&other);
+            let label = format!(
+                "{} ({})",
+                other.name,
+                other
+                    .server
+                    .trim_start_matches("https://")
+                    .trim_end_matches('/')
+            );
+            all = all.push(checkbox(label, selected).on_toggle(move |nv| {
+                Message::SetConfigCopyTarget {
+                    ident: player.ident,
+                    target: other.clone(),
+                    nv,
+                }
+            }));
+        }
+        let mut copy = button("Copy settings");
+        if !player.config_copy_targets.is_empty() {
+            copy = copy.on_press(Message::CopyCharConfigTo {
+                from: SFCharIdent {
+                    name: player.name.clone(),
+                    server: og_server.ident.url.clone(),
+                },
+                to: player.config_copy_targets.iter().cloned().collect(),
+            });
+        }
+        all = all.push(copy);
+    }
+
     column!(all)
         .padding(20)
         .height(Length::Fill)
@@ -6,19 +6,20 @@ use iced::{
     alignment::Horizontal,
     theme,
     widget::{
-        Image, button, checkbox, column, horizontal_space, row, scrollable,
-        text, vertical_space,
+        Image, button, checkbox, column, horizontal_space, pick_list, row,
+        scrollable, text, text_input, tooltip, vertical_space,
     },
 };
 use iced_aw::number_input;
 use num_format::ToFormattedString;
+use sf_api::gamestate::unlockables::EquipmentIdent;
 
-use super::{remaining_minutes, view_crawling};
+use super::{remaining_minutes, remaining_seconds, view_crawling};
 use crate::{
     ClassImages,
-    config::Config,
+    config::{BattleOrdering, Config},
     message::Message,
-    player::{AccountInfo, AccountStatus},
+    player::{AccountInfo, AccountStatus, MANUAL_BLACKLIST},
     server::ServerInfo,
 };
 
@@ -40,6 +41,27 @@ pub fn view_scrapbook<'a>(
         AccountStatus::LoggingInAgain => {
             return text("Logging in again".to_string()).size(20).into();
         }
+        AccountStatus::Relogging { attempt } => {
+            return text(format!("Logging in again (attempt {})", attempt + 1))
+                .size(20)
+                .into();
+        }
+        AccountStatus::WaitingToRetry { until, error } => {
+            return text(format!(
+                "Retrying login in {}: {error}",
+                remaining_minutes(*until, Local::now())
+            ))
+            .size(20)
+            .into();
+        }
+        AccountStatus::LoggedOutIdle { until } => {
+            return text(format!(
+                "Logged out while idle, logging in again in {}",
+                remaining_minutes(*until, Local::now())
+            ))
+            .size(20)
+            .into();
+        }
     };
 
     let Some(si) = &player.scrapbook_info else {
@@ -115,12 +137,20 @@ pub fn view_scrapbook<'a>(
             .align_items(Alignment::Center);
     left_col = left_col.push(max_attributes);
 
+    let ordering =
+        pick_list(BattleOrdering::all(), Some(si.battle_ordering), move |nv| {
+            Message::PlayerSetBattleOrdering { ident: aid, nv }
+        });
+    let ordering = row!(text("Order:"), horizontal_space(), ordering)
+        .align_items(Alignment::Center);
+    left_col = left_col.push(ordering);
+
     match &gs.arena.next_free_fight {
-        Some(x) if *x >= Local::now() => {
+        Some(x) if *x >= player.server_now() => {
             let t = text("Next free fight:");
             let r = row!(
                 t.width(Length::FillPortion(1)),
-                text(remaining_minutes(*x))
+                text(remaining_seconds(*x, player.server_now()))
                     .width(Length::FillPortion(1))
                     .horizontal_alignment(Horizontal::Right)
             );
@@ -138,16 +168,86 @@ pub fn view_scrapbook<'a>(
             .size(20),
     );
 
+    left_col = left_col.push(
+        checkbox("Farm with mushrooms", si.farming)
+            .on_toggle(|a| Message::PlayerSetFarming {
+                ident: player.ident,
+                nv: a,
+            })
+            .size(20),
+    );
+    if si.farming {
+        left_col = left_col.push(text(format!(
+            "Farming: {} mushrooms spent",
+            si.farming_spent
+        )));
+    }
+
     left_col = left_col.push(button("Copy Optimal Battle Order").on_press(
         Message::CopyBattleOrder {
             ident: player.ident,
         },
     ));
 
+    left_col = left_col.push(
+        row!(
+            text_input("Player name", &si.fight_name_input)
+                .on_input(|nv| Message::ScrapbookFightNameInput {
+                    ident: player.ident,
+                    nv,
+                })
+                .on_submit(Message::PlayerAttackByName {
+                    ident: player.ident,
+                }),
+            button("Fight").on_press(Message::PlayerAttackByName {
+                ident: player.ident,
+            }),
+        )
+        .spacing(5)
+        .align_items(Alignment::Center),
+    );
+
+    if !player.manual_attack_queue.is_empty() {
+        let names: Vec<_> = player
+            .manual_attack_queue
+            .iter()
+            .map(|a| a.info.name.as_str())
+            .collect();
+        left_col = left_col.push(
+            row!(
+                text(format!("Queued: {}", names.join(", "))),
+                horizontal_space(),
+                button("Clear").on_press(Message::ClearManualAttacks {
+                    ident: player.ident,
+                }),
+            )
+            .spacing(5)
+            .align_items(Alignment::Center),
+        );
+    }
+
+    if let Some(stats) = config
+        .get_char_conf(&player.name, server.ident.id)
+        .map(|a| a.stats)
+    {
+        for (name, val) in [
+            ("Fights won:", stats.wins),
+            ("Fights lost:", stats.losses),
+            ("Items gained:", stats.items_gained),
+        ] {
+            left_col = left_col.push(row!(
+                text(name).width(Length::FillPortion(1)),
+                text(val.to_formatted_string(&config.num_format))
+                    .width(Length::FillPortion(1))
+                    .horizontal_alignment(Horizontal::Right),
+            ));
+        }
+    }
+
     if !si.attack_log.is_empty() {
         let mut log = column!().padding(5).spacing(5);
 
-        for (time, target, won) in si.attack_log.iter().rev() {
+        for (time, target, won, _) in si.attack_log.iter().rev() {
             let time = text(format!("{}", time.time().format("%H:%M")));
             let mut info = target.info.name.to_string();
             if *won {
@@ -164,10 +264,79 @@ pub fn view_scrapbook<'a>(
         }
 
         left_col = left_col.push(scrollable(log).height(Length::Fixed(200.0)));
+        left_col = left_col.push(button("Export Attack Log").on_press(
+            Message::ExportAttackLog {
+                ident: player.ident,
+            },
+        ));
+    }
+
+    left_col = left_col.push(
+        row!(
+            button("Export Scrapbook").on_press(Message::ExportScrapbook {
+                ident: player.ident,
+            }),
+            button("Import Scrapbook").on_press(Message::ImportScrapbook {
+                ident: player.ident,
+            }),
+        )
+        .spacing(10),
+    );
+
+    left_col = left_col.push(text("Blacklist").size(18));
+    left_col = left_col.push(
+        row!(
+            text_input("Player name", &si.blacklist_input)
+                .on_input(|nv| Message::ScrapbookBlacklistInput {
+                    ident: player.ident,
+                    nv,
+                })
+                .on_submit(Message::ScrapbookBlacklistAdd {
+                    ident: player.ident,
+                }),
+            button("Add").on_press(Message::ScrapbookBlacklistAdd {
+                ident: player.ident,
+            }),
+        )
+        .spacing(5)
+        .align_items(Alignment::Center),
+    );
+
+    if !si.blacklist.is_empty() {
+        let mut blacklisted: Vec<_> = si.blacklist.iter().collect();
+        blacklisted.sort_by(|a, b| a.1.0.cmp(&b.1.0));
+
+        let mut list = column!().padding(5).spacing(5);
+        for (uid, (name, lost)) in blacklisted {
+            let lost = match *lost {
+                MANUAL_BLACKLIST => "manual".to_string(),
+                lost => format!("{lost} lost"),
+            };
+            let remove = button(
+                iced_aw::core::icons::bootstrap::icon_to_text(
+                    iced_aw::Bootstrap::X,
+                )
+                .size(14.0),
+            )
+            .on_press(Message::ScrapbookBlacklistRemove {
+                ident: player.ident,
+                uid: *uid,
+            })
+            .style(theme::Button::Destructive)
+            .padding(2);
+            list = list.push(
+                row!(text(name), horizontal_space(), text(lost), remove)
+                    .spacing(10)
+                    .align_items(Alignment::Center),
+            );
+        }
+        left_col =
+            left_col.push(scrollable(list).height(Length::Fixed(120.0)));
     }
+
     left_col = left_col.push(vertical_space());
 
-    left_col = left_col.push(view_crawling(server, config));
+    left_col = left_col.push(view_crawling(server, player, config));
 
     let mut name_bar = column!();
     name_bar = name_bar.push(row!(
@@ -210,6 +379,17 @@ pub fn view_scrapbook<'a>(
                 .horizontal_alignment(Horizontal::Left),
         );
 
+        // The items this fight would add to the scrapbook
+        let mut gained = column!().spacing(2);
+        for eq in &v.info.equipment {
+            if !si.scrapbook.items.contains(eq) {
+                gained = gained.push(text(item_name(eq)).size(13));
+            }
+        }
+        let target_ident =
+            tooltip(target_ident, gained, tooltip::Position::Left)
+                .style(theme::Container::Box);
+
         target_list = target_list.push(row!(
             column!(button("Attack").on_press(Message::PlayerAttack {
                 ident: player.ident,
@@ -235,7 +415,29 @@ pub fn view_scrapbook<'a>(
         ));
     }
     let target_list = scrollable(target_list);
-    let right_col = column!(name_bar, target_list).spacing(10);
+
+    let switch = |label, show_missing| {
+        button(label)
+            .on_press(Message::ScrapbookShowMissing {
+                ident: player.ident,
+                val: show_missing,
+            })
+            .padding(4)
+            .style(if si.show_missing == show_missing {
+                theme::Button::Primary
+            } else {
+                theme::Button::Secondary
+            })
+    };
+    let switch = row!(switch("Targets", false), switch("Rarest missing", true))
+        .spacing(5);
+
+    let right_col = if si.show_missing {
+        column!(switch, view_rarest_missing(&si.rarest_missing, config))
+    } else {
+        column!(switch, name_bar, target_list)
+    }
+    .spacing(10);
 
     row!(
         left_col.width(Length::Fixed(200.0)),
@@ -246,3 +448,43 @@ pub fn view_scrapbook<'a>(
     .align_items(Alignment::Start)
     .into()
 }
+
+fn item_name(eq: &EquipmentIdent) -> String {
+    let mut name = format!("{:?} {}", eq.typ, eq.model_id);
+    if let Some(class) = eq.class {
+        _ = write!(name, " ({class:?})");
+    }
+    name
+}
+
+fn view_rarest_missing<'a>(
+    rarest: &'a [(EquipmentIdent, usize)],
+    config: &'a Config,
+) -> Element<'a, Message> {
+    if rarest.is_empty() {
+        return text("No missing items have been crawled yet").into();
+    }
+
+    let name_bar = row!(
+        text("Owners")
+            .width(Length::FillPortion(5))
+            .horizontal_alignment(Horizontal::Center),
+        text("Item")
+            .width(Length::FillPortion(20))
+            .horizontal_alignment(Horizontal::Left),
+    );
+
+    let mut list = column!().spacing(10);
+    for (eq, owners) in rarest {
+        list = list.push(row!(
+            text(owners.to_formatted_string(&config.num_format))
+                .width(Length::FillPortion(5))
+                .horizontal_alignment(Horizontal::Center),
+            text(item_name(eq))
+                .width(Length::FillPortion(20))
+                .horizontal_alignment(Horizontal::Left),
+        ));
+    }
+
+    column!(name_bar, scrollable(list)).spacing(10).into()
+}
@@ -1,20 +1,24 @@
+use chrono::Local;
+
 use iced::{
     Alignment, Element, Length,
     alignment::Horizontal,
     theme,
     widget::{
-        Image, button, checkbox, column, horizontal_space, row, scrollable,
-        text, vertical_space,
+        Image, button, checkbox, column, horizontal_space, pick_list, row,
+        scrollable, text, vertical_space,
     },
 };
 use iced_aw::number_input;
+use num_format::ToFormattedString;
+use sf_api::misc::EnumMapGet;
 
-use super::view_crawling;
+use super::{remaining_minutes, view_crawling};
 use crate::{
     ClassImages,
-    config::Config,
+    config::{Config, LureExportFormat},
     message::Message,
-    player::{AccountInfo, AccountStatus},
+    player::{AccountInfo, AccountStatus, MAX_LURES_PER_DAY},
     server::ServerInfo,
 };
 
@@ -35,6 +39,30 @@ pub fn view_underworld<'a>(
         AccountStatus::LoggingInAgain => {
             return text("Logging in player again".to_string()).size(20).into();
         }
+        AccountStatus::Relogging { attempt } => {
+            return text(format!(
+                "Logging in player again (attempt {})",
+                attempt + 1
+            ))
+            .size(20)
+            .into();
+        }
+        AccountStatus::WaitingToRetry { until, error } => {
+            return text(format!(
+                "Retrying login in {}: {error}",
+                remaining_minutes(*until, Local::now())
+            ))
+            .size(20)
+            .into();
+        }
+        AccountStatus::LoggedOutIdle { until } => {
+            return text(format!(
+                "Logged out while idle, logging in again in {}",
+                remaining_minutes(*until, Local::now())
+            ))
+            .size(20)
+            .into();
+        }
     };
 
     let Some(info) = &player.underworld_info else {
@@ -46,9 +74,12 @@ pub fn view_underworld<'a>(
     let mut left_col = column!().align_items(Alignment::Center).spacing(10);
     left_col = left_col.push(row!(
         text("Lured Today:").width(Length::FillPortion(1)),
-        text(format!("{}/5", info.underworld.lured_today))
-            .width(Length::FillPortion(1))
-            .horizontal_alignment(Horizontal::Right),
+        text(format!(
+            "{}/{MAX_LURES_PER_DAY}",
+            info.underworld.lured_today
+        ))
+        .width(Length::FillPortion(1))
+        .horizontal_alignment(Horizontal::Right),
     ));
 
     let souls = info.underworld.souls_current;
@@ -96,11 +127,91 @@ pub fn view_underworld<'a>(
             })
             .size(20),
     );
-    left_col = left_col.push(button("Copy Targets").on_press(
-        Message::CopyBestLures {
-            ident: player.ident,
-        },
-    ));
+    if let Some(cc) = config.get_char_conf(&player.name, server.ident.id) {
+        left_col = left_col.push(
+            checkbox("Auto collect souls & lure", cc.auto_underworld_souls)
+                .on_toggle(|nv| Message::ConfigSetAutoUnderworldSouls {
+                    name: player.name.clone(),
+                    server: server.ident.id,
+                    nv,
+                })
+                .size(20),
+        );
+    }
+    let export_format = pick_list(
+        LureExportFormat::all(),
+        Some(info.export_format),
+        move |nv| Message::PlayerSetLureExportFormat { ident: aid, nv },
+    );
+    left_col = left_col.push(
+        row!(
+            button("Copy Targets").on_press(Message::CopyBestLures {
+                ident: player.ident,
+            }),
+            horizontal_space(),
+            export_format
+        )
+        .align_items(Alignment::Center),
+    );
+    left_col =
+        left_col.push(checkbox("Copy old targets", info.export_old).on_toggle(
+            move |nv| Message::PlayerSetLureExportOld { ident: aid, nv },
+        ));
+
+    if let Some(cfg) = config.get_char_conf(&player.name, server.ident.id) {
+        left_col = left_col.push(
+            checkbox("Auto Upgrade Buildings", cfg.auto_underworld_upgrade)
+                .on_toggle(|nv| Message::ConfigSetAutoUnderworldUpgrade {
+                    name: player.name.clone(),
+                    server: server.ident.id,
+                    nv,
+                })
+                .size(20),
+        );
+        if cfg.auto_underworld_upgrade {
+            let mut order = column!().spacing(5);
+            for (pos, building) in
+                cfg.underworld_upgrade_order().into_iter().enumerate()
+            {
+                let level = info.underworld.buildings.get(building).level;
+                let mut up = button(
+                    iced_aw::core::icons::bootstrap::icon_to_text(
+                        iced_aw::Bootstrap::CaretUpFill,
+                    )
+                    .size(14.0),
+                )
+                .style(theme::Button::Text)
+                .padding(0.0);
+                if pos > 0 {
+                    up = up.on_press(Message::ConfigUnderworldUpgradeUp {
+                        name: player.name.clone(),
+                        server: server.ident.id,
+                        building,
+                    });
+                }
+                order = order.push(
+                    row!(
+                        text(format!("{}. {building:?}", pos + 1)),
+                        horizontal_space(),
+                        text(format!("Lvl {level}")),
+                        up,
+                    )
+                    .spacing(5)
+                    .align_items(Alignment::Center),
+                );
+            }
+            left_col = left_col.push(order);
+        }
+    }
+
+    if let Some(cfg) = config.get_char_conf(&player.name, server.ident.id) {
+        left_col = left_col.push(row!(
+            text("Lures done:").width(Length::FillPortion(1)),
+            text(cfg.stats.lures_done.to_formatted_string(&config.num_format))
+                .width(Length::FillPortion(1))
+                .horizontal_alignment(Horizontal::Right),
+        ));
+    }
 
     if !info.attack_log.is_empty() {
         let mut log = column!().padding(5).spacing(5);
@@ -121,7 +232,7 @@ pub fn view_underworld<'a>(
     }
 
     left_col = left_col.push(vertical_space());
-    left_col = left_col.push(view_crawling(server, config));
+    left_col = left_col.push(view_crawling(server, player, config));
 
     let mut name_bar = column!();
     name_bar = name_bar.push(row!(
@@ -163,7 +274,7 @@ pub fn view_underworld<'a>(
 
         target_list = target_list.push(row!(
             column!(button("Lure").on_press_maybe(
-                if info.underworld.lured_today >= 5 {
+                if info.underworld.lured_today >= MAX_LURES_PER_DAY {
                     None
                 } else {
                     Some(Message::PlayerLure {